@@ -1,6 +1,7 @@
 use ray_tracer::{Canvas, Color, Shape, Point, PointLight, Ray, intersection, Material};
 use std::fs::File;
 
+#[allow(clippy::single_match)]
 fn main() {
     let ray_orign = Point::new(0.0, 0.0, -15.0);
     let wall_z = 10.0;
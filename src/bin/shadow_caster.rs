@@ -1,6 +1,7 @@
 use ray_tracer::{Canvas, Color, Shape, Point, Ray, intersection};
 use std::fs::File;
 
+#[allow(clippy::single_match)]
 fn main() {
     let ray_orign = Point::new(0.0, 0.0, -5.0);
     let wall_z = 10.0;
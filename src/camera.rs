@@ -1,4 +1,4 @@
-use crate::{Matrix4x4, Point, Ray, World, Canvas};
+use crate::{Color, Matrix4x4, Point, Ray, World, Canvas};
 
 pub struct Camera {
     pub hsize: usize,
@@ -8,7 +8,10 @@ pub struct Camera {
     pub half_width: f64,
     pub pixel_size: f64,
     pub transform: Matrix4x4,
-    pub inverse_transform: Matrix4x4
+    pub inverse_transform: Matrix4x4,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    pub time_samples: usize
 }
 
 impl Camera {
@@ -32,7 +35,10 @@ impl Camera {
             half_width,
             pixel_size,
             transform: Matrix4x4::identity(),
-            inverse_transform: Matrix4x4::identity()
+            inverse_transform: Matrix4x4::identity(),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            time_samples: 1
         }
     }
 
@@ -46,6 +52,36 @@ impl Camera {
         }
     }
 
+    // Keeps the shutter open from time `open` to `close`, so that moving
+    // shapes blur across the times in between.
+    pub fn shutter(self, open: f64, close: f64) -> Camera {
+        Camera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
+
+    // How many rays each pixel casts across the shutter interval. Every
+    // pixel casts at least one.
+    pub fn time_samples(self, time_samples: usize) -> Camera {
+        Camera {
+            time_samples: time_samples.max(1),
+            ..self
+        }
+    }
+
+    // The times of each pixel's rays, spread evenly through the shutter
+    // interval.
+    pub fn sample_times(&self) -> Vec<f64> {
+        (0..self.time_samples).
+            map(|i| {
+                let fraction = (i as f64 + 0.5) / self.time_samples as f64;
+                self.shutter_open + (self.shutter_close - self.shutter_open) * fraction
+            }).
+            collect()
+    }
+
     pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
         let xoffset = (px as f64 + 0.5) * self.pixel_size;
         let yoffset = (py as f64 + 0.5) * self.pixel_size;
@@ -56,17 +92,21 @@ impl Camera {
         let origin = self.inverse_transform * Point::origin();
         let direction = (pixel - origin).normalize();
 
-        Ray::new(origin, direction)
+        Ray::new(origin, direction).time(self.shutter_open)
     }
 
     pub fn render(&self, world: &World) -> Canvas {
         let mut canvas = Canvas::new(self.hsize, self.vsize);
+        let times = self.sample_times();
 
         for y in 0..self.vsize  {
             for x in 0..self.hsize  {
                 let ray = self.ray_for_pixel(x, y);
-                let color = world.color_at(ray);
-                canvas.set(x, y, color);
+                let color = times.
+                    iter().
+                    fold(Color::black(), |color, time| color + world.color_at(ray.time(*time)));
+
+                canvas.set(x, y, color * (1.0 / times.len() as f64));
             }
         }
 
@@ -139,4 +179,36 @@ mod tests {
         let image = c.render(&w);
         assert_eq!(Color::new(0.38066, 0.47583, 0.2855), image.get(5, 5))
     }
+
+    #[test]
+    fn rays_are_spread_across_the_shutter_interval() {
+        let c = Camera::new(201, 101, PI / 2.0).shutter(0.0, 1.0).time_samples(4);
+        assert_eq!(vec![0.125, 0.375, 0.625, 0.875], c.sample_times());
+        assert_eq!(0.0, c.ray_for_pixel(100, 50).time);
+        assert_eq!(vec![0.0], Camera::new(201, 101, PI / 2.0).sample_times());
+        assert_eq!(vec![0.5], Camera::new(201, 101, PI / 2.0).shutter(0.0, 1.0).time_samples(0).sample_times());
+    }
+
+    #[test]
+    fn moving_shapes_blur_across_the_shutter_interval() {
+        let w = World::default_world();
+        let w = World {
+            objects: vec![
+                w.objects[0].clone().motion(Matrix4x4::identity().translate(0.0, 0.0, 10.0))
+            ],
+            ..w
+        };
+        let from = Point::new(0.0, 0.0, -5.0);
+        let to = Point::new(0.0, 0.0, 0.0);
+        let up = Vector::new(0.0, 1.0, 0.0);
+        let c = Camera::new(11, 11, PI / 2.0).transform(view_transform(from, to, up));
+
+        let still = c.render(&w).get(5, 5);
+        let times = [0.25, 0.75].map(|time| w.color_at(c.ray_for_pixel(5, 5).time(time)));
+        let blurred = c.shutter(0.0, 1.0).time_samples(2).render(&w).get(5, 5);
+
+        assert_eq!(Color::new(0.38066, 0.47583, 0.2855), still);
+        assert_eq!((times[0] + times[1]) * 0.5, blurred);
+        assert!(times[0] != times[1]);
+    }
 }
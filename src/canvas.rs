@@ -23,6 +23,7 @@ impl Canvas {
         self.pixels[y * self.width + x] = c
     }
 
+    #[allow(clippy::explicit_counter_loop, clippy::write_with_newline)]
     pub fn to_ppm(&self, out: & mut dyn std::io::Write) -> std::io::Result<()> {
         let last = self.pixels.last().unwrap();
         let mut count = 0;
@@ -45,6 +46,78 @@ impl Canvas {
         std::io::Result::Ok(())
     }
 
+    // Reads an image in any of the plain (P2, P3) or raw (P5, P6) PGM and
+    // PPM formats. Greyscale images are expanded to equal r, g and b values.
+    pub fn from_ppm(input: &mut dyn std::io::Read) -> std::io::Result<Canvas> {
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+
+        let mut reader = PpmReader { bytes: &bytes, position: 0 };
+        let magic = reader.token()?;
+        let channels = match magic.as_str() {
+            "P2" | "P5" => 1,
+            "P3" | "P6" => 3,
+            _ => return Err(invalid_ppm(&format!("unsupported format {}", magic)))
+        };
+        let binary = magic == "P5" || magic == "P6";
+
+        let width = reader.number()?;
+        let height = reader.number()?;
+        let max_value = reader.number()?;
+
+        if max_value == 0 || max_value > 65535 {
+            return Err(invalid_ppm(&format!("invalid maximum value {}", max_value)));
+        }
+
+        if binary {
+            // Exactly one whitespace byte separates the header from the data.
+            reader.position += 1;
+        }
+
+        // The size comes from the file, so it is checked against the data
+        // that follows before anything is allocated. Binary samples take
+        // one or two bytes each, and plain ones at least one digit.
+        let sample_size = if binary && max_value > 255 { 2 } else { 1 };
+        let remaining = bytes.len().saturating_sub(reader.position);
+        let needed = width.
+            checked_mul(height).
+            and_then(|n| n.checked_mul(channels * sample_size));
+
+        match needed {
+            Some(needed) if needed <= remaining => (),
+            _ => return Err(invalid_ppm(&format!("too little data for a {}x{} image", width, height)))
+        }
+
+        let mut canvas = Canvas::new(width, height);
+
+        for pixel in canvas.pixels.iter_mut() {
+            let mut samples = [0.0; 3];
+
+            for sample in samples.iter_mut().take(channels) {
+                let value = if binary {
+                    reader.binary_sample(max_value > 255)?
+                } else {
+                    reader.number()?
+                };
+
+                if value > max_value {
+                    return Err(invalid_ppm(&format!("sample {} is above the maximum value {}", value, max_value)));
+                }
+
+                *sample = value as f64 / max_value as f64;
+            }
+
+            *pixel = if channels == 1 {
+                Color::new(samples[0], samples[0], samples[0])
+            } else {
+                Color::new(samples[0], samples[1], samples[2])
+            };
+        }
+
+        Ok(canvas)
+    }
+
+    #[allow(clippy::manual_clamp, clippy::unnecessary_cast)]
     fn write_pixel(pixel: &Color, out: & mut dyn std::io::Write) -> std::io::Result<()> {
         let r = (pixel.r.max(0.0).min(1.0) as f64 * 255.0) as i64;
         let g = (pixel.g.max(0.0).min(1.0) as f64 * 255.0) as i64;
@@ -54,6 +127,56 @@ impl Canvas {
     }
 }
 
+fn invalid_ppm(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid PPM: {}", message))
+}
+
+struct PpmReader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl PpmReader<'_> {
+    // Reads the next whitespace separated token, skipping `#` comments.
+    fn token(&mut self) -> std::io::Result<String> {
+        loop {
+            match self.bytes.get(self.position) {
+                Some(b'#') => {
+                    while !matches!(self.bytes.get(self.position), None | Some(b'\n')) {
+                        self.position += 1;
+                    }
+                },
+                Some(b) if b.is_ascii_whitespace() => self.position += 1,
+                Some(_) => break,
+                None => return Err(invalid_ppm("unexpected end of file"))
+            }
+        }
+
+        let start = self.position;
+
+        while matches!(self.bytes.get(self.position), Some(b) if !b.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.position]).into_owned())
+    }
+
+    fn number(&mut self) -> std::io::Result<usize> {
+        let token = self.token()?;
+        token.parse().map_err(|_| invalid_ppm(&format!("expected a number but found {}", token)))
+    }
+
+    fn binary_sample(&mut self, wide: bool) -> std::io::Result<usize> {
+        let size = if wide { 2 } else { 1 };
+        let bytes = self.bytes.
+            get(self.position..self.position + size).
+            ok_or_else(|| invalid_ppm("unexpected end of file"))?;
+        self.position += size;
+
+        Ok(bytes.iter().fold(0, |acc, b| acc * 256 + *b as usize))
+    }
+}
+
 impl IntoIterator for Canvas {
     type Item = Color;
     type IntoIter = std::vec::IntoIter<Self::Item>;
@@ -123,4 +246,63 @@ mod tests {
 
         assert_eq!("P3\n2 2\n255\n255 255 255\n0 0 0 0 0 0 0 0 0\n", s)
     }
+
+    #[test]
+    fn reading_a_plain_ppm() {
+        let ppm = "P3\n# a comment\n2 2\n255\n255 255 255 0 0 0\n0 51 0 # trailing\n0 0 255\n";
+        let c = Canvas::from_ppm(&mut ppm.as_bytes()).unwrap();
+
+        assert_eq!(2, c.width);
+        assert_eq!(2, c.height);
+        assert_eq!(Color::white(), c.get(0, 0));
+        assert_eq!(Color::black(), c.get(1, 0));
+        assert_eq!(Color::new(0.0, 0.2, 0.0), c.get(0, 1));
+        assert_eq!(Color::new(0.0, 0.0, 1.0), c.get(1, 1));
+    }
+
+    #[test]
+    fn reading_back_a_written_ppm() {
+        let mut c = Canvas::new(3, 2);
+        c.set(2, 1, Color::new(1.0, 0.0, 1.0));
+
+        let mut io: Vec<u8> = Vec::new();
+        c.to_ppm(&mut io).unwrap();
+        let read = Canvas::from_ppm(&mut io.as_slice()).unwrap();
+
+        assert_eq!(c.into_iter().collect::<Vec<Color>>(), read.into_iter().collect::<Vec<Color>>());
+    }
+
+    #[test]
+    fn reading_a_greyscale_pgm() {
+        let c = Canvas::from_ppm(&mut "P2 2 1 10 5 10".as_bytes()).unwrap();
+        assert_eq!(Color::new(0.5, 0.5, 0.5), c.get(0, 0));
+        assert_eq!(Color::white(), c.get(1, 0));
+    }
+
+    #[test]
+    fn reading_binary_images() {
+        let mut pgm = b"P5\n2 1\n255\n".to_vec();
+        pgm.extend([0, 255]);
+        let c = Canvas::from_ppm(&mut pgm.as_slice()).unwrap();
+        assert_eq!(Color::black(), c.get(0, 0));
+        assert_eq!(Color::white(), c.get(1, 0));
+
+        let mut ppm = b"P6 1 1 65535 ".to_vec();
+        ppm.extend([0xff, 0xff, 0x80, 0x00, 0x00, 0x00]);
+        let c = Canvas::from_ppm(&mut ppm.as_slice()).unwrap();
+        assert_eq!(Color::new(1.0, 0.50001, 0.0), c.get(0, 0));
+    }
+
+    #[test]
+    fn reading_an_invalid_ppm() {
+        assert!(Canvas::from_ppm(&mut "P7 1 1 255 0 0 0".as_bytes()).is_err());
+        assert!(Canvas::from_ppm(&mut "P3 2 1 255 0 0 0".as_bytes()).is_err());
+        assert!(Canvas::from_ppm(&mut "P3 x 1 255".as_bytes()).is_err());
+        assert!(Canvas::from_ppm(&mut "P6 1 1 255 ab".as_bytes()).is_err());
+        assert!(Canvas::from_ppm(&mut "P3 1 1 10 0 11 0".as_bytes()).is_err());
+        assert!(Canvas::from_ppm(&mut "P5 4294967296 4294967296 255 ab".as_bytes()).is_err());
+        assert!(Canvas::from_ppm(&mut "P5 100000 100000 255 ab".as_bytes()).is_err());
+        assert!(Canvas::from_ppm(&mut b"P5 2 1 256 \x00\x01\x00".as_slice()).is_err());
+        assert!(Canvas::from_ppm(&mut b"P5 1 1 10 \x0b".as_slice()).is_err());
+    }
 }
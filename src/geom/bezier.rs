@@ -0,0 +1,465 @@
+// Bicubic Bézier patches, defined by a 4x4 grid of control points.
+//
+// A patch can be turned into a group of smooth triangles with `tessellate`,
+// or intersected directly with `Shape::bezier_patch`. Direct intersection
+// finds a starting guess on a coarse tessellation of the patch and then
+// refines it with Newton's method on the true surface.
+
+use super::{Bounds, ShapeType};
+use crate::{ParseError, Point, Ray, Shape, Vector};
+
+const EPSILON: f64 = 0.0001;
+
+// The number of divisions along each side of the coarse grid used to seed
+// direct intersections.
+const GUIDE_DIVISIONS: usize = 8;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BezierPatch {
+    points: [Point; 16],
+    guide: Vec<Point>
+}
+
+fn bernstein(t: f64) -> [f64; 4] {
+    let s = 1.0 - t;
+    [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t]
+}
+
+fn bernstein_derivative(t: f64) -> [f64; 4] {
+    let s = 1.0 - t;
+    [-3.0 * s * s, 3.0 * s * s - 6.0 * t * s, 6.0 * t * s - 3.0 * t * t, 3.0 * t * t]
+}
+
+impl BezierPatch {
+    // Control points are given row by row, with u running along each row
+    // and v running down the rows.
+    pub fn new(points: [Point; 16]) -> BezierPatch {
+        let mut patch = BezierPatch {
+            points,
+            guide: vec![]
+        };
+
+        patch.guide = Self::grid(GUIDE_DIVISIONS).
+            map(|(u, v)| patch.point_at(u, v)).
+            collect();
+
+        patch
+    }
+
+    pub fn control_point(&self, row: usize, column: usize) -> Point {
+        self.points[row * 4 + column]
+    }
+
+    // Parameter values for a regular grid of (divisions + 1)^2 points,
+    // row by row.
+    fn grid(divisions: usize) -> impl Iterator<Item = (f64, f64)> {
+        (0..=divisions).flat_map(move |j| {
+            (0..=divisions).map(move |i| (i as f64 / divisions as f64, j as f64 / divisions as f64))
+        })
+    }
+
+    fn blend(&self, bu: [f64; 4], bv: [f64; 4]) -> Vector {
+        let mut sum = Vector::new(0.0, 0.0, 0.0);
+
+        for (row, v_weight) in bv.iter().enumerate() {
+            for (column, u_weight) in bu.iter().enumerate() {
+                sum = sum + (self.control_point(row, column) - Point::origin()) * (u_weight * v_weight);
+            }
+        }
+
+        sum
+    }
+
+    pub fn point_at(&self, u: f64, v: f64) -> Point {
+        Point::origin() + self.blend(bernstein(u), bernstein(v))
+    }
+
+    // A patch lies within the convex hull of its control points.
+    pub fn bounds(&self) -> Bounds {
+        Bounds::from_points(self.points)
+    }
+
+    // The partial derivatives of the surface along u and v.
+    pub fn derivatives(&self, u: f64, v: f64) -> (Vector, Vector) {
+        (
+            self.blend(bernstein_derivative(u), bernstein(v)),
+            self.blend(bernstein(u), bernstein_derivative(v))
+        )
+    }
+
+    // Where a patch pinches to a single point, as at the top of the
+    // teapot's lid, one derivative vanishes. The normal there is taken from
+    // just inside the patch instead.
+    pub fn normal_at_uv(&self, u: f64, v: f64) -> Vector {
+        let (du, dv) = self.derivatives(u, v);
+        let normal = du.cross(dv);
+
+        if normal.magnitude() > EPSILON {
+            return normal.normalize();
+        }
+
+        let nudge = |t: f64| t + (0.5 - t) * 0.001;
+        let (du, dv) = self.derivatives(nudge(u), nudge(v));
+        du.cross(dv).normalize()
+    }
+
+    // Finds the surface parameters of a point on the patch, starting from
+    // the nearest point of the guide grid.
+    pub fn uv_of(&self, point: Point) -> (f64, f64) {
+        let (index, _) = self.guide.
+            iter().
+            map(|p| (*p - point).magnitude()).
+            enumerate().
+            fold((0, f64::INFINITY), |best, (i, d)| if d < best.1 { (i, d) } else { best });
+
+        let mut u = (index % (GUIDE_DIVISIONS + 1)) as f64 / GUIDE_DIVISIONS as f64;
+        let mut v = (index / (GUIDE_DIVISIONS + 1)) as f64 / GUIDE_DIVISIONS as f64;
+
+        // Gauss-Newton steps towards the closest point on the surface.
+        for _ in 0..10 {
+            let (du, dv) = self.derivatives(u, v);
+            let offset = point - self.point_at(u, v);
+            let (a, b, c) = (du.dot(du), du.dot(dv), dv.dot(dv));
+            let det = a * c - b * b;
+
+            if det.abs() < 1e-12 {
+                break;
+            }
+
+            let (ru, rv) = (du.dot(offset), dv.dot(offset));
+            u = (u + (c * ru - b * rv) / det).clamp(0.0, 1.0);
+            v = (v + (a * rv - b * ru) / det).clamp(0.0, 1.0);
+        }
+
+        (u, v)
+    }
+
+    pub fn normal_at(&self, point: Point) -> Vector {
+        let (u, v) = self.uv_of(point);
+        self.normal_at_uv(u, v)
+    }
+
+    // Intersects the ray with the patch, returning t along with the surface
+    // parameters (u, v) of each hit.
+    pub fn intersects(&self, ray: &Ray) -> Vec<(f64, f64, f64)> {
+        let n = GUIDE_DIVISIONS + 1;
+        let step = 1.0 / GUIDE_DIVISIONS as f64;
+        let mut xs: Vec<(f64, f64, f64)> = vec![];
+
+        for j in 0..GUIDE_DIVISIONS {
+            for i in 0..GUIDE_DIVISIONS {
+                let corners = [(i, j), (i + 1, j), (i, j + 1), (i + 1, j + 1)];
+                let [p00, p10, p01, p11] = corners.map(|(i, j)| self.guide[j * n + i]);
+                let (u0, v0) = (i as f64 * step, j as f64 * step);
+
+                // Each cell is split as (00, 10, 01) and (11, 01, 10), with
+                // the barycentric coordinates mapped back onto u and v.
+                let guesses = [
+                    ShapeType::triangle_intersection(ray, p00, p10 - p00, p01 - p00).
+                        map(|(t, a, b)| (t, u0 + a * step, v0 + b * step)),
+                    ShapeType::triangle_intersection(ray, p11, p01 - p11, p10 - p11).
+                        map(|(t, a, b)| (t, u0 + (1.0 - a) * step, v0 + (1.0 - b) * step))
+                ];
+
+                for (t, u, v) in guesses.iter().flatten() {
+                    if let Some(hit) = self.refine(ray, *t, *u, *v) {
+                        if xs.iter().all(|x| (x.0 - hit.0).abs() > EPSILON) {
+                            xs.push(hit);
+                        }
+                    }
+                }
+            }
+        }
+
+        xs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        xs
+    }
+
+    // Newton's method on S(u, v) - ray(t) = 0.
+    fn refine(&self, ray: &Ray, mut t: f64, mut u: f64, mut v: f64) -> Option<(f64, f64, f64)> {
+        for _ in 0..12 {
+            let f = self.point_at(u, v) - ray.position(t);
+
+            if f.magnitude() < 1e-10 {
+                break;
+            }
+
+            let (du, dv) = self.derivatives(u, v);
+            let d = -ray.direction;
+            let det = d.dot(du.cross(dv));
+
+            if det.abs() < 1e-12 {
+                return None;
+            }
+
+            // Cramer's rule for [d du dv] * [dt du dv] = -f.
+            t -= f.dot(du.cross(dv)) / det;
+            u -= d.dot(f.cross(dv)) / det;
+            v -= d.dot(du.cross(f)) / det;
+        }
+
+        let converged = (self.point_at(u, v) - ray.position(t)).magnitude() < EPSILON;
+        let inside = (-EPSILON..=1.0 + EPSILON).contains(&u) && (-EPSILON..=1.0 + EPSILON).contains(&v);
+
+        if converged && inside {
+            Some((t, u.clamp(0.0, 1.0), v.clamp(0.0, 1.0)))
+        } else {
+            None
+        }
+    }
+
+    // Approximates the patch with a group of smooth triangles, using
+    // `divisions` steps along each side.
+    pub fn tessellate(&self, divisions: usize) -> Shape {
+        let n = divisions + 1;
+        let vertices: Vec<(Point, Vector)> = Self::grid(divisions).
+            map(|(u, v)| (self.point_at(u, v), self.normal_at_uv(u, v))).
+            collect();
+
+        let mut triangles = vec![];
+
+        for j in 0..divisions {
+            for i in 0..divisions {
+                let a = vertices[j * n + i];
+                let b = vertices[j * n + i + 1];
+                let c = vertices[(j + 1) * n + i];
+                let d = vertices[(j + 1) * n + i + 1];
+
+                for (p1, p2, p3) in [(a, b, c), (b, d, c)] {
+                    // Skip the slivers where the patch pinches to a point.
+                    if (p2.0 - p1.0).cross(p3.0 - p1.0).magnitude() > 1e-12 {
+                        triangles.push(Shape::smooth_triangle(p1.0, p2.0, p3.0, p1.1, p2.1, p3.1));
+                    }
+                }
+            }
+        }
+
+        Shape::group(triangles)
+    }
+}
+
+// Reads patches in the format of Newell's original teapot data: the number
+// of patches, then a line of 16 comma separated vertex indices (counting
+// from 1) for each patch, then the number of vertices and a line of comma
+// separated x, y, z coordinates for each.
+pub fn parse_patches(input: &str) -> Result<Vec<BezierPatch>, ParseError> {
+    let mut lines = input.
+        lines().
+        enumerate().
+        map(|(i, line)| (i + 1, line.trim())).
+        filter(|(_, line)| !line.is_empty());
+
+    let mut next_line = |expected: &str| {
+        lines.next().ok_or_else(|| ParseError::new(input.lines().count(), &format!("expected {}", expected)))
+    };
+
+    let parse_numbers = |line_number: usize, line: &str| -> Result<Vec<f64>, ParseError> {
+        line.split(|c: char| c == ',' || c.is_whitespace()).
+            filter(|s| !s.is_empty()).
+            map(|s| s.parse::<f64>().map_err(|_| ParseError::new(line_number, &format!("invalid number '{}'", s)))).
+            collect()
+    };
+
+    let parse_count = |line_number: usize, line: &str| -> Result<usize, ParseError> {
+        line.parse::<usize>().map_err(|_| ParseError::new(line_number, &format!("invalid count '{}'", line)))
+    };
+
+    let (line_number, line) = next_line("the number of patches")?;
+    let patch_count = parse_count(line_number, line)?;
+    let mut indices = vec![];
+
+    for _ in 0..patch_count {
+        let (line_number, line) = next_line("a patch")?;
+        let patch = parse_numbers(line_number, line)?;
+
+        if patch.len() != 16 {
+            return Err(ParseError::new(line_number, "a patch needs 16 vertex indices"));
+        }
+
+        indices.push((line_number, patch));
+    }
+
+    let (line_number, line) = next_line("the number of vertices")?;
+    let vertex_count = parse_count(line_number, line)?;
+    let mut vertices = vec![];
+
+    for _ in 0..vertex_count {
+        let (line_number, line) = next_line("a vertex")?;
+
+        match parse_numbers(line_number, line)?[..] {
+            [x, y, z] => vertices.push(Point::new(x, y, z)),
+            _ => return Err(ParseError::new(line_number, "a vertex needs 3 coordinates"))
+        }
+    }
+
+    indices.
+        into_iter().
+        map(|(line_number, patch)| {
+            let mut points = [Point::origin(); 16];
+
+            for (point, index) in points.iter_mut().zip(patch) {
+                *point = *vertices.
+                    get((index as usize).wrapping_sub(1)).
+                    filter(|_| index.fract() == 0.0).
+                    ok_or_else(|| ParseError::new(line_number, &format!("no vertex {}", index)))?;
+            }
+
+            Ok(BezierPatch::new(points))
+        }).
+        collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A flat patch covering x and z from 0 to 3 with y = 0.
+    fn flat_patch() -> BezierPatch {
+        let mut points = [Point::origin(); 16];
+
+        for (i, point) in points.iter_mut().enumerate() {
+            *point = Point::new((i % 4) as f64, 0.0, (i / 4) as f64);
+        }
+
+        BezierPatch::new(points)
+    }
+
+    // A dome rising to a height of 2 over the middle of the flat patch.
+    fn dome_patch() -> BezierPatch {
+        let mut points = [Point::origin(); 16];
+
+        for (i, point) in points.iter_mut().enumerate() {
+            let (x, z) = (i % 4, i / 4);
+            let inner = (x == 1 || x == 2) && (z == 1 || z == 2);
+            *point = Point::new(x as f64, if inner { 2.0 } else { 0.0 }, z as f64);
+        }
+
+        BezierPatch::new(points)
+    }
+
+    #[test]
+    fn corners_of_a_patch_are_its_corner_control_points() {
+        let patch = dome_patch();
+        assert_eq!(patch.control_point(0, 0), patch.point_at(0.0, 0.0));
+        assert_eq!(patch.control_point(0, 3), patch.point_at(1.0, 0.0));
+        assert_eq!(patch.control_point(3, 0), patch.point_at(0.0, 1.0));
+        assert_eq!(patch.control_point(3, 3), patch.point_at(1.0, 1.0));
+    }
+
+    #[test]
+    fn evaluating_a_patch() {
+        assert_eq!(Point::new(1.5, 0.0, 0.75), flat_patch().point_at(0.5, 0.25));
+        assert_eq!(Point::new(1.5, 1.125, 1.5), dome_patch().point_at(0.5, 0.5));
+    }
+
+    #[test]
+    fn normal_of_a_flat_patch() {
+        let patch = flat_patch();
+        assert_eq!(Vector::new(0.0, -1.0, 0.0), patch.normal_at_uv(0.3, 0.6));
+        assert_eq!(Vector::new(0.0, -1.0, 0.0), patch.normal_at(Point::new(2.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn normal_of_a_pinched_patch_is_taken_from_inside_it() {
+        // Every point of the first row is the same, so du vanishes along it.
+        let mut points = [Point::origin(); 16];
+        for (i, point) in points.iter_mut().enumerate().skip(4) {
+            *point = Point::new((i % 4) as f64, 0.0, (i / 4) as f64);
+        }
+        let patch = BezierPatch::new(points);
+        let n = patch.normal_at_uv(0.5, 0.0);
+
+        assert!(n.magnitude() > 0.99);
+        assert_eq!(Vector::new(0.0, -1.0, 0.0), n);
+    }
+
+    #[test]
+    fn finding_the_parameters_of_a_point_on_a_patch() {
+        let patch = dome_patch();
+        let (u, v) = patch.uv_of(patch.point_at(0.37, 0.81));
+        assert!((u - 0.37).abs() < 0.0001);
+        assert!((v - 0.81).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_strikes_a_patch_directly() {
+        let patch = dome_patch();
+        let r = Ray::new(Point::new(1.5, 5.0, 1.5), Vector::new(0.0, -1.0, 0.0));
+        let xs = patch.intersects(&r);
+
+        assert_eq!(1, xs.len());
+        assert!((xs[0].0 - (5.0 - 1.125)).abs() < 0.0001);
+        assert!((xs[0].1 - 0.5).abs() < 0.0001);
+        assert!((xs[0].2 - 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_through_the_side_of_a_dome_hits_it_twice() {
+        let patch = dome_patch();
+        let r = Ray::new(Point::new(-1.0, 0.5, 1.5), Vector::new(1.0, 0.0, 0.0));
+        let xs = patch.intersects(&r);
+
+        assert_eq!(2, xs.len());
+        for (t, u, v) in xs {
+            assert_eq!(r.position(t), patch.point_at(u, v));
+        }
+    }
+
+    #[test]
+    fn ray_misses_a_patch() {
+        let patch = dome_patch();
+        let r = Ray::new(Point::new(4.0, 5.0, 1.5), Vector::new(0.0, -1.0, 0.0));
+        assert!(patch.intersects(&r).is_empty());
+    }
+
+    #[test]
+    fn tessellating_a_patch() {
+        let group = dome_patch().tessellate(16);
+        assert_eq!(512, group.children().len());
+
+        // The triangles only approximate the surface between their corners.
+        let r = Ray::new(Point::new(1.3, 5.0, 1.4), Vector::new(0.0, -1.0, 0.0));
+        let xs = group.intersects(&r);
+        let exact = dome_patch().intersects(&r);
+        assert_eq!(1, xs.len());
+        assert!((xs[0].t - exact[0].0).abs() < 0.01);
+    }
+
+    #[test]
+    fn tessellation_skips_degenerate_triangles() {
+        let mut points = [Point::origin(); 16];
+        for (i, point) in points.iter_mut().enumerate().skip(4) {
+            *point = Point::new((i % 4) as f64, 0.0, (i / 4) as f64);
+        }
+
+        // The first row of cells loses the triangle along the pinched edge.
+        assert_eq!(6, BezierPatch::new(points).tessellate(2).children().len());
+    }
+
+    #[test]
+    fn parsing_teapot_patch_data() {
+        let input = "1\n\
+            1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16\n\
+            16\n\
+            0,0,0\n1,0,0\n2,0,0\n3,0,0\n\
+            0,0,1\n1,2,1\n2,2,1\n3,0,1\n\
+            0,0,2\n1,2,2\n2,2,2\n3,0,2\n\
+            0,0,3\n1,0,3\n2,0,3\n3,0,3\n";
+        let patches = parse_patches(input).unwrap();
+
+        assert_eq!(1, patches.len());
+        assert_eq!(dome_patch(), patches[0]);
+    }
+
+    #[test]
+    fn parsing_invalid_patch_data() {
+        assert_eq!(Err(ParseError::new(1, "invalid count 'x'")), parse_patches("x"));
+        assert_eq!(Err(ParseError::new(2, "a patch needs 16 vertex indices")), parse_patches("1\n1,2,3\n"));
+        assert_eq!(
+            Err(ParseError::new(2, "no vertex 17")),
+            parse_patches("1\n1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,17\n1\n0,0,0\n"));
+        assert_eq!(
+            Err(ParseError::new(4, "a vertex needs 3 coordinates")),
+            parse_patches("1\n1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1\n1\n0,0\n"));
+    }
+}
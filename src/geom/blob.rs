@@ -0,0 +1,253 @@
+// Metaballs: an implicit surface formed by blending the fields of several
+// weighted centres together.
+//
+// Each centre contributes `strength * (1 - r^2 / radius^2)^2` to the field
+// at a distance `r` from it, falling smoothly to nothing at `radius`. The
+// surface is wherever the total field equals the threshold, with the inside
+// of the blob being where it is greater.
+//
+// Along a ray each contribution is a quartic in t, so between the points
+// where the ray crosses the centres' bounding spheres the field is a single
+// quartic that can be solved exactly. Stretches of the ray outside every
+// bounding sphere are skipped entirely.
+
+use super::Bounds;
+use crate::{Point, Ray, Vector};
+use crate::polynomial;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct BlobComponent {
+    pub center: Point,
+    pub radius: f64,
+    pub strength: f64
+}
+
+impl BlobComponent {
+    fn field_at(&self, p: Point) -> f64 {
+        let r2 = (p - self.center).dot(p - self.center) / self.radius.powi(2);
+
+        if r2 >= 1.0 {
+            0.0
+        } else {
+            self.strength * (1.0 - r2).powi(2)
+        }
+    }
+
+    // Where the ray is inside the component's bounding sphere.
+    fn bounding_interval(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let to_ray = ray.origin - self.center;
+        let a = ray.direction.dot(ray.direction);
+        let b = 2.0 * ray.direction.dot(to_ray);
+        let c = to_ray.dot(to_ray) - self.radius.powi(2);
+        let discriminant = b.powi(2) - 4.0 * a * c;
+
+        if discriminant <= 0.0 {
+            None
+        } else {
+            Some(((-b - discriminant.sqrt()) / (2.0 * a), (-b + discriminant.sqrt()) / (2.0 * a)))
+        }
+    }
+
+    // The coefficients of this component's field along the ray, as a
+    // quartic in t from the highest power down.
+    fn coefficients(&self, ray: &Ray) -> [f64; 5] {
+        let to_ray = ray.origin - self.center;
+        let r2 = self.radius.powi(2);
+        let a = -ray.direction.dot(ray.direction) / r2;
+        let b = -2.0 * ray.direction.dot(to_ray) / r2;
+        let c = 1.0 - to_ray.dot(to_ray) / r2;
+        let s = self.strength;
+
+        [
+            s * a * a,
+            s * 2.0 * a * b,
+            s * (b * b + 2.0 * a * c),
+            s * 2.0 * b * c,
+            s * c * c
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Blob {
+    components: Vec<BlobComponent>,
+    threshold: f64
+}
+
+impl Blob {
+    pub fn new(threshold: f64) -> Blob {
+        Blob {
+            components: vec![],
+            threshold
+        }
+    }
+
+    pub fn component(self, center: Point, radius: f64, strength: f64) -> Blob {
+        let mut components = self.components;
+        components.push(BlobComponent { center, radius, strength });
+
+        Blob {
+            components,
+            ..self
+        }
+    }
+
+    pub fn components(&self) -> &[BlobComponent] {
+        &self.components
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub fn field_at(&self, p: Point) -> f64 {
+        self.components.iter().map(|c| c.field_at(p)).sum()
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.components.
+            iter().
+            fold(Bounds::empty(), |bounds, c| {
+                let extent = Vector::new(c.radius, c.radius, c.radius);
+                bounds.merge(Bounds::new(c.center - extent, c.center + extent))
+            })
+    }
+
+    pub fn intersects(&self, ray: &Ray) -> Vec<f64> {
+        let intervals: Vec<(&BlobComponent, (f64, f64))> = self.components.
+            iter().
+            filter_map(|c| c.bounding_interval(ray).map(|interval| (c, interval))).
+            collect();
+
+        let mut boundaries: Vec<f64> = intervals.
+            iter().
+            flat_map(|(_, (t0, t1))| vec![*t0, *t1]).
+            collect();
+        boundaries.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mut xs = vec![];
+
+        for segment in boundaries.windows(2) {
+            let (start, end) = (segment[0], segment[1]);
+            let middle = (start + end) / 2.0;
+            let mut coefficients = [0.0; 5];
+            let mut active = false;
+
+            for (component, _) in intervals.iter().filter(|(_, (t0, t1))| *t0 <= middle && middle <= *t1) {
+                for (total, c) in coefficients.iter_mut().zip(component.coefficients(ray).iter()) {
+                    *total += c;
+                }
+                active = true;
+            }
+
+            if !active {
+                continue;
+            }
+
+            coefficients[4] -= self.threshold;
+            let [a, b, c, d, e] = coefficients;
+
+            xs.extend(
+                polynomial::solve_quartic(a, b, c, d, e).
+                    into_iter().
+                    filter(|t| start <= *t && *t < end)
+            );
+        }
+
+        xs
+    }
+
+    // The normal points down the gradient of the field, out of the blob.
+    pub fn normal_at(&self, p: Point) -> Vector {
+        self.components.
+            iter().
+            filter(|c| (p - c.center).magnitude() < c.radius).
+            fold(Vector::new(0.0, 0.0, 0.0), |normal, c| {
+                let to_point = p - c.center;
+                let r2 = c.radius.powi(2);
+                let falloff = 1.0 - to_point.dot(to_point) / r2;
+
+                normal + to_point * (4.0 * c.strength * falloff / r2)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_of_a_single_component() {
+        let blob = Blob::new(0.5).component(Point::origin(), 2.0, 1.0);
+        assert_eq!(1.0, blob.field_at(Point::origin()));
+        assert_eq!(0.5625, blob.field_at(Point::new(1.0, 0.0, 0.0)));
+        assert_eq!(0.0, blob.field_at(Point::new(0.0, 2.0, 0.0)));
+        assert_eq!(0.0, blob.field_at(Point::new(0.0, 0.0, 3.0)));
+    }
+
+    #[test]
+    fn single_component_blob_is_a_sphere() {
+        // (1 - r^2)^2 = 0.25 when r = sqrt(0.5).
+        let blob = Blob::new(0.25).component(Point::origin(), 1.0, 1.0);
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = blob.intersects(&r);
+
+        assert_eq!(2, xs.len());
+        assert!((xs[0] - (5.0 - 0.5_f64.sqrt())).abs() < 0.0001);
+        assert!((xs[1] - (5.0 + 0.5_f64.sqrt())).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_misses_a_blob() {
+        let blob = Blob::new(0.25).component(Point::origin(), 1.0, 1.0);
+        let examples = vec![
+            Ray::new(Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0)),
+            Ray::new(Point::new(0.0, 0.8, -5.0), Vector::new(0.0, 0.0, 1.0))
+        ];
+
+        for r in examples {
+            assert!(blob.intersects(&r).is_empty());
+        }
+    }
+
+    #[test]
+    fn nearby_components_merge_into_one_surface() {
+        let blob = Blob::new(0.5).
+            component(Point::new(-0.6, 0.0, 0.0), 1.0, 1.0).
+            component(Point::new(0.6, 0.0, 0.0), 1.0, 1.0);
+        let r = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let xs = blob.intersects(&r);
+
+        // Each component alone would be a sphere of radius 0.541, leaving a
+        // gap between them, but their fields add up across the middle.
+        assert!(blob.field_at(Point::origin()) > 0.5);
+        assert_eq!(2, xs.len());
+        assert!(xs[0] < 5.0 - 0.6 - 0.541);
+        assert!(xs[1] > 5.0 + 0.6 + 0.541);
+    }
+
+    #[test]
+    fn distant_components_stay_separate() {
+        let blob = Blob::new(0.25).
+            component(Point::new(-3.0, 0.0, 0.0), 1.0, 1.0).
+            component(Point::new(3.0, 0.0, 0.0), 1.0, 1.0);
+        let r = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let xs = blob.intersects(&r);
+        let radius = 0.5_f64.sqrt();
+        let expected = [2.0 - radius, 2.0 + radius, 8.0 - radius, 8.0 + radius];
+
+        assert_eq!(4, xs.len());
+        for (t, e) in xs.iter().zip(expected.iter()) {
+            assert!((t - e).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn normal_of_a_blob_points_out_of_the_surface() {
+        let blob = Blob::new(0.25).component(Point::origin(), 1.0, 1.0);
+        let radius = 0.5_f64.sqrt();
+
+        assert_eq!(Vector::new(1.0, 0.0, 0.0), blob.normal_at(Point::new(radius, 0.0, 0.0)).normalize());
+        assert_eq!(Vector::new(0.0, -1.0, 0.0), blob.normal_at(Point::new(0.0, -radius, 0.0)).normalize());
+    }
+}
@@ -0,0 +1,259 @@
+// Axis aligned bounding boxes.
+//
+// Boxes may be infinite along any axis, as for planes, so transforming one
+// takes care never to multiply an infinity by zero. An empty box has its
+// minimum above its maximum and stays empty however it is transformed.
+
+use crate::{Matrix4x4, Point, Ray};
+
+const EPSILON: f64 = 0.0001;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point
+}
+
+impl Bounds {
+    pub fn new(min: Point, max: Point) -> Bounds {
+        Bounds { min, max }
+    }
+
+    pub fn empty() -> Bounds {
+        Self::new(
+            Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY))
+    }
+
+    pub fn infinite() -> Bounds {
+        Self::new(
+            Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY))
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Point>) -> Bounds {
+        points.into_iter().fold(Self::empty(), |bounds, p| bounds.add_point(p))
+    }
+
+    pub fn add_point(self, p: Point) -> Bounds {
+        Self::new(
+            Point::new(self.min.x.min(p.x), self.min.y.min(p.y), self.min.z.min(p.z)),
+            Point::new(self.max.x.max(p.x), self.max.y.max(p.y), self.max.z.max(p.z)))
+    }
+
+    pub fn merge(self, other: Bounds) -> Bounds {
+        if other.is_empty() {
+            return self;
+        }
+
+        self.add_point(other.min).add_point(other.max)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        [self.min.x, self.min.y, self.min.z, self.max.x, self.max.y, self.max.z].
+            iter().
+            any(|c| c.is_infinite())
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        self.min.x <= p.x && p.x <= self.max.x &&
+            self.min.y <= p.y && p.y <= self.max.y &&
+            self.min.z <= p.z && p.z <= self.max.z
+    }
+
+    pub fn center(&self) -> Point {
+        self.min + (self.max - self.min) / 2.0
+    }
+
+    // Finds the box around this one after transformation by `m`, following
+    // Arvo's "Transforming Axis-Aligned Bounding Boxes" from Graphics Gems.
+    pub fn transform(&self, m: Matrix4x4) -> Bounds {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let min = [self.min.x, self.min.y, self.min.z];
+        let max = [self.max.x, self.max.y, self.max.z];
+        let mut new_min = [0.0; 3];
+        let mut new_max = [0.0; 3];
+
+        for i in 0..3 {
+            new_min[i] = m.get(i, 3);
+            new_max[i] = m.get(i, 3);
+
+            for j in 0..3 {
+                let a = m.get(i, j);
+
+                // Zero entries contribute nothing, even from an infinite
+                // extent.
+                if a == 0.0 {
+                    continue;
+                }
+
+                let (e, f) = (a * min[j], a * max[j]);
+                new_min[i] += e.min(f);
+                new_max[i] += e.max(f);
+            }
+        }
+
+        Self::new(
+            Point::new(new_min[0], new_min[1], new_min[2]),
+            Point::new(new_max[0], new_max[1], new_max[2]))
+    }
+
+    // Whether the line of the ray passes through the box. Crossings behind
+    // the ray's origin count too, since shapes report those intersections
+    // as well.
+    pub fn intersects(&self, ray: &Ray) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        let axes = [
+            (ray.origin.x, ray.direction.x, self.min.x, self.max.x),
+            (ray.origin.y, ray.direction.y, self.min.y, self.max.y),
+            (ray.origin.z, ray.direction.z, self.min.z, self.max.z)
+        ];
+        let mut tmin = f64::NEG_INFINITY;
+        let mut tmax = f64::INFINITY;
+
+        for (origin, direction, min, max) in axes {
+            if direction == 0.0 {
+                if origin < min - EPSILON || origin > max + EPSILON {
+                    return false;
+                }
+                continue;
+            }
+
+            let t0 = (min - EPSILON - origin) / direction;
+            let t1 = (max + EPSILON - origin) / direction;
+            tmin = tmin.max(t0.min(t1));
+            tmax = tmax.min(t0.max(t1));
+        }
+
+        tmin <= tmax
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector;
+    use std::f64::consts::{PI, SQRT_2};
+
+    fn unit_box() -> Bounds {
+        Bounds::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn building_bounds_from_points() {
+        let b = Bounds::from_points(vec![
+            Point::new(-5.0, 2.0, 0.0),
+            Point::new(7.0, 0.0, -3.0),
+            Point::new(0.0, 4.0, 1.0)
+        ]);
+
+        assert_eq!(Point::new(-5.0, 0.0, -3.0), b.min);
+        assert_eq!(Point::new(7.0, 4.0, 1.0), b.max);
+        assert!(Bounds::from_points(vec![]).is_empty());
+    }
+
+    #[test]
+    fn merging_bounds() {
+        let a = Bounds::new(Point::new(-5.0, -2.0, 0.0), Point::new(7.0, 4.0, 4.0));
+        let b = Bounds::new(Point::new(8.0, -7.0, -2.0), Point::new(14.0, 2.0, 8.0));
+        let merged = a.merge(b);
+
+        assert_eq!(Point::new(-5.0, -7.0, -2.0), merged.min);
+        assert_eq!(Point::new(14.0, 4.0, 8.0), merged.max);
+        assert_eq!(a, a.merge(Bounds::empty()));
+    }
+
+    #[test]
+    fn checking_whether_bounds_contain_a_point() {
+        let b = Bounds::new(Point::new(5.0, -2.0, 0.0), Point::new(11.0, 4.0, 7.0));
+        assert!(b.contains(Point::new(5.0, -2.0, 0.0)));
+        assert!(b.contains(Point::new(8.0, 1.0, 3.0)));
+        assert!(!b.contains(Point::new(3.0, 0.0, 3.0)));
+        assert!(!b.contains(Point::new(8.0, 1.0, 8.0)));
+    }
+
+    #[test]
+    fn transforming_bounds() {
+        let b = unit_box().transform(Matrix4x4::identity().rotation_y(PI / 4.0).rotation_x(PI / 4.0));
+        assert_eq!(Point::new(-SQRT_2, -1.707106, -1.707106), b.min);
+        assert_eq!(Point::new(SQRT_2, 1.707106, 1.707106), b.max);
+
+        let b = unit_box().transform(Matrix4x4::identity().scale(2.0, 1.0, 1.0).translate(1.0, 2.0, 3.0));
+        assert_eq!(Point::new(-1.0, 1.0, 2.0), b.min);
+        assert_eq!(Point::new(3.0, 3.0, 4.0), b.max);
+    }
+
+    #[test]
+    fn transforming_infinite_bounds() {
+        let plane = Bounds::new(
+            Point::new(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            Point::new(f64::INFINITY, 0.0, f64::INFINITY));
+        let b = plane.transform(Matrix4x4::identity().translate(0.0, 2.0, 0.0));
+
+        assert_eq!(2.0, b.min.y);
+        assert_eq!(2.0, b.max.y);
+        assert_eq!(f64::NEG_INFINITY, b.min.x);
+        assert_eq!(f64::INFINITY, b.max.z);
+
+        let tilted = plane.transform(Matrix4x4::identity().rotation_z(PI / 4.0));
+        assert_eq!(f64::NEG_INFINITY, tilted.min.y);
+        assert!(tilted.is_infinite());
+    }
+
+    #[test]
+    fn empty_bounds_stay_empty_when_transformed() {
+        let b = Bounds::empty().transform(Matrix4x4::identity().rotation_z(PI / 4.0));
+        assert!(b.is_empty());
+    }
+
+    #[test]
+    fn ray_overlaps_bounds() {
+        let b = Bounds::new(Point::new(5.0, -2.0, 0.0), Point::new(11.0, 4.0, 7.0));
+        let examples = vec![
+            (Point::new(15.0, 1.0, 2.0), Vector::new(-1.0, 0.0, 0.0), true),
+            (Point::new(-5.0, -1.0, 4.0), Vector::new(1.0, 0.0, 0.0), true),
+            (Point::new(7.0, 6.0, 5.0), Vector::new(0.0, -1.0, 0.0), true),
+            (Point::new(9.0, -5.0, 6.0), Vector::new(0.0, 1.0, 0.0), true),
+            (Point::new(8.0, 2.0, 12.0), Vector::new(0.0, 0.0, -1.0), true),
+            (Point::new(6.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0), true),
+            (Point::new(8.0, 1.0, 3.5), Vector::new(0.0, 0.0, 1.0), true),
+            (Point::new(9.0, -1.0, -8.0), Vector::new(2.0, 4.0, 6.0), false),
+            (Point::new(8.0, 3.0, -4.0), Vector::new(6.0, 2.0, 4.0), false),
+            (Point::new(9.0, -1.0, -2.0), Vector::new(4.0, 6.0, 2.0), false),
+            (Point::new(4.0, 0.0, 9.0), Vector::new(0.0, 0.0, -1.0), false),
+            (Point::new(8.0, 6.0, -1.0), Vector::new(0.0, -1.0, 0.0), false),
+            (Point::new(12.0, 5.0, 4.0), Vector::new(-1.0, 0.0, 0.0), false)
+        ];
+
+        for (origin, direction, expected) in examples {
+            assert_eq!(expected, b.intersects(&Ray::new(origin, direction)));
+        }
+    }
+
+    #[test]
+    fn ray_overlaps_bounds_behind_it() {
+        let r = Ray::new(Point::new(0.0, 0.0, 5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(unit_box().intersects(&r));
+    }
+
+    #[test]
+    fn ray_overlaps_flat_and_infinite_bounds() {
+        let plane = Bounds::new(
+            Point::new(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            Point::new(f64::INFINITY, 0.0, f64::INFINITY));
+
+        assert!(plane.intersects(&Ray::new(Point::new(3.0, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0))));
+        assert!(!plane.intersects(&Ray::new(Point::new(3.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0))));
+        assert!(!Bounds::empty().intersects(&Ray::new(Point::origin(), Vector::new(1.0, 0.0, 0.0))));
+    }
+}
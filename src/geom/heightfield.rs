@@ -0,0 +1,424 @@
+// A terrain surface built from a regular grid of heights.
+//
+// The grid covers the unit square from (0, 0) to (1, 1) in the xz plane,
+// with each height giving the y value of a grid vertex. Each grid cell is
+// split into two triangles. Rays walk the grid one cell at a time so only
+// the cells the ray passes over are tested.
+
+use super::{Bounds, Mesh, ShapeType};
+use crate::{Canvas, Point, Ray, Vector};
+
+const EPSILON: f64 = 0.0001;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Heightfield {
+    columns: usize,
+    rows: usize,
+    heights: Vec<f64>,
+    normals: Vec<Vector>,
+    min_height: f64,
+    max_height: f64
+}
+
+impl Heightfield {
+    // Builds a heightfield from rows of heights, where `heights[z][x]`
+    // is the height at the xth column of the zth row. The grid needs at
+    // least two rows and two columns, so any short rows are padded out to
+    // the longest by repeating their last height, an empty row repeats the
+    // one before it, and a single row or column is repeated to make a
+    // second. With no heights at all, the surface is flat at zero.
+    pub fn new(heights: Vec<Vec<f64>>) -> Heightfield {
+        let columns = heights.iter().map(Vec::len).max().unwrap_or(0).max(2);
+        let mut grid: Vec<Vec<f64>> = vec![];
+
+        for row in heights {
+            let mut row = if row.is_empty() {
+                grid.last().cloned().unwrap_or_else(|| vec![0.0])
+            } else {
+                row
+            };
+
+            row.resize(columns, row[row.len() - 1]);
+            grid.push(row);
+        }
+
+        while grid.len() < 2 {
+            grid.push(grid.last().cloned().unwrap_or_else(|| vec![0.0; columns]));
+        }
+
+        let rows = grid.len();
+        let heights: Vec<f64> = grid.into_iter().flatten().collect();
+        let min_height = heights.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max_height = heights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+        let mut heightfield = Heightfield {
+            columns,
+            rows,
+            heights,
+            normals: vec![],
+            min_height,
+            max_height
+        };
+
+        heightfield.normals = (0..rows).
+            flat_map(|j| (0..columns).map(move |i| (i, j))).
+            map(|(i, j)| heightfield.vertex_normal(i, j)).
+            collect();
+
+        heightfield
+    }
+
+    // Uses the brightness of each pixel as a height between 0 and 1, with
+    // the top row of the image at z = 0.
+    pub fn from_canvas(canvas: &Canvas) -> Heightfield {
+        Self::new(
+            (0..canvas.height).
+                map(|y| {
+                    (0..canvas.width).
+                        map(|x| {
+                            let c = canvas.get(x, y);
+                            (c.r + c.g + c.b) / 3.0
+                        }).
+                        collect()
+                }).
+                collect()
+        )
+    }
+
+    pub fn height(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.columns + i]
+    }
+
+    pub fn min_height(&self) -> f64 {
+        self.min_height
+    }
+
+    pub fn max_height(&self) -> f64 {
+        self.max_height
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        Bounds::new(Point::new(0.0, self.min_height, 0.0), Point::new(1.0, self.max_height, 1.0))
+    }
+
+    // The grid as a mesh of two triangles per cell, with the same normals
+    // used for shading.
+    pub fn to_mesh(&self) -> Mesh {
+        let vertices = (0..self.rows).
+            flat_map(|j| (0..self.columns).map(move |i| (i, j))).
+            map(|(i, j)| self.vertex(i, j)).
+            collect();
+        let index = |i: usize, j: usize| j * self.columns + i;
+        let triangles = (0..self.rows - 1).
+            flat_map(|j| (0..self.columns - 1).map(move |i| (i, j))).
+            flat_map(|(i, j)| [
+                [index(i, j), index(i + 1, j), index(i, j + 1)],
+                [index(i + 1, j), index(i + 1, j + 1), index(i, j + 1)]
+            ]).
+            collect();
+
+        Mesh {
+            vertices,
+            normals: self.normals.clone(),
+            colors: vec![],
+            triangles
+        }
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Point {
+        Point::new(
+            i as f64 / (self.columns - 1) as f64,
+            self.height(i, j),
+            j as f64 / (self.rows - 1) as f64
+        )
+    }
+
+    // Estimates the normal at a grid vertex from the slope across its
+    // neighbours, falling back to one sided differences at the edges.
+    fn vertex_normal(&self, i: usize, j: usize) -> Vector {
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.columns - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.rows - 1));
+
+        let dx = (self.height(i1, j) - self.height(i0, j)) /
+            ((i1 - i0) as f64 / (self.columns - 1) as f64);
+        let dz = (self.height(i, j1) - self.height(i, j0)) /
+            ((j1 - j0) as f64 / (self.rows - 1) as f64);
+
+        Vector::new(-dx, 1.0, -dz).normalize()
+    }
+
+    // Walks the cells under the ray with a 2D DDA, starting where the ray
+    // enters the heightfield's bounding box, and returns the first hit.
+    pub fn intersects(&self, ray: &Ray) -> Vec<f64> {
+        let (tmin, tmax) = match self.bounding_box_intersection(ray) {
+            Some(range) => range,
+            None => return vec![]
+        };
+
+        let tmin = tmin.max(0.0);
+        if tmin > tmax {
+            return vec![];
+        }
+
+        let scale_x = (self.columns - 1) as f64;
+        let scale_z = (self.rows - 1) as f64;
+        let start = ray.position(tmin);
+        let mut i = ((start.x * scale_x).floor() as isize).clamp(0, self.columns as isize - 2);
+        let mut j = ((start.z * scale_z).floor() as isize).clamp(0, self.rows as isize - 2);
+
+        let (step_i, mut next_x, delta_x) = Self::dda_axis(start.x * scale_x, ray.direction.x * scale_x, i, tmin);
+        let (step_j, mut next_z, delta_z) = Self::dda_axis(start.z * scale_z, ray.direction.z * scale_z, j, tmin);
+
+        loop {
+            let exit = next_x.min(next_z).min(tmax);
+
+            if let Some(t) = self.cell_intersection(ray, i as usize, j as usize, tmin) {
+                if t <= exit + EPSILON {
+                    return vec![t];
+                }
+            }
+
+            if exit >= tmax {
+                return vec![];
+            }
+
+            if next_x < next_z {
+                i += step_i;
+                next_x += delta_x;
+            } else {
+                j += step_j;
+                next_z += delta_z;
+            }
+
+            if i < 0 || j < 0 || i > self.columns as isize - 2 || j > self.rows as isize - 2 {
+                return vec![];
+            }
+        }
+    }
+
+    // For one axis of the grid, finds the direction to step through cells,
+    // the t at which the ray crosses into the next cell, and how far t
+    // advances per cell.
+    fn dda_axis(position: f64, direction: f64, cell: isize, t: f64) -> (isize, f64, f64) {
+        if direction > 0.0 {
+            (1, t + ((cell + 1) as f64 - position) / direction, 1.0 / direction)
+        } else if direction < 0.0 {
+            (-1, t + (cell as f64 - position) / direction, -1.0 / direction)
+        } else {
+            (0, f64::INFINITY, f64::INFINITY)
+        }
+    }
+
+    fn cell_intersection(&self, ray: &Ray, i: usize, j: usize, tmin: f64) -> Option<f64> {
+        let p00 = self.vertex(i, j);
+        let p10 = self.vertex(i + 1, j);
+        let p01 = self.vertex(i, j + 1);
+        let p11 = self.vertex(i + 1, j + 1);
+
+        [(p00, p10, p01), (p10, p11, p01)].
+            iter().
+            filter_map(|(p1, p2, p3)| ShapeType::triangle_intersection(ray, *p1, *p2 - *p1, *p3 - *p1)).
+            map(|(t, _, _)| t).
+            filter(|t| *t >= tmin).
+            fold(None, |closest: Option<f64>, t| Some(closest.map_or(t, |c| c.min(t))))
+    }
+
+    fn bounding_box_intersection(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let axes = [
+            (ray.origin.x, ray.direction.x, 0.0, 1.0),
+            (ray.origin.y, ray.direction.y, self.min_height - EPSILON, self.max_height + EPSILON),
+            (ray.origin.z, ray.direction.z, 0.0, 1.0)
+        ];
+
+        let mut tmin = f64::NEG_INFINITY;
+        let mut tmax = f64::INFINITY;
+
+        for (origin, direction, min, max) in axes.iter() {
+            if direction.abs() < EPSILON {
+                if origin < min || origin > max {
+                    return None;
+                }
+            } else {
+                let t0 = (min - origin) / direction;
+                let t1 = (max - origin) / direction;
+                tmin = tmin.max(t0.min(t1));
+                tmax = tmax.min(t0.max(t1));
+            }
+        }
+
+        if tmin > tmax {
+            None
+        } else {
+            Some((tmin, tmax))
+        }
+    }
+
+    // Blends the normals of the four vertices around the cell containing
+    // the point.
+    pub fn normal_at(&self, point: Point) -> Vector {
+        let x = (point.x * (self.columns - 1) as f64).clamp(0.0, (self.columns - 1) as f64);
+        let z = (point.z * (self.rows - 1) as f64).clamp(0.0, (self.rows - 1) as f64);
+        let i = (x.floor() as usize).min(self.columns - 2);
+        let j = (z.floor() as usize).min(self.rows - 2);
+        let fx = x - i as f64;
+        let fz = z - j as f64;
+
+        let normal = |i: usize, j: usize| self.normals[j * self.columns + i];
+
+        (normal(i, j) * ((1.0 - fx) * (1.0 - fz)) +
+            normal(i + 1, j) * (fx * (1.0 - fz)) +
+            normal(i, j + 1) * ((1.0 - fx) * fz) +
+            normal(i + 1, j + 1) * (fx * fz)).normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    fn ramp() -> Heightfield {
+        // Rises by 1 along x, flat along z.
+        Heightfield::new(vec![
+            vec![0.0, 0.5, 1.0],
+            vec![0.0, 0.5, 1.0],
+            vec![0.0, 0.5, 1.0]
+        ])
+    }
+
+    #[test]
+    fn creating_a_heightfield() {
+        let h = ramp();
+        assert_eq!(0.5, h.height(1, 2));
+        assert_eq!(0.0, h.min_height());
+        assert_eq!(1.0, h.max_height());
+    }
+
+    #[test]
+    fn repeating_the_edges_of_a_single_row() {
+        let h = Heightfield::new(vec![vec![0.0, 1.0]]);
+        assert_eq!(1.0, h.height(1, 1));
+
+        let h = Heightfield::new(vec![vec![0.5]]);
+        assert_eq!(0.5, h.height(1, 1));
+        assert_eq!(0.5, h.max_height());
+
+        let h = Heightfield::new(vec![]);
+        assert_eq!(0.0, h.height(1, 1));
+        assert_eq!(vec![1.0], h.intersects(&Ray::new(Point::new(0.5, 1.0, 0.5), Vector::new(0.0, -1.0, 0.0))));
+    }
+
+    #[test]
+    fn padding_ragged_rows() {
+        let h = Heightfield::new(vec![vec![0.0, 0.5, 1.0], vec![0.25], vec![]]);
+
+        assert_eq!(0.25, h.height(2, 1));
+        assert_eq!(0.25, h.height(0, 2));
+        assert_eq!(0.25, h.height(2, 2));
+    }
+
+    #[test]
+    fn creating_a_heightfield_from_a_single_column_canvas() {
+        let mut c = Canvas::new(1, 3);
+        c.set(0, 2, Color::white());
+        let h = Heightfield::from_canvas(&c);
+
+        assert_eq!(0.0, h.height(1, 0));
+        assert_eq!(1.0, h.height(1, 2));
+    }
+
+    #[test]
+    fn creating_a_heightfield_from_a_canvas() {
+        let mut c = Canvas::new(2, 2);
+        c.set(1, 0, Color::white());
+        c.set(0, 1, Color::new(0.5, 0.5, 0.5));
+        let h = Heightfield::from_canvas(&c);
+
+        assert_eq!(0.0, h.height(0, 0));
+        assert_eq!(1.0, h.height(1, 0));
+        assert_eq!(0.5, h.height(0, 1));
+    }
+
+    #[test]
+    fn ray_straight_down_hits_the_surface() {
+        let h = ramp();
+        let examples = vec![
+            (0.0, 0.5, 5.0),
+            (0.25, 0.5, 4.75),
+            (0.75, 0.1, 4.25),
+            (1.0, 0.9, 4.0)
+        ];
+
+        for (x, z, t) in examples {
+            let r = Ray::new(Point::new(x, 5.0, z), Vector::new(0.0, -1.0, 0.0));
+            let xs = h.intersects(&r);
+            assert_eq!(1, xs.len());
+            assert!((xs[0] - t).abs() < 0.0001, "expected {} but got {}", t, xs[0]);
+        }
+    }
+
+    #[test]
+    fn ray_crossing_many_cells_finds_the_first_hit() {
+        // A flat field with a single raised spike in the middle.
+        let mut heights = vec![vec![0.0; 11]; 11];
+        heights[5][5] = 1.0;
+        let h = Heightfield::new(heights);
+
+        let r = Ray::new(Point::new(-1.0, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0));
+        let xs = h.intersects(&r);
+        assert_eq!(1, xs.len());
+        assert!((xs[0] - 1.45).abs() < 0.0001);
+
+        let r = Ray::new(Point::new(-1.0, 0.01, 0.5), Vector::new(1.0, 0.0, 0.0));
+        assert!((h.intersects(&r)[0] - 1.401).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_misses_the_heightfield() {
+        let h = ramp();
+        let examples = vec![
+            (Point::new(-1.0, 5.0, 0.5), Vector::new(0.0, -1.0, 0.0)),
+            (Point::new(0.5, 5.0, 2.0), Vector::new(0.0, -1.0, 0.0)),
+            (Point::new(-1.0, 2.0, 0.5), Vector::new(1.0, 0.0, 0.0)),
+            (Point::new(0.5, 5.0, 0.5), Vector::new(0.0, 1.0, 0.0))
+        ];
+
+        for (origin, direction) in examples {
+            assert!(h.intersects(&Ray::new(origin, direction)).is_empty());
+        }
+    }
+
+    #[test]
+    fn ray_along_the_grid_passes_between_rising_cells() {
+        let h = ramp();
+        let r = Ray::new(Point::new(1.5, 0.75, 0.5), Vector::new(-1.0, 0.0, 0.0));
+        let xs = h.intersects(&r);
+        assert_eq!(1, xs.len());
+        assert!((xs[0] - 0.75).abs() < 0.0001);
+    }
+
+    #[test]
+    fn normals_follow_the_slope() {
+        let h = ramp();
+        let n = Vector::new(-1.0, 1.0, 0.0).normalize();
+        assert_eq!(n, h.normal_at(Point::new(0.5, 0.5, 0.5)));
+        assert_eq!(n, h.normal_at(Point::new(0.1, 0.1, 0.9)));
+    }
+
+    #[test]
+    fn normals_are_interpolated_across_cells() {
+        let h = Heightfield::new(vec![
+            vec![0.0, 0.0, 1.0],
+            vec![0.0, 0.0, 1.0]
+        ]);
+
+        let flat = h.normal_at(Point::new(0.0, 0.0, 0.0));
+        let middle = h.normal_at(Point::new(0.5, 0.0, 0.0));
+        let steep = h.normal_at(Point::new(1.0, 1.0, 0.0));
+
+        assert_eq!(Vector::new(0.0, 1.0, 0.0), flat);
+        assert_eq!(Vector::new(-1.0, 1.0, 0.0).normalize(), middle);
+        assert_eq!(Vector::new(-2.0, 1.0, 0.0).normalize(), steep);
+        assert!(h.normal_at(Point::new(0.25, 0.0, 0.0)).x > middle.x);
+    }
+}
@@ -0,0 +1,428 @@
+// Indexed triangle meshes: a list of vertices, with an optional normal and
+// color for each, and triangles made of three indices into them.
+//
+// Triangles are wound the same way as `Shape::triangle`, whose normal is
+// (p3 - p1) x (p2 - p1).
+
+use crate::{Color, Material, Matrix4x4, Pattern, Point, Shape, Vector};
+use std::collections::HashMap;
+
+// How close vertices must be for `weld` to merge them.
+const WELD_TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Point>,
+    pub normals: Vec<Vector>,
+    pub colors: Vec<Color>,
+    pub triangles: Vec<[usize; 3]>
+}
+
+impl Mesh {
+    pub fn new() -> Mesh {
+        Mesh::default()
+    }
+
+    // Whether every vertex has a normal of its own.
+    pub fn has_normals(&self) -> bool {
+        !self.vertices.is_empty() && self.normals.len() == self.vertices.len()
+    }
+
+    // Whether every vertex has a color of its own.
+    pub fn has_colors(&self) -> bool {
+        !self.vertices.is_empty() && self.colors.len() == self.vertices.len()
+    }
+
+    pub fn face_normal(&self, triangle: [usize; 3]) -> Vector {
+        let [p1, p2, p3] = triangle.map(|i| self.vertices[i]);
+        (p3 - p1).cross(p2 - p1)
+    }
+
+    // Adds the vertices and triangles of `other` to this mesh. Normals and
+    // colors are only kept if both meshes have them.
+    pub fn append(&mut self, other: Mesh) {
+        let offset = self.vertices.len();
+        let keep_normals = offset == 0 || self.has_normals() == other.has_normals();
+        let keep_colors = offset == 0 || self.has_colors() == other.has_colors();
+
+        if keep_normals {
+            self.normals.extend(other.normals);
+        } else {
+            self.normals.clear();
+        }
+
+        if keep_colors {
+            self.colors.extend(other.colors);
+        } else {
+            self.colors.clear();
+        }
+
+        self.vertices.extend(other.vertices);
+        self.triangles.extend(other.triangles.iter().map(|t| t.map(|i| i + offset)));
+    }
+
+    pub fn transform(&self, m: Matrix4x4) -> Mesh {
+        let normal_transform = m.inverse().unwrap().transpose();
+
+        Mesh {
+            vertices: self.vertices.iter().map(|p| m * *p).collect(),
+            normals: self.normals.iter().map(|n| (normal_transform * *n).normalize()).collect(),
+            colors: self.colors.clone(),
+            triangles: self.triangles.clone()
+        }
+    }
+
+    // Merges vertices at the same position, dropping any triangles that
+    // collapse as a result. Merged vertices share the average of their
+    // normals and colors.
+    pub fn weld(&self) -> Mesh {
+        self.weld_within(WELD_TOLERANCE)
+    }
+
+    // Like `weld`, but merges vertices up to `tolerance` apart, which joins
+    // up meshes whose neighbouring triangles don't quite meet. Each vertex
+    // is merged into the first one found within the tolerance of it.
+    pub fn weld_within(&self, tolerance: f64) -> Mesh {
+        let cell_size = tolerance.max(WELD_TOLERANCE);
+        let cell = |p: Point| [p.x, p.y, p.z].map(|c| (c / cell_size).floor() as i64);
+        let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        let mut welded = Mesh::new();
+        let mut normals = vec![];
+        let mut remap = vec![];
+
+        for p in &self.vertices {
+            let [x, y, z] = cell(*p);
+
+            // A vertex within the tolerance must be in the same cell or
+            // one of its neighbours.
+            let nearby = (-1..=1).
+                flat_map(|dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| [x + dx, y + dy, z + dz]))).
+                filter_map(|key| cells.get(&key)).
+                flatten().
+                copied().
+                filter(|i| (welded.vertices[*i] - *p).magnitude() <= tolerance).
+                min();
+
+            let index = match nearby {
+                Some(index) => index,
+                None => {
+                    welded.vertices.push(*p);
+                    normals.push(Vector::new(0.0, 0.0, 0.0));
+                    cells.entry([x, y, z]).or_default().push(welded.vertices.len() - 1);
+                    welded.vertices.len() - 1
+                }
+            };
+
+            remap.push(index);
+        }
+
+        if self.has_normals() {
+            for (i, n) in self.normals.iter().enumerate() {
+                normals[remap[i]] = normals[remap[i]] + *n;
+            }
+
+            welded.normals = normals.into_iter().map(normalize_or_zero).collect();
+        }
+
+        if self.has_colors() {
+            let mut colors = vec![(Color::black(), 0.0); welded.vertices.len()];
+
+            for (i, c) in self.colors.iter().enumerate() {
+                let (sum, count) = colors[remap[i]];
+                colors[remap[i]] = (sum + *c, count + 1.0);
+            }
+
+            welded.colors = colors.into_iter().map(|(sum, count)| sum * (1.0 / count)).collect();
+        }
+
+        welded.triangles = self.triangles.
+            iter().
+            map(|t| t.map(|i| remap[i])).
+            filter(|[a, b, c]| a != b && b != c && a != c).
+            collect();
+
+        welded
+    }
+
+    // Turns every triangle to face the other way, reversing its winding
+    // and its normals.
+    pub fn flip(&self) -> Mesh {
+        Mesh {
+            vertices: self.vertices.clone(),
+            normals: self.normals.iter().map(|n| -*n).collect(),
+            colors: self.colors.clone(),
+            triangles: self.triangles.iter().map(|[a, b, c]| [*a, *c, *b]).collect()
+        }
+    }
+
+    // Replaces the normals with the average of the faces around each
+    // vertex, weighted by their area.
+    pub fn compute_normals(&self) -> Mesh {
+        let mut normals = vec![Vector::new(0.0, 0.0, 0.0); self.vertices.len()];
+
+        for triangle in &self.triangles {
+            let face = self.face_normal(*triangle);
+
+            for i in triangle {
+                normals[*i] = normals[*i] + face;
+            }
+        }
+
+        Mesh {
+            vertices: self.vertices.clone(),
+            normals: normals.
+                into_iter().
+                map(normalize_or_zero).
+                collect(),
+            colors: self.colors.clone(),
+            triangles: self.triangles.clone()
+        }
+    }
+
+    // Gives each corner of each triangle the area weighted average of the
+    // normals of the triangles around it that meet its own at less than
+    // `crease_angle` radians, so that sharp edges stay sharp. Vertices on a
+    // crease are split into one for each side.
+    pub fn smooth(&self, crease_angle: f64) -> Mesh {
+        let limit = crease_angle.cos();
+        let faces: Vec<Vector> = self.triangles.iter().map(|t| self.face_normal(*t)).collect();
+        let units: Vec<Vector> = faces.iter().map(|n| normalize_or_zero(*n)).collect();
+        let mut around = vec![vec![]; self.vertices.len()];
+
+        for (i, triangle) in self.triangles.iter().enumerate() {
+            for v in triangle {
+                around[*v].push(i);
+            }
+        }
+
+        let key = |v: usize, n: Vector| (v, (n.x * 1e6).round() as i64, (n.y * 1e6).round() as i64, (n.z * 1e6).round() as i64);
+        let mut corners: HashMap<(usize, i64, i64, i64), usize> = HashMap::new();
+        let mut smoothed = Mesh::new();
+
+        for (i, triangle) in self.triangles.iter().enumerate() {
+            let mut corner_indices = [0; 3];
+
+            for (corner, v) in triangle.iter().enumerate() {
+                let normal = normalize_or_zero(
+                    around[*v].
+                        iter().
+                        filter(|j| **j == i || units[**j].dot(units[i]) >= limit).
+                        fold(Vector::new(0.0, 0.0, 0.0), |sum, j| sum + faces[*j]));
+
+                corner_indices[corner] = *corners.entry(key(*v, normal)).or_insert_with(|| {
+                    smoothed.vertices.push(self.vertices[*v]);
+                    smoothed.normals.push(normal);
+                    if self.has_colors() {
+                        smoothed.colors.push(self.colors[*v]);
+                    }
+                    smoothed.vertices.len() - 1
+                });
+            }
+
+            smoothed.triangles.push(corner_indices);
+        }
+
+        smoothed
+    }
+
+    // Builds a group of triangles with the given material, smooth if the
+    // mesh has normals. If the mesh has colors, each triangle's material
+    // blends the colors of its corners instead of using the material's own
+    // pattern. Degenerate triangles are left out.
+    pub fn to_shape(&self, material: &Material) -> Shape {
+        let triangles = self.triangles.
+            iter().
+            filter(|t| self.face_normal(**t).magnitude() > 1e-12).
+            map(|&[a, b, c]| {
+                let (p1, p2, p3) = (self.vertices[a], self.vertices[b], self.vertices[c]);
+
+                let material = if self.has_colors() {
+                    material.clone().pattern(Pattern::vertex_colors(
+                        [p1, p2, p3],
+                        [self.colors[a], self.colors[b], self.colors[c]]))
+                } else {
+                    material.clone()
+                };
+
+                if self.has_normals() {
+                    Shape::smooth_triangle(p1, p2, p3, self.normals[a], self.normals[b], self.normals[c])
+                } else {
+                    Shape::triangle(p1, p2, p3)
+                }.material(material)
+            }).
+            collect();
+
+        Shape::group(triangles)
+    }
+}
+
+// Leaves zero normals alone, as at the apex of a cone, rather than
+// dividing by zero.
+fn normalize_or_zero(n: Vector) -> Vector {
+    if n.magnitude() > 0.0 { n.normalize() } else { n }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A unit square in the xz plane, facing up, split along its diagonal.
+    fn square() -> Mesh {
+        Mesh {
+            vertices: vec![
+                Point::new(0.0, 0.0, 0.0),
+                Point::new(1.0, 0.0, 0.0),
+                Point::new(0.0, 0.0, 1.0),
+                Point::new(1.0, 0.0, 1.0)
+            ],
+            normals: vec![],
+            colors: vec![],
+            triangles: vec![[0, 1, 2], [1, 3, 2]]
+        }
+    }
+
+    #[test]
+    fn face_normals_follow_the_triangle_winding() {
+        let mesh = square();
+        assert_eq!(Vector::new(0.0, 1.0, 0.0), mesh.face_normal(mesh.triangles[0]));
+        assert_eq!(
+            Shape::triangle(mesh.vertices[0], mesh.vertices[1], mesh.vertices[2]).normal_at(Point::origin()),
+            mesh.face_normal(mesh.triangles[0]));
+    }
+
+    #[test]
+    fn appending_meshes_offsets_their_indices() {
+        let mut mesh = square();
+        mesh.append(square());
+
+        assert_eq!(8, mesh.vertices.len());
+        assert_eq!([5, 7, 6], mesh.triangles[3]);
+    }
+
+    #[test]
+    fn transforming_a_mesh() {
+        let mesh = square().compute_normals().transform(Matrix4x4::identity().rotation_x(std::f64::consts::PI / 2.0));
+
+        assert_eq!(Point::new(0.0, -1.0, 0.0), mesh.vertices[2]);
+        assert_eq!(Vector::new(0.0, 0.0, 1.0), mesh.normals[0]);
+    }
+
+    #[test]
+    fn welding_merges_matching_vertices() {
+        let mut mesh = square();
+        mesh.append(square());
+        mesh.triangles.push([0, 4, 1]);
+        let welded = mesh.weld();
+
+        assert_eq!(4, welded.vertices.len());
+        assert_eq!(4, welded.triangles.len());
+        assert_eq!([1, 3, 2], welded.triangles[3]);
+    }
+
+    #[test]
+    fn welding_averages_normals() {
+        let mut mesh = square().compute_normals();
+        let mut side = square().compute_normals().transform(Matrix4x4::identity().rotation_z(std::f64::consts::PI / 2.0));
+        side.triangles.clear();
+        mesh.append(side);
+        let welded = mesh.weld();
+
+        // The origin and (0, 0, 1) are on both the square and its rotated
+        // copy, which faces along -x.
+        assert_eq!(6, welded.vertices.len());
+        assert_eq!(Vector::new(-1.0, 1.0, 0.0).normalize(), welded.normals[0]);
+        assert_eq!(Vector::new(0.0, 1.0, 0.0), welded.normals[1]);
+    }
+
+    #[test]
+    fn welding_within_a_tolerance() {
+        let mut mesh = square();
+        let mut nudged = square().transform(Matrix4x4::identity().translate(0.001, 0.0, -0.001));
+        nudged.triangles.clear();
+        mesh.append(nudged);
+
+        assert_eq!(8, mesh.weld().vertices.len());
+
+        let welded = mesh.weld_within(0.01);
+        assert_eq!(4, welded.vertices.len());
+        assert_eq!(mesh.vertices[..4], welded.vertices[..]);
+        assert_eq!(square().triangles, welded.triangles);
+    }
+
+    #[test]
+    fn flipping_reverses_the_winding_and_normals() {
+        let mesh = square().compute_normals().flip();
+
+        assert_eq!([0, 2, 1], mesh.triangles[0]);
+        assert_eq!(Vector::new(0.0, -1.0, 0.0), mesh.face_normal(mesh.triangles[1]));
+        assert_eq!(Vector::new(0.0, -1.0, 0.0), mesh.normals[3]);
+    }
+
+    #[test]
+    fn computed_normals_average_the_faces_around_a_vertex() {
+        // Fold the square sharply along its diagonal.
+        let mut mesh = square();
+        mesh.vertices[3] = Point::new(0.0, -1.0, 0.0);
+        let crease = mesh.face_normal([1, 3, 2]);
+        let mesh = mesh.compute_normals();
+
+        assert_eq!(Vector::new(0.0, 1.0, 0.0), mesh.normals[0]);
+        assert_eq!(crease.normalize(), mesh.normals[3]);
+        assert_eq!((Vector::new(0.0, 1.0, 0.0) + crease).normalize(), mesh.normals[1]);
+    }
+
+    #[test]
+    fn converting_a_mesh_to_a_shape() {
+        let material = Material::new().ambient(0.5);
+        let flat = square().to_shape(&material);
+        let smooth = square().compute_normals().to_shape(&material);
+
+        assert_eq!(2, flat.children().len());
+        assert_eq!(material, flat.children()[1].material);
+        assert_eq!(
+            Shape::triangle(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0), Point::new(0.0, 0.0, 1.0)).material(material.clone()),
+            flat.children()[0]);
+        assert_eq!(2, smooth.children().len());
+    }
+
+    #[test]
+    fn welding_averages_colors() {
+        let mut mesh = square();
+        mesh.colors = vec![Color::white(); 4];
+        let mut other = square();
+        other.colors = vec![Color::black(); 4];
+        mesh.append(other);
+        let welded = mesh.weld();
+
+        assert_eq!(vec![Color::new(0.5, 0.5, 0.5); 4], welded.colors);
+    }
+
+    #[test]
+    fn colored_meshes_color_their_triangles() {
+        let mut mesh = square();
+        mesh.colors = vec![Color::new(1.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0), Color::new(0.0, 0.0, 1.0), Color::white()];
+        let shape = mesh.to_shape(&Material::new().ambient(0.5));
+        let first = &shape.children()[0].material;
+
+        assert_eq!(0.5, first.ambient);
+        assert_eq!(Color::new(0.0, 1.0, 0.0), first.pattern.color_at(Point::new(1.0, 0.0, 0.0)));
+        assert_eq!(Color::white(), shape.children()[1].material.pattern.color_at(Point::new(1.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn smoothing_keeps_creases_sharper_than_the_crease_angle() {
+        // Fold the square sharply along its diagonal.
+        let mut mesh = square();
+        mesh.vertices[3] = Point::new(0.0, -1.0, 0.0);
+        let crease = mesh.face_normal([1, 3, 2]).normalize();
+
+        let sharp = mesh.smooth(std::f64::consts::PI / 4.0);
+        assert_eq!(6, sharp.vertices.len());
+        assert_eq!(Vector::new(0.0, 1.0, 0.0), sharp.normals[sharp.triangles[0][1]]);
+        assert_eq!(crease, sharp.normals[sharp.triangles[1][0]]);
+
+        let smooth = mesh.smooth(std::f64::consts::PI * 0.75);
+        assert_eq!(4, smooth.vertices.len());
+        assert_eq!(mesh.compute_normals().normals[1], smooth.normals[smooth.triangles[0][1]]);
+    }
+}
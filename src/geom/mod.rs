@@ -1,31 +1,467 @@
 pub use crate::{Intersection, Material, Matrix4x4, Ray, Point, Vector};
+use crate::polynomial;
+use std::sync::Arc;
+
+pub use bezier::BezierPatch;
+pub use blob::Blob;
+pub use bounds::Bounds;
+pub use heightfield::Heightfield;
+pub use mesh::Mesh;
+pub use motion::Motion;
+pub use primitive::Primitive;
+pub use quadric::Quadric;
+pub use sdf::DistanceField;
+pub use volume::{Medium, Phase};
+
+pub mod bezier;
+pub mod blob;
+pub mod bounds;
+pub mod heightfield;
+pub mod mesh;
+pub mod motion;
+pub mod primitive;
+pub mod quadric;
+pub mod sdf;
+pub mod tessellate;
+pub mod volume;
+
+const EPSILON: f64 = 0.0001;
+const NO_SURFACE: &str = "groups, CSG, instances and volumes have no surface of their own";
 
 #[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CsgOperation {
+    Difference,
+    Intersection,
+    Union
+}
+
+impl CsgOperation {
+    // Decides whether a hit on one operand is on the surface of the
+    // combined shape, given which operand was hit and whether the hit is
+    // currently inside each operand.
+    fn allows(&self, left_hit: bool, in_left: bool, in_right: bool) -> bool {
+        match self {
+            Self::Difference => (left_hit && !in_right) || (!left_hit && in_left),
+            Self::Intersection => (left_hit && in_right) || (!left_hit && in_left),
+            Self::Union => (left_hit && !in_right) || (!left_hit && !in_left)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 enum ShapeType {
+    BezierPatch(Box<BezierPatch>),
+    Blob(Blob),
+    Cone { minimum: f64, maximum: f64, closed: bool },
+    Csg { operation: CsgOperation, operands: Box<[Shape; 2]> },
+    Cube,
+    Cylinder { minimum: f64, maximum: f64, closed: bool },
+    Disk { inner_radius: f64, radius: f64 },
+    DistanceField(DistanceField),
+    Group { children: Vec<Shape>, bounds: Bounds },
+    Heightfield(Heightfield),
+    Instance { geometry: Arc<Shape>, overrides_material: bool },
     Plane,
+    Primitive(Arc<dyn Primitive>),
+    Quadric(Quadric),
+    Rectangle { width: f64, depth: f64 },
+    SmoothTriangle { p1: Point, p2: Point, p3: Point, n1: Vector, n2: Vector, n3: Vector, e1: Vector, e2: Vector },
     Sphere,
-    TestShape
+    TestShape,
+    Torus { major: f64, minor: f64 },
+    Triangle { p1: Point, p2: Point, p3: Point, e1: Vector, e2: Vector, normal: Vector },
+    Volume { boundary: Box<Shape>, medium: Medium }
 }
 
 impl ShapeType {
     pub fn intersects(&self, local_ray: &Ray) -> Vec<f64> {
         match self {
+            Self::BezierPatch(patch) =>
+                patch.intersects(local_ray).
+                    into_iter().
+                    map(|(t, _, _)| t).
+                    collect(),
+            Self::Blob(blob) => blob.intersects(local_ray),
+            Self::Cone { minimum, maximum, closed } =>
+                Self::cone_intersection(local_ray, *minimum, *maximum, *closed),
+            Self::Cube => Self::cube_intersection(local_ray),
+            Self::Cylinder { minimum, maximum, closed } =>
+                Self::cylinder_intersection(local_ray, *minimum, *maximum, *closed),
+            Self::Csg { .. } | Self::Group { .. } | Self::Instance { .. } =>
+                unreachable!("composite shapes are intersected through their children"),
+            Self::Disk { inner_radius, radius } =>
+                Self::disk_intersection(local_ray, *inner_radius, *radius),
+            Self::DistanceField(field) => field.intersects(local_ray),
+            Self::Heightfield(heightfield) => heightfield.intersects(local_ray),
             Self::Plane => Self::plane_intersection(local_ray),
+            Self::Primitive(primitive) => primitive.intersects(local_ray),
+            Self::Quadric(quadric) => quadric.intersects(local_ray),
+            Self::Rectangle { width, depth } =>
+                Self::rectangle_intersection(local_ray, *width, *depth),
+            Self::SmoothTriangle { p1, e1, e2, .. } |
+            Self::Triangle { p1, e1, e2, .. } =>
+                Self::triangle_intersection(local_ray, *p1, *e1, *e2).
+                    into_iter().
+                    map(|(t, _, _)| t).
+                    collect(),
             Self::Sphere => Self::sphere_intersection(local_ray),
-            Self::TestShape => vec![]
+            Self::TestShape => vec![],
+            Self::Torus { major, minor } => Self::torus_intersection(local_ray, *major, *minor),
+            // Rays pass through volumes, which the world deals with separately.
+            Self::Volume { .. } => vec![]
         }
     }
 
-    pub fn normal_at(&self, point: Point) -> Vector {
-        match self {
-            Self::Plane => Vector::new(0.0, 1.0, 0.0),
+    pub fn normal_at(&self, point: Point) -> Option<Vector> {
+        let normal = match self {
+            Self::BezierPatch(patch) => patch.normal_at(point),
+            Self::Blob(blob) => blob.normal_at(point),
+            Self::Cone { minimum, maximum, .. } =>
+                Self::cone_normal_at(point, *minimum, *maximum),
+            Self::Cube => Self::cube_normal_at(point),
+            Self::Cylinder { minimum, maximum, .. } =>
+                Self::cylinder_normal_at(point, *minimum, *maximum),
+            Self::Csg { .. } | Self::Group { .. } | Self::Instance { .. } | Self::Volume { .. } => return None,
+            Self::DistanceField(field) => field.normal_at(point),
+            Self::Disk { .. } | Self::Plane | Self::Rectangle { .. } => Vector::new(0.0, 1.0, 0.0),
+            Self::Heightfield(heightfield) => heightfield.normal_at(point),
+            Self::Primitive(primitive) => primitive.normal_at(point),
+            Self::Quadric(quadric) => quadric.normal_at(point),
+            Self::SmoothTriangle { p1, e1, e2, .. } => {
+                let (u, v) = Self::barycentric(point, *p1, *e1, *e2);
+                return self.normal_at_uv(point, u, v);
+            },
             Self::Sphere => point - Point::origin(),
-            Self::TestShape => Vector::new(0.0, 0.0, 0.0)
+            Self::TestShape => Vector::new(0.0, 0.0, 0.0),
+            Self::Torus { major, minor } => Self::torus_normal_at(point, *major, *minor),
+            Self::Triangle { normal, .. } => *normal
+        };
+
+        Some(normal)
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        match self {
+            Self::BezierPatch(patch) => patch.bounds(),
+            Self::Blob(blob) => blob.bounds(),
+            Self::Cone { minimum, maximum, .. } => {
+                let radius = minimum.abs().max(maximum.abs());
+                Bounds::new(Point::new(-radius, *minimum, -radius), Point::new(radius, *maximum, radius))
+            },
+            Self::Csg { operands, .. } => operands[0].bounds().merge(operands[1].bounds()),
+            Self::Cube | Self::Sphere | Self::TestShape =>
+                Bounds::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0)),
+            Self::Cylinder { minimum, maximum, .. } =>
+                Bounds::new(Point::new(-1.0, *minimum, -1.0), Point::new(1.0, *maximum, 1.0)),
+            Self::Disk { radius, .. } =>
+                Bounds::new(Point::new(-radius, 0.0, -radius), Point::new(*radius, 0.0, *radius)),
+            // A distance function says nothing about how far its surface
+            // reaches.
+            Self::DistanceField(_) => Bounds::infinite(),
+            Self::Group { bounds, .. } => *bounds,
+            Self::Heightfield(heightfield) => heightfield.bounds(),
+            Self::Instance { geometry, .. } => geometry.bounds(),
+            Self::Plane => Bounds::new(
+                Point::new(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+                Point::new(f64::INFINITY, 0.0, f64::INFINITY)),
+            Self::Primitive(primitive) => primitive.bounds(),
+            Self::Quadric(quadric) => quadric.bounds(),
+            Self::Rectangle { width, depth } =>
+                Bounds::new(Point::new(-width / 2.0, 0.0, -depth / 2.0), Point::new(width / 2.0, 0.0, depth / 2.0)),
+            Self::SmoothTriangle { p1, p2, p3, .. } |
+            Self::Triangle { p1, p2, p3, .. } => Bounds::from_points(vec![*p1, *p2, *p3]),
+            Self::Torus { major, minor } => {
+                let radius = major + minor;
+                Bounds::new(Point::new(-radius, -minor, -radius), Point::new(radius, *minor, radius))
+            },
+            Self::Volume { boundary, .. } => boundary.bounds()
+        }
+    }
+
+    pub fn normal_at_uv(&self, point: Point, u: f64, v: f64) -> Option<Vector> {
+        match self {
+            Self::BezierPatch(patch) => Some(patch.normal_at_uv(u, v)),
+            Self::SmoothTriangle { n1, n2, n3, .. } => Some(*n2 * u + *n3 * v + *n1 * (1.0 - u - v)),
+            _ => self.normal_at(point)
+        }
+    }
+
+    fn cone_intersection(ray: &Ray, minimum: f64, maximum: f64, closed: bool) -> Vec<f64> {
+        let mut xs = vec![];
+        let a = ray.direction.x.powi(2) - ray.direction.y.powi(2) + ray.direction.z.powi(2);
+        let b = 2.0 * ray.origin.x * ray.direction.x -
+            2.0 * ray.origin.y * ray.direction.y +
+            2.0 * ray.origin.z * ray.direction.z;
+        let c = ray.origin.x.powi(2) - ray.origin.y.powi(2) + ray.origin.z.powi(2);
+
+        let ts = if a.abs() < EPSILON {
+            // The ray is parallel to one of the cone's halves, so it can
+            // cross the other half at most once.
+            if b.abs() < EPSILON {
+                vec![]
+            } else {
+                vec![-c / (2.0 * b)]
+            }
+        } else {
+            let discriminant = b.powi(2) - 4.0 * a * c;
+
+            if discriminant < 0.0 {
+                return xs;
+            }
+
+            let t0 = (-b - discriminant.sqrt()) / (2.0 * a);
+            let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
+            vec![t0.min(t1), t0.max(t1)]
+        };
+
+        for t in ts {
+            let y = ray.origin.y + t * ray.direction.y;
+
+            if minimum < y && y < maximum {
+                xs.push(t);
+            }
+        }
+
+        if closed {
+            xs.extend(Self::caps_intersection(ray, minimum, maximum, f64::abs));
+        }
+
+        xs
+    }
+
+    fn cone_normal_at(point: Point, minimum: f64, maximum: f64) -> Vector {
+        let dist = point.x.powi(2) + point.z.powi(2);
+
+        if dist < maximum.powi(2) && point.y >= maximum - EPSILON {
+            Vector::new(0.0, 1.0, 0.0)
+        } else if dist < minimum.powi(2) && point.y <= minimum + EPSILON {
+            Vector::new(0.0, -1.0, 0.0)
+        } else {
+            let y = if point.y > 0.0 { -dist.sqrt() } else { dist.sqrt() };
+            Vector::new(point.x, y, point.z)
+        }
+    }
+
+    fn cube_intersection(ray: &Ray) -> Vec<f64> {
+        let (xtmin, xtmax) = Self::check_axis(ray.origin.x, ray.direction.x);
+        let (ytmin, ytmax) = Self::check_axis(ray.origin.y, ray.direction.y);
+        let (ztmin, ztmax) = Self::check_axis(ray.origin.z, ray.direction.z);
+
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+
+        if tmin > tmax {
+            vec![]
+        } else {
+            vec![tmin, tmax]
         }
     }
 
+    // Finds where a ray enters and leaves the slab between -1 and 1 on a
+    // single axis. A ray parallel to the slab either never enters it or
+    // never leaves it.
+    fn check_axis(origin: f64, direction: f64) -> (f64, f64) {
+        let tmin_numerator = -1.0 - origin;
+        let tmax_numerator = 1.0 - origin;
+
+        let (tmin, tmax) = if direction.abs() >= EPSILON {
+            (tmin_numerator / direction, tmax_numerator / direction)
+        } else {
+            (tmin_numerator * f64::INFINITY, tmax_numerator * f64::INFINITY)
+        };
+
+        if tmin > tmax {
+            (tmax, tmin)
+        } else {
+            (tmin, tmax)
+        }
+    }
+
+    fn cube_normal_at(point: Point) -> Vector {
+        let maxc = point.x.abs().max(point.y.abs()).max(point.z.abs());
+
+        if maxc == point.x.abs() {
+            Vector::new(point.x, 0.0, 0.0)
+        } else if maxc == point.y.abs() {
+            Vector::new(0.0, point.y, 0.0)
+        } else {
+            Vector::new(0.0, 0.0, point.z)
+        }
+    }
+
+    fn cylinder_intersection(ray: &Ray, minimum: f64, maximum: f64, closed: bool) -> Vec<f64> {
+        let mut xs = vec![];
+        let a = ray.direction.x.powi(2) + ray.direction.z.powi(2);
+
+        // A ray parallel to the y axis can only hit the caps.
+        if a.abs() >= EPSILON {
+            let b = 2.0 * ray.origin.x * ray.direction.x + 2.0 * ray.origin.z * ray.direction.z;
+            let c = ray.origin.x.powi(2) + ray.origin.z.powi(2) - 1.0;
+            let discriminant = b.powi(2) - 4.0 * a * c;
+
+            if discriminant < 0.0 {
+                return xs;
+            }
+
+            let t0 = (-b - discriminant.sqrt()) / (2.0 * a);
+            let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
+
+            for t in [t0.min(t1), t0.max(t1)] {
+                let y = ray.origin.y + t * ray.direction.y;
+
+                if minimum < y && y < maximum {
+                    xs.push(t);
+                }
+            }
+        }
+
+        if closed {
+            xs.extend(Self::caps_intersection(ray, minimum, maximum, |_| 1.0));
+        }
+
+        xs
+    }
+
+    // Intersects the ray with the end caps at `minimum` and `maximum`, where
+    // `radius` gives the radius of the cap at a given y.
+    fn caps_intersection(ray: &Ray, minimum: f64, maximum: f64, radius: impl Fn(f64) -> f64) -> Vec<f64> {
+        if ray.direction.y.abs() < EPSILON {
+            return vec![];
+        }
+
+        [minimum, maximum].
+            iter().
+            map(|y| ((y - ray.origin.y) / ray.direction.y, radius(*y))).
+            filter(|(t, r)| {
+                let x = ray.origin.x + t * ray.direction.x;
+                let z = ray.origin.z + t * ray.direction.z;
+                x.powi(2) + z.powi(2) <= r.powi(2)
+            }).
+            map(|(t, _)| t).
+            collect()
+    }
+
+    fn cylinder_normal_at(point: Point, minimum: f64, maximum: f64) -> Vector {
+        let dist = point.x.powi(2) + point.z.powi(2);
+
+        if dist < 1.0 && point.y >= maximum - EPSILON {
+            Vector::new(0.0, 1.0, 0.0)
+        } else if dist < 1.0 && point.y <= minimum + EPSILON {
+            Vector::new(0.0, -1.0, 0.0)
+        } else {
+            Vector::new(point.x, 0.0, point.z)
+        }
+    }
+
+    // Intersects a torus lying in the xz plane, centred on the origin, by
+    // solving the quartic in t. Far away rays make that quartic badly
+    // conditioned, so the ray is first advanced to the torus' bounding sphere
+    // and given a unit direction, and the roots mapped back afterwards.
+    fn torus_intersection(ray: &Ray, major: f64, minor: f64) -> Vec<f64> {
+        let length = ray.direction.magnitude();
+        let direction = ray.direction / length;
+        let to_origin = ray.origin - Point::origin();
+
+        let bound = major + minor;
+        let b = direction.dot(to_origin);
+        let c = to_origin.dot(to_origin) - bound.powi(2);
+        let discriminant = b.powi(2) - c;
+
+        if discriminant < 0.0 {
+            return vec![];
+        }
+
+        let start = -b - discriminant.sqrt();
+        let origin = ray.origin + direction * start;
+        let o = origin - Point::origin();
+
+        let four_r2 = 4.0 * major.powi(2);
+        let f = o.dot(direction);
+        let e = o.dot(o) - major.powi(2) - minor.powi(2);
+
+        polynomial::solve_quartic(
+            1.0,
+            4.0 * f,
+            2.0 * e + 4.0 * f.powi(2) + four_r2 * direction.y.powi(2),
+            4.0 * f * e + 2.0 * four_r2 * o.y * direction.y,
+            e.powi(2) - four_r2 * (minor.powi(2) - o.y.powi(2))).
+            iter().
+            map(|t| (t + start) / length).
+            collect()
+    }
+
+    fn torus_normal_at(point: Point, major: f64, minor: f64) -> Vector {
+        let p = point - Point::origin();
+        let param = p.dot(p) - major.powi(2) - minor.powi(2);
+
+        Vector::new(point.x * param, point.y * (param + 2.0 * major.powi(2)), point.z * param)
+    }
+
+    // Möller–Trumbore intersection, returning the distance along the ray
+    // and the barycentric coordinates (u, v) of the hit.
+    fn triangle_intersection(ray: &Ray, p1: Point, e1: Vector, e2: Vector) -> Option<(f64, f64, f64)> {
+        let dir_cross_e2 = ray.direction.cross(e2);
+        let det = e1.dot(dir_cross_e2);
+
+        if det.abs() < EPSILON {
+            return None;
+        }
+
+        let f = 1.0 / det;
+        let p1_to_origin = ray.origin - p1;
+        let u = f * p1_to_origin.dot(dir_cross_e2);
+
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let origin_cross_e1 = p1_to_origin.cross(e1);
+        let v = f * ray.direction.dot(origin_cross_e1);
+
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        Some((f * e2.dot(origin_cross_e1), u, v))
+    }
+
+    // Recovers the barycentric coordinates of a point lying in the plane of
+    // a triangle.
+    fn barycentric(point: Point, p1: Point, e1: Vector, e2: Vector) -> (f64, f64) {
+        let p = point - p1;
+        let d11 = e1.dot(e1);
+        let d12 = e1.dot(e2);
+        let d22 = e2.dot(e2);
+        let dp1 = p.dot(e1);
+        let dp2 = p.dot(e2);
+        let denom = d11 * d22 - d12 * d12;
+
+        ((d22 * dp1 - d12 * dp2) / denom, (d11 * dp2 - d12 * dp1) / denom)
+    }
+
+    fn disk_intersection(ray: &Ray, inner_radius: f64, radius: f64) -> Vec<f64> {
+        Self::plane_intersection(ray).
+            into_iter().
+            filter(|t| {
+                let p = ray.position(*t);
+                let distance = p.x.powi(2) + p.z.powi(2);
+                inner_radius.powi(2) <= distance && distance <= radius.powi(2)
+            }).
+            collect()
+    }
+
+    fn rectangle_intersection(ray: &Ray, width: f64, depth: f64) -> Vec<f64> {
+        Self::plane_intersection(ray).
+            into_iter().
+            filter(|t| {
+                let p = ray.position(*t);
+                p.x.abs() <= width / 2.0 && p.z.abs() <= depth / 2.0
+            }).
+            collect()
+    }
+
     fn plane_intersection(ray: &Ray) -> Vec<f64> {
-        if ray.direction.y.abs() < 0.0001 {
+        if ray.direction.y.abs() < EPSILON {
             vec![]
         } else {
             vec![-ray.origin.y / ray.direction.y]
@@ -56,7 +492,9 @@ pub struct Shape {
     shape_type: ShapeType,
     pub material: Material,
     pub transform: Matrix4x4,
-    pub inverse_transform: Matrix4x4
+    pub inverse_transform: Matrix4x4,
+    parent_inverse: Matrix4x4,
+    motion: Option<Motion>
 }
 
 impl Shape {
@@ -65,53 +503,377 @@ impl Shape {
             shape_type,
             material: Material::new(),
             transform: Matrix4x4::identity(),
-            inverse_transform: Matrix4x4::identity()
+            inverse_transform: Matrix4x4::identity(),
+            parent_inverse: Matrix4x4::identity(),
+            motion: None
         }
     }
 
+    pub fn csg(operation: CsgOperation, left: Shape, right: Shape) -> Shape {
+        let mut csg = Self::new(ShapeType::Csg { operation, operands: Box::new([left, right]) });
+        csg.set_parent_inverse(Matrix4x4::identity());
+        csg
+    }
+
+    // A disk of the given radius in the xz plane, centred on the origin.
+    pub fn disk(radius: f64) -> Shape {
+        Self::annulus(0.0, radius)
+    }
+
+    // A disk with a hole of `inner_radius` cut from its centre.
+    pub fn annulus(inner_radius: f64, radius: f64) -> Shape {
+        Self::new(ShapeType::Disk { inner_radius, radius })
+    }
+
+    pub fn distance_field(field: DistanceField) -> Shape {
+        Self::new(ShapeType::DistanceField(field))
+    }
+
+    pub fn group(children: Vec<Shape>) -> Shape {
+        let bounds = children.
+            iter().
+            fold(Bounds::empty(), |bounds, child| bounds.merge(child.bounds()));
+        let mut group = Self::new(ShapeType::Group { children, bounds });
+        group.set_parent_inverse(Matrix4x4::identity());
+        group
+    }
+
     pub fn test_shape() -> Shape {
         Self::new(ShapeType::TestShape)
     }
 
+    // Intersects the patch directly rather than tessellating it first.
+    pub fn bezier_patch(patch: BezierPatch) -> Shape {
+        Self::new(ShapeType::BezierPatch(Box::new(patch)))
+    }
+
+    pub fn blob(blob: Blob) -> Shape {
+        Self::new(ShapeType::Blob(blob))
+    }
+
+    // Places shared geometry in the scene without copying it, so the same
+    // mesh can appear many times with different transforms and materials.
+    pub fn instance(geometry: Arc<Shape>) -> Shape {
+        Self::new(ShapeType::Instance { geometry, overrides_material: false })
+    }
+
+    pub fn cone(minimum: f64, maximum: f64, closed: bool) -> Shape {
+        Self::new(ShapeType::Cone { minimum, maximum, closed })
+    }
+
+    pub fn cube() -> Shape {
+        Self::new(ShapeType::Cube)
+    }
+
+    pub fn cylinder(minimum: f64, maximum: f64, closed: bool) -> Shape {
+        Self::new(ShapeType::Cylinder { minimum, maximum, closed })
+    }
+
+    pub fn heightfield(heightfield: Heightfield) -> Shape {
+        Self::new(ShapeType::Heightfield(heightfield))
+    }
+
     pub fn plane() -> Shape {
         Self::new(ShapeType::Plane)
     }
 
+    // Wraps geometry defined outside the crate, which is intersected and
+    // shaded like any of the built in shapes.
+    pub fn primitive(primitive: impl Primitive + 'static) -> Shape {
+        Self::new(ShapeType::Primitive(Arc::new(primitive)))
+    }
+
+    pub fn quadric(quadric: Quadric) -> Shape {
+        Self::new(ShapeType::Quadric(quadric))
+    }
+
+    // A `width` by `depth` rectangle in the xz plane, centred on the origin.
+    pub fn rectangle(width: f64, depth: f64) -> Shape {
+        Self::new(ShapeType::Rectangle { width, depth })
+    }
+
+    pub fn smooth_triangle(p1: Point, p2: Point, p3: Point, n1: Vector, n2: Vector, n3: Vector) -> Shape {
+        Self::new(ShapeType::SmoothTriangle { p1, p2, p3, n1, n2, n3, e1: p2 - p1, e2: p3 - p1 })
+    }
+
     pub fn sphere() -> Shape {
         Self::new(ShapeType::Sphere)
     }
 
+    // A ring of tube radius `minor` swept around the y axis at a distance of
+    // `major` from the origin.
+    pub fn torus(major: f64, minor: f64) -> Shape {
+        Self::new(ShapeType::Torus { major, minor })
+    }
+
+    pub fn triangle(p1: Point, p2: Point, p3: Point) -> Shape {
+        let e1 = p2 - p1;
+        let e2 = p3 - p1;
+        let normal = e2.cross(e1).normalize();
+
+        Self::new(ShapeType::Triangle { p1, p2, p3, e1, e2, normal })
+    }
+
+    // An instance only overrides the materials of its geometry once it has
+    // been given a material of its own.
     pub fn material(self, material: Material) -> Shape {
-        Shape {
+        let mut shape = Shape {
             material,
             ..self
+        };
+
+        if let ShapeType::Instance { overrides_material, .. } = &mut shape.shape_type {
+            *overrides_material = true;
         }
+
+        shape
     }
 
+    // Replaces any motion the shape had with a fixed transform.
     pub fn transform(self, transform: Matrix4x4) -> Shape {
-        Shape{
+        let mut shape = Shape{
             transform,
             inverse_transform: transform.inverse().unwrap(),
+            motion: None,
             ..self
+        };
+
+        shape.set_parent_inverse(shape.parent_inverse);
+        shape
+    }
+
+    // Moves the shape from its current transform at time 0 to `end` at
+    // time 1.
+    pub fn motion(self, end: Matrix4x4) -> Shape {
+        let start = self.transform;
+        self.keyframes(vec![(0.0, start), (1.0, end)])
+    }
+
+    // Moves the shape through a transform at each of the given times, as
+    // seen by rays cast at those times. Anything that doesn't look at a
+    // ray's time, such as `normal_at`, sees the first keyframe.
+    pub fn keyframes(self, keyframes: Vec<(f64, Matrix4x4)>) -> Shape {
+        let motion = Motion::new(keyframes);
+        let mut shape = self.transform(motion.transform_at(f64::NEG_INFINITY));
+        shape.motion = Some(motion);
+        shape
+    }
+
+    fn inverse_transform_at(&self, time: f64) -> Matrix4x4 {
+        match &self.motion {
+            None => self.inverse_transform,
+            Some(motion) => motion.inverse_at(time)
         }
     }
 
-    pub fn intersects<'a>(&'a self, ray: &'a Ray) -> Vec<Intersection> {
-        let object_ray = ray.transform(self.inverse_transform);
-        let intersects = self.shape_type.intersects(&object_ray);
-        intersects.
-            iter().
-            map(|t| Intersection{ray: &ray, object: self, t: *t}).
+    pub fn children(&self) -> &[Shape] {
+        match &self.shape_type {
+            ShapeType::Csg { operands, .. } => &operands[..],
+            ShapeType::Group { children, .. } => children,
+            ShapeType::Instance { geometry, .. } => std::slice::from_ref(&**geometry),
+            ShapeType::Volume { boundary, .. } => std::slice::from_ref(&**boundary),
+            _ => &[]
+        }
+    }
+
+    pub fn intersects<'a>(&'a self, ray: &'a Ray) -> Vec<Intersection<'a>> {
+        self.parent_space_intersects(ray, *ray, Matrix4x4::identity())
+    }
+
+    // Intersects `parent_ray`, which is `ray` expressed in the space of this
+    // shape's parent, with `world_to_parent` taking world space there. The
+    // intersections refer back to the original ray since `t` is the same in
+    // every space.
+    fn parent_space_intersects<'a>(&'a self, ray: &'a Ray, parent_ray: Ray, world_to_parent: Matrix4x4) -> Vec<Intersection<'a>> {
+        let inverse = self.inverse_transform_at(ray.time);
+        let object_ray = parent_ray.transform(inverse);
+
+        let hits: Vec<(f64, f64, f64)> = match &self.shape_type {
+            // Groups cache the bounds of their children, so rays that miss
+            // them all can skip the group entirely.
+            ShapeType::Group { bounds, .. } if !bounds.intersects(&object_ray) => return vec![],
+            ShapeType::Group { children, .. } => {
+                let world_to_object = inverse * world_to_parent;
+                let mut intersections: Vec<Intersection> = children.
+                    iter().
+                    flat_map(|child| child.parent_space_intersects(ray, object_ray, world_to_object)).
+                    collect();
+
+                intersections.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+                return intersections;
+            },
+            ShapeType::Csg { operation, operands } => {
+                let world_to_object = inverse * world_to_parent;
+                let [left, right] = &**operands;
+
+                // Each hit is tagged with the operand it came from as it is
+                // found, since instances in both operands can share the
+                // very same geometry.
+                let mut intersections: Vec<(bool, Intersection)> = left.
+                    parent_space_intersects(ray, object_ray, world_to_object).
+                    into_iter().
+                    map(|i| (true, i)).
+                    chain(right.
+                        parent_space_intersects(ray, object_ray, world_to_object).
+                        into_iter().
+                        map(|i| (false, i))).
+                    collect();
+                intersections.sort_by(|(_, a), (_, b)| a.t.partial_cmp(&b.t).unwrap());
+
+                return Self::filter_intersections(*operation, intersections);
+            },
+            ShapeType::Instance { geometry, overrides_material } => {
+                return geometry.
+                    parent_space_intersects(ray, object_ray, inverse * world_to_parent).
+                    into_iter().
+                    map(|i| Intersection {
+                        material: if *overrides_material { &self.material } else { i.material },
+                        ..i
+                    }).
+                    collect();
+            },
+            ShapeType::BezierPatch(patch) => patch.intersects(&object_ray),
+            ShapeType::SmoothTriangle { p1, e1, e2, .. } |
+            ShapeType::Triangle { p1, e1, e2, .. } =>
+                ShapeType::triangle_intersection(&object_ray, *p1, *e1, *e2).into_iter().collect(),
+            shape_type =>
+                shape_type.intersects(&object_ray).
+                    into_iter().
+                    map(|t| (t, 0.0, 0.0)).
+                    collect()
+        };
+
+        if hits.is_empty() {
+            return vec![];
+        }
+
+        // Shared geometry and moving groups mean the transforms recorded in
+        // the shape can't be trusted at the time of the ray, so each
+        // intersection carries the way into object space it was found with.
+        let world_to_object = inverse * world_to_parent;
+
+        hits.
+            into_iter().
+            map(|(t, u, v)| Intersection {
+                world_to_object,
+                ..Intersection::new(ray, t, self).with_uv(u, v)
+            }).
             collect()
     }
 
+    // Keeps only the sorted intersections that lie on the surface of the
+    // combined shape, tracking whether each hit enters or leaves an operand.
+    // Each intersection comes with whether it was a hit on the left operand.
+    fn filter_intersections<'a>(operation: CsgOperation, intersections: Vec<(bool, Intersection<'a>)>) -> Vec<Intersection<'a>> {
+        let mut in_left = false;
+        let mut in_right = false;
+
+        intersections.
+            into_iter().
+            filter(|(left_hit, _)| {
+                let left_hit = *left_hit;
+                let allowed = operation.allows(left_hit, in_left, in_right);
+
+                if left_hit {
+                    in_left = !in_left;
+                } else {
+                    in_right = !in_right;
+                }
+
+                allowed
+            }).
+            map(|(_, i)| i).
+            collect()
+    }
+
+    // The bounds of the shape in its own object space.
+    pub fn object_bounds(&self) -> Bounds {
+        self.shape_type.bounds()
+    }
+
+    // The bounds of the shape in the space of its parent, after its own
+    // transform and covering the whole of any motion.
+    pub fn bounds(&self) -> Bounds {
+        match &self.motion {
+            None => self.object_bounds().transform(self.transform),
+            Some(motion) => motion.sweep(self.object_bounds())
+        }
+    }
+
+    // The bounds of the shape in world space, passing out through the space
+    // of every group containing it.
+    pub fn world_bounds(&self) -> Bounds {
+        let parent_to_world = self.parent_inverse.inverse().unwrap();
+
+        match &self.motion {
+            None => self.object_bounds().transform(parent_to_world * self.transform),
+            Some(_) => self.bounds().transform(parent_to_world)
+        }
+    }
+
+    // The surface normal at a world space point. Panics for groups, CSG,
+    // instances and volumes, which have no surface of their own; use
+    // `try_normal_at` for shapes that may be one of those.
     pub fn normal_at(&self, p: Point) -> Vector {
-        let object_point = self.inverse_transform * p;
-        let object_normal = self.shape_type.normal_at(object_point);
-        let world_normal = self.inverse_transform.transpose() * object_normal;
+        self.try_normal_at(p).expect(NO_SURFACE)
+    }
+
+    // Like `normal_at`, but returns None for shapes with no surface.
+    pub fn try_normal_at(&self, p: Point) -> Option<Vector> {
+        let object_point = self.world_to_object(p);
+        self.shape_type.normal_at(object_point).map(|n| self.normal_to_world(n))
+    }
+
+    // Like `normal_at`, but uses the surface coordinates recorded in an
+    // `Intersection` for shapes that interpolate their normals.
+    pub fn normal_at_uv(&self, p: Point, u: f64, v: f64) -> Vector {
+        let object_point = self.world_to_object(p);
+        self.normal_to_world(self.local_normal_at(object_point, u, v))
+    }
+
+    // The normal in object space, before any transforms are applied.
+    pub fn local_normal_at(&self, object_point: Point, u: f64, v: f64) -> Vector {
+        self.shape_type.normal_at_uv(object_point, u, v).expect(NO_SURFACE)
+    }
+
+    // The transform from world space into this shape's object space,
+    // passing through the space of every group containing it.
+    pub fn world_inverse(&self) -> Matrix4x4 {
+        self.inverse_transform * self.parent_inverse
+    }
+
+    // Converts a world space point into this shape's object space, passing
+    // through the space of every group containing it.
+    pub fn world_to_object(&self, p: Point) -> Point {
+        self.world_inverse() * p
+    }
+
+    // Converts an object space normal into world space, passing back out
+    // through the space of every group containing this shape.
+    pub fn normal_to_world(&self, object_normal: Vector) -> Vector {
+        let normal = (self.inverse_transform.transpose() * object_normal).normalize();
+        let world_normal = self.parent_inverse.transpose() * normal;
 
         world_normal.normalize()
     }
+
+    // Records the combined inverse transform of the groups containing this
+    // shape, and passes the result on to any children of its own.
+    fn set_parent_inverse(&mut self, parent_inverse: Matrix4x4) {
+        self.parent_inverse = parent_inverse;
+        let object_inverse = self.inverse_transform * parent_inverse;
+
+        let children: &mut [Shape] = match &mut self.shape_type {
+            ShapeType::Csg { operands, .. } => &mut operands[..],
+            ShapeType::Group { children, .. } => children,
+            ShapeType::Volume { boundary, .. } => std::slice::from_mut(&mut **boundary),
+            _ => &mut []
+        };
+
+        for child in children {
+            child.set_parent_inverse(object_inverse);
+        }
+    }
 }
 
 #[cfg(test)]
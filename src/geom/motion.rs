@@ -0,0 +1,323 @@
+// Transforms that change over time, for motion blur.
+//
+// Each keyframe's transform is split into a translation, a rotation and a
+// scale, which are interpolated separately between keyframes so that
+// rotating objects sweep around an arc rather than shrinking through it.
+// Rotations take the shortest way round, so a spin of more than half a turn
+// needs keyframes at least every half turn. Shears don't survive the split
+// and are lost.
+
+use super::Bounds;
+use crate::{Matrix4x4, Vector};
+
+// Each stretch between keyframes is sampled this many times when sweeping
+// bounds along the motion.
+const SWEEP_STEPS: usize = 16;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Quaternion {
+    w: f64,
+    x: f64,
+    y: f64,
+    z: f64
+}
+
+impl Quaternion {
+    // Shepperd's method, picking the largest component to divide by.
+    fn from_rotation(m: [[f64; 3]; 3]) -> Quaternion {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion { w: 0.25 * s, x: (m[2][1] - m[1][2]) / s, y: (m[0][2] - m[2][0]) / s, z: (m[1][0] - m[0][1]) / s }
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Quaternion { w: (m[2][1] - m[1][2]) / s, x: 0.25 * s, y: (m[0][1] + m[1][0]) / s, z: (m[0][2] + m[2][0]) / s }
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Quaternion { w: (m[0][2] - m[2][0]) / s, x: (m[0][1] + m[1][0]) / s, y: 0.25 * s, z: (m[1][2] + m[2][1]) / s }
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Quaternion { w: (m[1][0] - m[0][1]) / s, x: (m[0][2] + m[2][0]) / s, y: (m[1][2] + m[2][1]) / s, z: 0.25 * s }
+        }
+    }
+
+    fn rotation(&self) -> [[f64; 3]; 3] {
+        let Quaternion { w, x, y, z } = *self;
+
+        [
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w)],
+            [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w)],
+            [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y)]
+        ]
+    }
+
+    fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    fn blend(&self, a: f64, other: &Quaternion, b: f64) -> Quaternion {
+        Quaternion {
+            w: self.w * a + other.w * b,
+            x: self.x * a + other.x * b,
+            y: self.y * a + other.y * b,
+            z: self.z * a + other.z * b
+        }
+    }
+
+    fn slerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        // q and -q are the same rotation; pick whichever is nearer.
+        let (other, cos_theta) = if self.dot(other) < 0.0 {
+            (other.blend(-1.0, other, 0.0), -self.dot(other))
+        } else {
+            (*other, self.dot(other))
+        };
+
+        // Nearly identical rotations would divide by almost zero, and a
+        // straight blend is just as good there.
+        let q = if cos_theta > 0.9995 {
+            self.blend(1.0 - t, &other, t)
+        } else {
+            let theta = cos_theta.acos();
+            let a = ((1.0 - t) * theta).sin() / theta.sin();
+            let b = (t * theta).sin() / theta.sin();
+            self.blend(a, &other, b)
+        };
+
+        let length = q.dot(&q).sqrt();
+        q.blend(1.0 / length, &q, 0.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Keyframe {
+    time: f64,
+    translation: Vector,
+    rotation: Quaternion,
+    scale: Vector
+}
+
+impl Keyframe {
+    fn new(time: f64, m: Matrix4x4) -> Keyframe {
+        let column = |j: usize| Vector::new(m.get(0, j), m.get(1, j), m.get(2, j));
+        let (c0, c1, c2) = (column(0), column(1), column(2));
+
+        // A mirroring transform is a rotation with one axis scaled
+        // negatively.
+        let flip = if c0.cross(c1).dot(c2) < 0.0 { -1.0 } else { 1.0 };
+        let scale = Vector::new(c0.magnitude() * flip, c1.magnitude(), c2.magnitude());
+        let scales = [scale.x, scale.y, scale.z];
+
+        assert!(scales.iter().all(|s| *s != 0.0), "motion keyframes can't scale an axis to zero");
+
+        let mut rotation = [[0.0; 3]; 3];
+        for (i, row) in rotation.iter_mut().enumerate() {
+            for (j, r) in row.iter_mut().enumerate() {
+                *r = m.get(i, j) / scales[j];
+            }
+        }
+
+        Keyframe {
+            time,
+            translation: Vector::new(m.get(0, 3), m.get(1, 3), m.get(2, 3)),
+            rotation: Quaternion::from_rotation(rotation),
+            scale
+        }
+    }
+
+    fn interpolate(&self, other: &Keyframe, time: f64) -> Keyframe {
+        let t = (time - self.time) / (other.time - self.time);
+
+        Keyframe {
+            time,
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t
+        }
+    }
+
+    // Translation * rotation * scale.
+    fn transform(&self) -> Matrix4x4 {
+        let r = self.rotation.rotation();
+        let s = [self.scale.x, self.scale.y, self.scale.z];
+        let t = [self.translation.x, self.translation.y, self.translation.z];
+        let mut m = [[0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] = r[i][j] * s[j];
+            }
+            m[i][3] = t[i];
+        }
+
+        Matrix4x4::from_elements(m)
+    }
+
+    // The inverse scale * the transposed rotation * the inverse
+    // translation, which saves inverting the whole matrix for every ray.
+    fn inverse(&self) -> Matrix4x4 {
+        let r = self.rotation.rotation();
+        let s = [self.scale.x, self.scale.y, self.scale.z];
+        let t = [self.translation.x, self.translation.y, self.translation.z];
+        let mut m = [[0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] = r[j][i] / s[i];
+            }
+            m[i][3] = -(m[i][0] * t[0] + m[i][1] * t[1] + m[i][2] * t[2]);
+        }
+
+        Matrix4x4::from_elements(m)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Motion {
+    keyframes: Vec<Keyframe>
+}
+
+impl Motion {
+    // Keyframes are pairs of a time and the transform at that time. Before
+    // the first keyframe and after the last, the transform holds still.
+    // Every transform must be invertible, so none may scale an axis to
+    // zero.
+    pub fn new(keyframes: Vec<(f64, Matrix4x4)>) -> Motion {
+        assert!(!keyframes.is_empty(), "motion needs at least one keyframe");
+
+        let mut keyframes: Vec<Keyframe> = keyframes.
+            into_iter().
+            map(|(time, m)| Keyframe::new(time, m)).
+            collect();
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+
+        Motion { keyframes }
+    }
+
+    fn keyframe_at(&self, time: f64) -> Keyframe {
+        let first = self.keyframes[0];
+        let last = self.keyframes[self.keyframes.len() - 1];
+
+        if time <= first.time {
+            first
+        } else if time >= last.time {
+            last
+        } else {
+            let next = self.keyframes.iter().position(|k| k.time > time).unwrap();
+            self.keyframes[next - 1].interpolate(&self.keyframes[next], time)
+        }
+    }
+
+    pub fn transform_at(&self, time: f64) -> Matrix4x4 {
+        self.keyframe_at(time).transform()
+    }
+
+    pub fn inverse_at(&self, time: f64) -> Matrix4x4 {
+        self.keyframe_at(time).inverse()
+    }
+
+    // Finds the bounds swept out by `bounds` over the whole motion. The
+    // motion is sampled, so the result is padded slightly to cover the arcs
+    // of rotations between samples.
+    pub fn sweep(&self, bounds: Bounds) -> Bounds {
+        if bounds.is_empty() {
+            return bounds;
+        }
+
+        let mut swept = bounds.transform(self.keyframes[0].transform());
+
+        for pair in self.keyframes.windows(2) {
+            for step in 1..=SWEEP_STEPS {
+                let time = pair[0].time + (pair[1].time - pair[0].time) * step as f64 / SWEEP_STEPS as f64;
+                swept = swept.merge(bounds.transform(pair[0].interpolate(&pair[1], time).transform()));
+            }
+        }
+
+        let padding = (swept.max - swept.min) * 0.01;
+        Bounds::new(swept.min - padding, swept.max + padding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Point;
+    use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+    #[test]
+    fn keyframes_are_reproduced_exactly() {
+        let m = Matrix4x4::identity().scale(2.0, 3.0, 4.0).rotation_y(PI / 3.0).translate(1.0, -2.0, 5.0);
+        let motion = Motion::new(vec![(0.0, Matrix4x4::identity()), (1.0, m)]);
+
+        assert_eq!(Matrix4x4::identity(), motion.transform_at(0.0));
+        assert_eq!(m, motion.transform_at(1.0));
+        assert_eq!(m.inverse().unwrap(), motion.inverse_at(1.0));
+    }
+
+    #[test]
+    fn mirroring_transforms_are_reproduced() {
+        let m = Matrix4x4::identity().scale(-1.0, 1.0, 1.0).rotation_z(PI / 4.0);
+        let motion = Motion::new(vec![(0.0, m)]);
+        assert_eq!(m, motion.transform_at(0.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn keyframes_cannot_scale_to_zero() {
+        Motion::new(vec![(0.0, Matrix4x4::identity()), (1.0, Matrix4x4::identity().scale(1.0, 0.0, 1.0))]);
+    }
+
+    #[test]
+    fn translation_is_interpolated_linearly() {
+        let motion = Motion::new(vec![
+            (0.0, Matrix4x4::identity()),
+            (1.0, Matrix4x4::identity().translate(4.0, 0.0, -2.0))
+        ]);
+
+        assert_eq!(Matrix4x4::identity().translate(1.0, 0.0, -0.5), motion.transform_at(0.25));
+    }
+
+    #[test]
+    fn rotation_sweeps_around_an_arc() {
+        let motion = Motion::new(vec![
+            (0.0, Matrix4x4::identity()),
+            (1.0, Matrix4x4::identity().rotation_y(PI / 2.0))
+        ]);
+        let p = motion.transform_at(0.5) * Point::new(0.0, 0.0, 1.0);
+
+        assert_eq!(Point::new(FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2), p);
+    }
+
+    #[test]
+    fn spins_follow_every_keyframe() {
+        let motion = Motion::new((0..=4).
+            map(|i| (i as f64 / 4.0, Matrix4x4::identity().rotation_z(i as f64 * PI / 2.0))).
+            collect());
+
+        assert_eq!(Matrix4x4::identity().rotation_z(3.0 * PI / 4.0), motion.transform_at(0.375));
+        assert_eq!(Matrix4x4::identity().rotation_z(7.0 * PI / 4.0), motion.transform_at(0.875));
+    }
+
+    #[test]
+    fn motion_holds_still_outside_its_keyframes() {
+        let m = Matrix4x4::identity().translate(1.0, 2.0, 3.0);
+        let motion = Motion::new(vec![(0.5, m), (1.0, Matrix4x4::identity())]);
+
+        assert_eq!(m, motion.transform_at(0.0));
+        assert_eq!(Matrix4x4::identity(), motion.transform_at(2.0));
+    }
+
+    #[test]
+    fn sweeping_bounds_along_a_motion() {
+        let unit = Bounds::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+        let motion = Motion::new(vec![
+            (0.0, Matrix4x4::identity()),
+            (1.0, Matrix4x4::identity().translate(10.0, 0.0, 0.0))
+        ]);
+        let swept = motion.sweep(unit);
+
+        assert!(swept.contains(Point::new(-1.0, -1.0, -1.0)));
+        assert!(swept.contains(Point::new(11.0, 1.0, 1.0)));
+        assert!(!swept.contains(Point::new(12.0, 0.0, 0.0)));
+    }
+}
@@ -0,0 +1,31 @@
+// User-defined primitives, for geometry that isn't built in.
+//
+// A primitive only has to describe itself in its own object space: where a
+// ray meets it, its normal at a point on its surface, and the box that
+// contains it. `Shape::primitive` wraps one up as a shape, which takes care
+// of transforms, materials, motion, groups and shadows like any other.
+
+use super::Bounds;
+use crate::{Point, Ray, Vector};
+
+pub trait Primitive: std::fmt::Debug + Send + Sync {
+    // The values of t at which the ray meets the surface, in any order.
+    fn intersects(&self, ray: &Ray) -> Vec<f64>;
+
+    // The normal at a point on the surface, which needn't be normalized.
+    fn normal_at(&self, point: Point) -> Vector;
+
+    // Groups use the bounds to skip rays that miss their contents, so a
+    // primitive that can't say how far it reaches can leave them infinite.
+    fn bounds(&self) -> Bounds {
+        Bounds::infinite()
+    }
+}
+
+// Primitives can't be compared by value, so shapes holding the same
+// primitive are equal and any others are not.
+impl PartialEq for dyn Primitive {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
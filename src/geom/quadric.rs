@@ -0,0 +1,222 @@
+// General quadric surfaces, the points where
+//
+//   Ax^2 + By^2 + Cz^2 + Dxy + Exz + Fyz + Gx + Hy + Iz + J = 0
+//
+// which covers ellipsoids, hyperboloids, paraboloids, cones and elliptic
+// cylinders. Unbounded quadrics can be clipped to a range of y, leaving the
+// ends open.
+
+use super::Bounds;
+use crate::{Point, Ray, Vector};
+use crate::polynomial;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Quadric {
+    coefficients: [f64; 10],
+    minimum: f64,
+    maximum: f64
+}
+
+impl Quadric {
+    // Coefficients are given in the order A to J above.
+    pub fn new(coefficients: [f64; 10]) -> Quadric {
+        Quadric {
+            coefficients,
+            minimum: f64::NEG_INFINITY,
+            maximum: f64::INFINITY
+        }
+    }
+
+    // x^2/rx^2 + y^2/ry^2 + z^2/rz^2 = 1
+    pub fn ellipsoid(rx: f64, ry: f64, rz: f64) -> Quadric {
+        Self::new([1.0 / rx.powi(2), 1.0 / ry.powi(2), 1.0 / rz.powi(2), 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0])
+    }
+
+    // A paraboloid opening up the y axis with its focus at (0, focus, 0),
+    // as used for reflectors: x^2 + z^2 = 4 * focus * y.
+    pub fn paraboloid(focus: f64) -> Quadric {
+        Self::new([1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, -4.0 * focus, 0.0, 0.0])
+    }
+
+    // x^2/rx^2 + z^2/rz^2 - y^2/ry^2 = 1, a single connected sheet around
+    // the y axis.
+    pub fn hyperboloid(rx: f64, ry: f64, rz: f64) -> Quadric {
+        Self::new([1.0 / rx.powi(2), -1.0 / ry.powi(2), 1.0 / rz.powi(2), 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0])
+    }
+
+    // Keeps only the part of the surface between `minimum` and `maximum`
+    // in y.
+    pub fn clip(self, minimum: f64, maximum: f64) -> Quadric {
+        Quadric {
+            minimum,
+            maximum,
+            ..self
+        }
+    }
+
+    pub fn coefficients(&self) -> [f64; 10] {
+        self.coefficients
+    }
+
+    // Only ellipsoids are bounded in every direction. Without cross terms,
+    // and with every square term the same sign, completing the squares
+    // gives a(x - cx)^2 + b(y - cy)^2 + c(z - cz)^2 = k, from which the
+    // extents follow. Anything else is only bounded by its y clipping.
+    pub fn bounds(&self) -> Bounds {
+        let [a, b, c, d, e, f, g, h, i, j] = self.coefficients;
+        let same_sign = (a > 0.0 && b > 0.0 && c > 0.0) || (a < 0.0 && b < 0.0 && c < 0.0);
+
+        let bounds = if d == 0.0 && e == 0.0 && f == 0.0 && same_sign {
+            let center = Point::new(-g / (2.0 * a), -h / (2.0 * b), -i / (2.0 * c));
+            let k = g * g / (4.0 * a) + h * h / (4.0 * b) + i * i / (4.0 * c) - j;
+
+            if k / a < 0.0 {
+                return Bounds::empty();
+            }
+
+            let extent = Vector::new((k / a).sqrt(), (k / b).sqrt(), (k / c).sqrt());
+            Bounds::new(center - extent, center + extent)
+        } else {
+            Bounds::infinite()
+        };
+
+        Bounds::new(
+            Point::new(bounds.min.x, bounds.min.y.max(self.minimum), bounds.min.z),
+            Point::new(bounds.max.x, bounds.max.y.min(self.maximum), bounds.max.z))
+    }
+
+    pub fn value_at(&self, p: Point) -> f64 {
+        let [a, b, c, d, e, f, g, h, i, j] = self.coefficients;
+
+        a * p.x * p.x + b * p.y * p.y + c * p.z * p.z +
+            d * p.x * p.y + e * p.x * p.z + f * p.y * p.z +
+            g * p.x + h * p.y + i * p.z + j
+    }
+
+    pub fn intersects(&self, ray: &Ray) -> Vec<f64> {
+        let [a, b, c, d, e, f, g, h, i, _] = self.coefficients;
+        let (o, v) = (ray.origin, ray.direction);
+
+        let t2 = a * v.x * v.x + b * v.y * v.y + c * v.z * v.z +
+            d * v.x * v.y + e * v.x * v.z + f * v.y * v.z;
+        let t1 = 2.0 * (a * o.x * v.x + b * o.y * v.y + c * o.z * v.z) +
+            d * (o.x * v.y + o.y * v.x) +
+            e * (o.x * v.z + o.z * v.x) +
+            f * (o.y * v.z + o.z * v.y) +
+            g * v.x + h * v.y + i * v.z;
+
+        polynomial::solve_quadratic(t2, t1, self.value_at(o)).
+            into_iter().
+            filter(|t| {
+                let y = o.y + t * v.y;
+                self.minimum < y && y < self.maximum
+            }).
+            collect()
+    }
+
+    // The gradient of the quadric, which points towards where its value is
+    // positive.
+    pub fn normal_at(&self, p: Point) -> Vector {
+        let [a, b, c, d, e, f, g, h, i, _] = self.coefficients;
+
+        Vector::new(
+            2.0 * a * p.x + d * p.y + e * p.z + g,
+            2.0 * b * p.y + d * p.x + f * p.z + h,
+            2.0 * c * p.z + e * p.x + f * p.y + i
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_strikes_an_ellipsoid() {
+        let q = Quadric::ellipsoid(1.0, 2.0, 3.0);
+        let examples = vec![
+            (Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 4.0, 6.0),
+            (Point::new(0.0, -5.0, 0.0), Vector::new(0.0, 1.0, 0.0), 3.0, 7.0),
+            (Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0), 2.0, 8.0)
+        ];
+
+        for (origin, direction, t0, t1) in examples {
+            let xs = q.intersects(&Ray::new(origin, direction));
+            assert_eq!(2, xs.len());
+            assert!((xs[0] - t0).abs() < 0.0001);
+            assert!((xs[1] - t1).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn ray_misses_an_ellipsoid() {
+        let q = Quadric::ellipsoid(1.0, 2.0, 3.0);
+        let r = Ray::new(Point::new(-5.0, 2.5, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(q.intersects(&r).is_empty());
+    }
+
+    #[test]
+    fn ray_parallel_to_a_paraboloid_axis_hits_it_once() {
+        let q = Quadric::paraboloid(0.5);
+        let r = Ray::new(Point::new(1.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let xs = q.intersects(&r);
+
+        assert_eq!(1, xs.len());
+        assert!((xs[0] - 4.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn clipping_a_quadric() {
+        let q = Quadric::hyperboloid(1.0, 1.0, 1.0).clip(-1.0, 1.0);
+        let examples = vec![
+            (Point::new(-5.0, 0.5, 0.0), 2),
+            (Point::new(-5.0, 1.5, 0.0), 0),
+            (Point::new(-5.0, -1.0, 0.0), 0)
+        ];
+
+        for (origin, count) in examples {
+            let r = Ray::new(origin, Vector::new(1.0, 0.0, 0.0));
+            assert_eq!(count, q.intersects(&r).len());
+        }
+    }
+
+    #[test]
+    fn cross_terms_rotate_a_quadric() {
+        // An elliptic cylinder along the line x = y in the xy plane:
+        // (x - y)^2 / 2 + z^2 = 1.
+        let q = Quadric::new([0.5, 0.5, 1.0, -1.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0]);
+        let r = Ray::new(Point::new(3.0, 3.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = q.intersects(&r);
+
+        assert_eq!(2, xs.len());
+        assert!((xs[0] - 4.0).abs() < 0.0001);
+        assert_eq!(Vector::new(0.0, 0.0, -2.0), q.normal_at(r.position(xs[0])));
+    }
+
+    #[test]
+    fn bounds_of_quadrics() {
+        let b = Quadric::ellipsoid(1.0, 2.0, 3.0).bounds();
+        assert_eq!(Point::new(-1.0, -2.0, -3.0), b.min);
+        assert_eq!(Point::new(1.0, 2.0, 3.0), b.max);
+
+        // (x - 1)^2 + (y + 2)^2 + z^2 = 4, written out in full.
+        let b = Quadric::new([1.0, 1.0, 1.0, 0.0, 0.0, 0.0, -2.0, 4.0, 0.0, 1.0]).bounds();
+        assert_eq!(Point::new(-1.0, -4.0, -2.0), b.min);
+        assert_eq!(Point::new(3.0, 0.0, 2.0), b.max);
+
+        let b = Quadric::paraboloid(1.0).clip(0.0, 2.0).bounds();
+        assert_eq!(0.0, b.min.y);
+        assert_eq!(2.0, b.max.y);
+        assert_eq!(f64::INFINITY, b.max.x);
+    }
+
+    #[test]
+    fn normal_is_the_gradient_of_the_quadric() {
+        let q = Quadric::ellipsoid(1.0, 2.0, 3.0);
+        assert_eq!(Vector::new(2.0, 0.0, 0.0), q.normal_at(Point::new(1.0, 0.0, 0.0)));
+        assert_eq!(Vector::new(0.0, -1.0, 0.0), q.normal_at(Point::new(0.0, -2.0, 0.0)));
+
+        let p = Quadric::paraboloid(0.5);
+        assert_eq!(Vector::new(2.0, -2.0, 0.0), p.normal_at(Point::new(1.0, 0.5, 0.0)));
+    }
+}
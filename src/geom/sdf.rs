@@ -0,0 +1,291 @@
+// Shapes described by a signed distance function, which gives the distance
+// from any point to the nearest surface: negative inside the shape and
+// positive outside. They are intersected by sphere tracing, stepping along
+// the ray by the distance to the nearest surface until it is close enough
+// to count as a hit. Marching carries on through the inside of the shape
+// to find where the ray leaves it again, so distance fields work as CSG
+// operands and volume boundaries like any other closed shape.
+
+use crate::{Point, Ray, Vector};
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct DistanceField {
+    distance: Arc<dyn Fn(Point) -> f64 + Send + Sync>,
+    max_steps: usize,
+    epsilon: f64,
+    max_distance: f64
+}
+
+impl DistanceField {
+    pub fn new(distance: impl Fn(Point) -> f64 + Send + Sync + 'static) -> DistanceField {
+        DistanceField {
+            distance: Arc::new(distance),
+            max_steps: 256,
+            epsilon: 0.0001,
+            max_distance: 1000.0
+        }
+    }
+
+    pub fn max_steps(self, max_steps: usize) -> DistanceField {
+        DistanceField {
+            max_steps,
+            ..self
+        }
+    }
+
+    pub fn epsilon(self, epsilon: f64) -> DistanceField {
+        DistanceField {
+            epsilon,
+            ..self
+        }
+    }
+
+    pub fn max_distance(self, max_distance: f64) -> DistanceField {
+        DistanceField {
+            max_distance,
+            ..self
+        }
+    }
+
+    pub fn distance(&self, p: Point) -> f64 {
+        (self.distance)(p)
+    }
+
+    // Finds every crossing of the surface along the ray, so that rays enter
+    // and leave the shape in pairs. A ray starting inside the shape is
+    // marched backwards as well, to find where it entered behind its
+    // origin, and one starting on the surface and heading in enters at
+    // its origin.
+    pub fn intersects(&self, ray: &Ray) -> Vec<f64> {
+        let length = ray.direction.magnitude();
+        let direction = ray.direction / length;
+        let d = self.distance(ray.origin);
+
+        let mut xs = if d <= -self.epsilon {
+            self.march(ray.origin, -direction).into_iter().take(1).map(|t| -t / length).collect()
+        } else if d.abs() < self.epsilon && self.normal_at(ray.origin).dot(direction) < 0.0 {
+            vec![0.0]
+        } else {
+            vec![]
+        };
+
+        xs.extend(self.march(ray.origin, direction).into_iter().map(|t| t / length));
+        xs
+    }
+
+    // Marches along a ray with a unit direction, recording each point
+    // within epsilon of the surface. Inside the shape the distance is
+    // negative, so its size is used to march on to where the ray leaves.
+    // After each hit, and at the start of a ray beginning on the surface,
+    // the ray is stepped off the surface before looking for the next, so
+    // the same crossing isn't found twice.
+    fn march(&self, origin: Point, direction: Vector) -> Vec<f64> {
+        let mut xs = vec![];
+        let mut t = 0.0;
+        let mut leaving_surface = true;
+
+        for _ in 0..self.max_steps {
+            let d = self.distance(origin + direction * t).abs();
+
+            if d >= self.epsilon {
+                leaving_surface = false;
+            } else if !leaving_surface {
+                xs.push(t);
+                leaving_surface = true;
+            }
+
+            t += d.max(self.epsilon);
+
+            if t > self.max_distance {
+                break;
+            }
+        }
+
+        xs
+    }
+
+    // Estimates the gradient of the distance function with central
+    // differences.
+    pub fn normal_at(&self, p: Point) -> Vector {
+        let h = self.epsilon;
+        let dx = Vector::new(h, 0.0, 0.0);
+        let dy = Vector::new(0.0, h, 0.0);
+        let dz = Vector::new(0.0, 0.0, h);
+
+        Vector::new(
+            self.distance(p + dx) - self.distance(p - dx),
+            self.distance(p + dy) - self.distance(p - dy),
+            self.distance(p + dz) - self.distance(p - dz)
+        ).normalize()
+    }
+}
+
+impl std::fmt::Debug for DistanceField {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("DistanceField").
+            field("max_steps", &self.max_steps).
+            field("epsilon", &self.epsilon).
+            field("max_distance", &self.max_distance).
+            finish()
+    }
+}
+
+// Closures can't be compared, so fields are only equal when they share the
+// same distance function.
+impl PartialEq for DistanceField {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.distance, &other.distance) &&
+            self.max_steps == other.max_steps &&
+            self.epsilon == other.epsilon &&
+            self.max_distance == other.max_distance
+    }
+}
+
+pub fn sphere(radius: f64) -> impl Fn(Point) -> f64 + Send + Sync {
+    move |p| (p - Point::origin()).magnitude() - radius
+}
+
+// A box with the given half extents, with its edges rounded off by `radius`.
+pub fn rounded_box(half_extents: Vector, radius: f64) -> impl Fn(Point) -> f64 + Send + Sync {
+    move |p| {
+        let q = Vector::new(
+            p.x.abs() - half_extents.x + radius,
+            p.y.abs() - half_extents.y + radius,
+            p.z.abs() - half_extents.z + radius
+        );
+        let outside = Vector::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+        let inside = q.x.max(q.y).max(q.z).min(0.0);
+
+        outside + inside - radius
+    }
+}
+
+// Blends two distance functions together, filleting the seam between them
+// over a distance of roughly `k`.
+pub fn smooth_union(a: impl Fn(Point) -> f64 + Send + Sync, b: impl Fn(Point) -> f64 + Send + Sync, k: f64) -> impl Fn(Point) -> f64 + Send + Sync {
+    move |p| {
+        let da = a(p);
+        let db = b(p);
+        let h = (0.5 + 0.5 * (db - da) / k).clamp(0.0, 1.0);
+
+        db + (da - db) * h - k * h * (1.0 - h)
+    }
+}
+
+pub fn translate(d: impl Fn(Point) -> f64 + Send + Sync, offset: Vector) -> impl Fn(Point) -> f64 + Send + Sync {
+    move |p| d(p - offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_marches_to_the_surface_of_a_sphere() {
+        let field = DistanceField::new(sphere(1.0));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = field.intersects(&r);
+        assert_eq!(2, xs.len());
+        assert!((xs[0] - 4.0).abs() < 0.0001);
+        assert!((xs[1] - 6.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_marches_through_every_surface_it_crosses() {
+        let field = DistanceField::new(smooth_union(
+            translate(sphere(1.0), Vector::new(-3.0, 0.0, 0.0)),
+            translate(sphere(1.0), Vector::new(3.0, 0.0, 0.0)),
+            0.1));
+        let r = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let xs = field.intersects(&r);
+
+        assert_eq!(4, xs.len());
+
+        for (x, expected) in xs.iter().zip([1.0, 3.0, 7.0, 9.0]) {
+            assert!((x - expected).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn marching_accounts_for_unnormalized_directions() {
+        let field = DistanceField::new(sphere(1.0));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 2.0));
+        let xs = field.intersects(&r);
+        assert!((xs[0] - 2.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_misses_a_distance_field() {
+        let field = DistanceField::new(sphere(1.0));
+        let r = Ray::new(Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(field.intersects(&r).is_empty());
+    }
+
+    #[test]
+    fn ray_from_inside_finds_the_surfaces_on_either_side() {
+        let field = DistanceField::new(sphere(1.0));
+        let r = Ray::new(Point::origin(), Vector::new(0.0, 0.0, 2.0));
+        let xs = field.intersects(&r);
+        assert_eq!(2, xs.len());
+        assert!((xs[0] + 0.5).abs() < 0.0001);
+        assert!((xs[1] - 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_from_the_surface_heading_inside_enters_at_its_origin() {
+        let field = DistanceField::new(sphere(1.0));
+        let r = Ray::new(Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = field.intersects(&r);
+        assert_eq!(2, xs.len());
+        assert_eq!(0.0, xs[0]);
+        assert!((xs[1] - 2.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ray_leaving_the_surface_does_not_hit_it_again() {
+        let field = DistanceField::new(sphere(1.0));
+        let r = Ray::new(Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(field.intersects(&r).is_empty());
+    }
+
+    #[test]
+    fn marching_gives_up_after_max_steps() {
+        let field = DistanceField::new(sphere(1.0)).max_steps(2);
+        let r = Ray::new(Point::new(0.0, 0.99, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(field.intersects(&r).is_empty());
+    }
+
+    #[test]
+    fn normal_is_the_gradient_of_the_field() {
+        let field = DistanceField::new(sphere(1.0));
+        assert_eq!(Vector::new(1.0, 0.0, 0.0), field.normal_at(Point::new(1.0, 0.0, 0.0)));
+        assert_eq!(Vector::new(0.0, 0.0, -1.0), field.normal_at(Point::new(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn distance_to_a_rounded_box() {
+        let d = rounded_box(Vector::new(1.0, 1.0, 1.0), 0.25);
+        assert!((d(Point::new(2.0, 0.0, 0.0)) - 1.0).abs() < 0.0001);
+        assert!((d(Point::origin()) + 1.0).abs() < 0.0001);
+        let corner = 0.75 + 0.25 / 3.0_f64.sqrt();
+        assert!(d(Point::new(corner, corner, corner)).abs() < 0.0001);
+    }
+
+    #[test]
+    fn smooth_union_fills_the_gap_between_shapes() {
+        let a = translate(sphere(1.0), Vector::new(-1.2, 0.0, 0.0));
+        let b = translate(sphere(1.0), Vector::new(1.2, 0.0, 0.0));
+        let d = smooth_union(a, b, 1.0);
+        assert!(d(Point::new(0.0, 0.0, 0.0)) < 0.0);
+        assert!((d(Point::new(-2.2, 0.0, 0.0))).abs() < 0.0001);
+    }
+
+    #[test]
+    fn fields_are_equal_only_when_sharing_a_function() {
+        let field = DistanceField::new(sphere(1.0));
+        assert_eq!(field, field.clone());
+        assert_ne!(field, DistanceField::new(sphere(1.0)));
+        assert_ne!(field, field.clone().epsilon(0.01));
+    }
+}
@@ -0,0 +1,357 @@
+// Approximating shapes with triangle meshes, and displacing the surface of
+// those meshes to add real detail to a shape.
+//
+// Most shapes are tessellated by stepping across a parametric grid over
+// their surface, with `resolution` divisions along each side. Each
+// triangle is wound to face the same way as the surface normals, so the
+// meshes work with flat triangles as well as smooth ones. Shapes with no
+// finite surface, or none that can be walked as a grid, can't be
+// tessellated.
+
+use super::{Mesh, Shape, ShapeType};
+use crate::{Material, Pattern, Point, Vector};
+use std::f64::consts::PI;
+
+impl Shape {
+    // Approximates the shape with a mesh in the space of its parent, or
+    // returns None for shapes that can't be tessellated: planes, CSG,
+    // distance fields, blobs, quadrics, user-defined primitives, volumes
+    // and open-ended cylinders and cones. Groups combine the meshes of all
+    // their children, losing their individual materials.
+    pub fn tessellate(&self, resolution: usize) -> Option<Mesh> {
+        self.object_mesh(resolution).map(|mesh| mesh.transform(self.transform))
+    }
+
+    // Offsets the surface along its normal by `scale` times the brightness
+    // of `pattern`, giving a group of smooth triangles that changes the
+    // silhouette of the shape as well as its shading. The pattern is looked
+    // up in object space, so the detail moves with the shape.
+    //
+    // Vertices on the edges between faces, as on a cube, move along the
+    // average of the normals of the faces that meet there, which keeps the
+    // surface closed.
+    //
+    // Returns None for the shapes that `tessellate` can't handle, including
+    // any group with a child that can't be tessellated, such as a plane.
+    pub fn displace(self, pattern: &Pattern, scale: f64, resolution: usize) -> Option<Shape> {
+        let mesh = self.object_mesh(resolution)?.weld();
+
+        let displaced = Mesh {
+            vertices: mesh.vertices.
+                iter().
+                zip(&mesh.normals).
+                map(|(p, n)| {
+                    let offset = scale * pattern.value_at(*p);
+                    if n.magnitude() > 0.0 { *p + n.normalize() * offset } else { *p }
+                }).
+                collect(),
+            normals: vec![],
+            colors: vec![],
+            triangles: mesh.triangles
+        }.weld().compute_normals();
+
+        let mut shape = Shape {
+            shape_type: displaced.to_shape(&self.material).shape_type,
+            ..self
+        };
+
+        shape.set_parent_inverse(shape.parent_inverse);
+        Some(shape)
+    }
+
+    // Tessellates each part of the shape separately, in the space of its
+    // parent, along with the material it is drawn with, so that the
+    // children of groups keep their own materials. Planes are clipped to a
+    // square `plane_size` across, centred on their origin, triangles are
+    // kept as they are rather than subdivided, and any other parts that
+    // can't be tessellated are left out. Flat triangles have no normals, so
+    // that they stay flat when written out. Triangles colored by their corners
+    // carry those colors on their vertices, so the triangles of a colored
+    // mesh still share one material.
+    pub fn tessellate_parts(&self, resolution: usize, plane_size: f64) -> Vec<(Mesh, &Material)> {
+        let parts: Vec<(Mesh, &Material)> = match &self.shape_type {
+            ShapeType::Group { children, .. } =>
+                children.
+                    iter().
+                    flat_map(|child| child.tessellate_parts(resolution, plane_size)).
+                    collect(),
+            ShapeType::Instance { geometry, overrides_material } =>
+                geometry.
+                    tessellate_parts(resolution, plane_size).
+                    into_iter().
+                    map(|(mesh, material)| (mesh, if *overrides_material { &self.material } else { material })).
+                    collect(),
+            ShapeType::Plane =>
+                vec![(grid(1, 1, |u, v| {
+                    (Point::new((u - 0.5) * plane_size, 0.0, (v - 0.5) * plane_size), Vector::new(0.0, 1.0, 0.0))
+                }), &self.material)],
+            ShapeType::SmoothTriangle { .. } | ShapeType::Triangle { .. } =>
+                self.
+                    object_mesh(1).
+                    into_iter().
+                    map(|mut mesh| {
+                        if let ShapeType::Triangle { .. } = self.shape_type {
+                            mesh.normals.clear();
+                        }
+
+                        if self.material.pattern.is_vertex_colors() {
+                            mesh.colors = mesh.vertices.iter().map(|p| self.material.pattern.color_at(*p)).collect();
+                        }
+
+                        (mesh, &self.material)
+                    }).
+                    collect(),
+            _ =>
+                self.object_mesh(resolution).into_iter().map(|mesh| (mesh, &self.material)).collect()
+        };
+
+        parts.
+            into_iter().
+            map(|(mesh, material)| (mesh.transform(self.transform), material)).
+            collect()
+    }
+
+    fn object_mesh(&self, resolution: usize) -> Option<Mesh> {
+        let n = resolution.max(1);
+
+        let mesh = match &self.shape_type {
+            ShapeType::BezierPatch(patch) =>
+                grid(n, n, |u, v| (patch.point_at(u, v), patch.normal_at_uv(u, v))),
+            ShapeType::Cone { minimum, maximum, closed } =>
+                lathe(n, *minimum, *maximum, *closed, |y| y.abs())?,
+            ShapeType::Cube => cube(n),
+            ShapeType::Cylinder { minimum, maximum, closed } =>
+                lathe(n, *minimum, *maximum, *closed, |_| 1.0)?,
+            ShapeType::Disk { inner_radius, radius } => disk(n, 0.0, *inner_radius, *radius, 1.0),
+            ShapeType::Group { children, .. } => {
+                let mut mesh = Mesh::new();
+
+                for child in children {
+                    mesh.append(child.tessellate(resolution)?);
+                }
+
+                mesh
+            },
+            ShapeType::Heightfield(heightfield) => heightfield.to_mesh(),
+            ShapeType::Instance { geometry, .. } => geometry.tessellate(resolution)?,
+            ShapeType::Rectangle { width, depth } =>
+                grid(n, n, |u, v| {
+                    (Point::new((u - 0.5) * width, 0.0, (v - 0.5) * depth), Vector::new(0.0, 1.0, 0.0))
+                }),
+            ShapeType::SmoothTriangle { p1, e1, e2, n1, n2, n3, .. } =>
+                triangle(n, *p1, *e1, *e2, |u, v| *n2 * u + *n3 * v + *n1 * (1.0 - u - v)),
+            ShapeType::Sphere =>
+                grid(2 * n, n, |u, v| {
+                    let (phi, theta) = (2.0 * PI * u, PI * v);
+                    let p = Point::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+                    (p, p - Point::origin())
+                }),
+            ShapeType::Torus { major, minor } =>
+                grid(2 * n, n, |u, v| {
+                    let (phi, theta) = (2.0 * PI * u, 2.0 * PI * v);
+                    let normal = Vector::new(theta.cos() * phi.cos(), theta.sin(), theta.cos() * phi.sin());
+                    let center = Point::new(major * phi.cos(), 0.0, major * phi.sin());
+                    (center + normal * *minor, normal)
+                }),
+            ShapeType::Triangle { p1, e1, e2, normal, .. } =>
+                triangle(n, *p1, *e1, *e2, |_, _| *normal),
+            ShapeType::Blob(_) |
+            ShapeType::Csg { .. } |
+            ShapeType::DistanceField(_) |
+            ShapeType::Plane |
+            ShapeType::Primitive(_) |
+            ShapeType::Quadric(_) |
+            ShapeType::TestShape |
+            ShapeType::Volume { .. } => return None
+        };
+
+        Some(mesh)
+    }
+}
+
+// Flips any triangle that faces away from the normals at its vertices.
+fn orient(mesh: Mesh) -> Mesh {
+    let triangles = mesh.triangles.
+        iter().
+        map(|&[a, b, c]| {
+            let normal = mesh.normals[a] + mesh.normals[b] + mesh.normals[c];
+
+            if mesh.face_normal([a, b, c]).dot(normal) < 0.0 {
+                [a, c, b]
+            } else {
+                [a, b, c]
+            }
+        }).
+        collect();
+
+    Mesh { triangles, ..mesh }
+}
+
+// A `columns` by `rows` grid of cells over u and v from 0 to 1, with each
+// cell split into two triangles. `vertex` gives the point and normal at
+// each (u, v).
+fn grid(columns: usize, rows: usize, vertex: impl Fn(f64, f64) -> (Point, Vector)) -> Mesh {
+    let mut mesh = Mesh::new();
+
+    for j in 0..=rows {
+        for i in 0..=columns {
+            let (p, n) = vertex(i as f64 / columns as f64, j as f64 / rows as f64);
+            mesh.vertices.push(p);
+            mesh.normals.push(n);
+        }
+    }
+
+    let index = |i: usize, j: usize| j * (columns + 1) + i;
+
+    for j in 0..rows {
+        for i in 0..columns {
+            mesh.triangles.push([index(i, j), index(i + 1, j), index(i, j + 1)]);
+            mesh.triangles.push([index(i + 1, j), index(i + 1, j + 1), index(i, j + 1)]);
+        }
+    }
+
+    orient(mesh)
+}
+
+// Splits a triangle into `n * n` smaller ones, with `normal` giving the
+// normal at each barycentric (u, v).
+fn triangle(n: usize, p1: Point, e1: Vector, e2: Vector, normal: impl Fn(f64, f64) -> Vector) -> Mesh {
+    let mut mesh = Mesh::new();
+    let mut rows = vec![];
+
+    for j in 0..=n {
+        let start = mesh.vertices.len();
+
+        for i in 0..=(n - j) {
+            let (u, v) = (i as f64 / n as f64, j as f64 / n as f64);
+            mesh.vertices.push(p1 + e1 * u + e2 * v);
+            mesh.normals.push(normal(u, v));
+        }
+
+        rows.push(start);
+    }
+
+    for j in 0..n {
+        for i in 0..(n - j) {
+            let (a, b, c) = (rows[j] + i, rows[j] + i + 1, rows[j + 1] + i);
+            mesh.triangles.push([a, b, c]);
+
+            if i + 1 < n - j {
+                mesh.triangles.push([b, c + 1, c]);
+            }
+        }
+    }
+
+    orient(mesh)
+}
+
+// A flat ring at height `y` between two radii, facing up if `facing` is 1
+// and down if it is -1.
+fn disk(n: usize, y: f64, inner_radius: f64, radius: f64, facing: f64) -> Mesh {
+    grid(2 * n, n, |u, v| {
+        let phi = 2.0 * PI * u;
+        let r = inner_radius + (radius - inner_radius) * v;
+        (Point::new(r * phi.cos(), y, r * phi.sin()), Vector::new(0.0, facing, 0.0))
+    })
+}
+
+// A surface of revolution about the y axis between `minimum` and `maximum`,
+// with `radius` giving its radius at each height, as for cylinders and
+// cones. Closed shapes are capped at each end.
+fn lathe(n: usize, minimum: f64, maximum: f64, closed: bool, radius: impl Fn(f64) -> f64) -> Option<Mesh> {
+    if !minimum.is_finite() || !maximum.is_finite() {
+        return None;
+    }
+
+    let mut mesh = grid(2 * n, n, |u, v| {
+        let phi = 2.0 * PI * u;
+        let y = minimum + (maximum - minimum) * v;
+
+        // The slope of the side, which tilts the normal up or down.
+        let h = 0.0001;
+        let slope = (radius(y + h) - radius(y - h)) / (2.0 * h);
+
+        (
+            Point::new(radius(y) * phi.cos(), y, radius(y) * phi.sin()),
+            Vector::new(phi.cos(), -slope, phi.sin())
+        )
+    });
+
+    if closed {
+        mesh.append(disk(n, minimum, 0.0, radius(minimum), -1.0));
+        mesh.append(disk(n, maximum, 0.0, radius(maximum), 1.0));
+    }
+
+    Some(mesh)
+}
+
+fn cube(n: usize) -> Mesh {
+    let (x, y, z) = (Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+    let mut mesh = Mesh::new();
+
+    for (normal, a, b) in [(x, y, z), (-x, y, z), (y, x, z), (-y, x, z), (z, x, y), (-z, x, y)] {
+        mesh.append(grid(n, n, |u, v| {
+            (Point::origin() + normal + a * (2.0 * u - 1.0) + b * (2.0 * v - 1.0), normal)
+        }));
+    }
+
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geom::Medium;
+    use crate::{Color, Matrix4x4};
+
+    #[test]
+    fn triangles_face_along_the_vertex_normals() {
+        let meshes = vec![
+            cube(2),
+            disk(2, 1.0, 0.5, 1.0, -1.0),
+            lathe(3, -1.0, 1.0, true, |y| y.abs()).unwrap(),
+            triangle(3, Point::new(0.0, 1.0, 0.0), Vector::new(-1.0, -1.0, 0.0), Vector::new(1.0, -1.0, 0.0), |_, _| Vector::new(0.0, 0.0, 1.0))
+        ];
+
+        for mesh in meshes {
+            for t in &mesh.triangles {
+                let normal = mesh.normals[t[0]] + mesh.normals[t[1]] + mesh.normals[t[2]];
+                assert!(mesh.face_normal(*t).dot(normal) >= 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn subdividing_a_triangle() {
+        let mesh = triangle(3, Point::origin(), Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), |_, _| Vector::new(0.0, 0.0, -1.0));
+
+        assert_eq!(10, mesh.vertices.len());
+        assert_eq!(9, mesh.triangles.len());
+        assert_eq!(Point::new(0.0, 1.0, 0.0), mesh.vertices[9]);
+    }
+
+    #[test]
+    fn tessellating_parts_keeps_their_materials() {
+        let red = Material::new().color(Color::new(1.0, 0.0, 0.0));
+        let g = Shape::group(vec![
+            Shape::plane().transform(Matrix4x4::identity().translate(0.0, -1.0, 0.0)),
+            Shape::sphere().material(red.clone()),
+            Shape::triangle(Point::origin(), Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0)),
+            Shape::volume(Shape::cube(), Medium::new(1.0))
+        ]).transform(Matrix4x4::identity().translate(0.0, 0.0, 5.0));
+        let parts = g.tessellate_parts(4, 10.0);
+
+        assert_eq!(3, parts.len());
+        assert_eq!(&Material::new(), parts[0].1);
+        assert!(parts[0].0.vertices.contains(&Point::new(5.0, -1.0, 10.0)));
+        assert_eq!(Vector::new(0.0, 1.0, 0.0), parts[0].0.face_normal(parts[0].0.triangles[0]).normalize());
+        assert_eq!(&red, parts[1].1);
+        assert_eq!(1, parts[2].0.triangles.len());
+        assert_eq!(Point::new(1.0, 0.0, 5.0), parts[2].0.vertices[1]);
+    }
+
+    #[test]
+    fn open_ended_shapes_have_no_mesh() {
+        assert!(lathe(2, f64::NEG_INFINITY, 1.0, false, |_| 1.0).is_none());
+    }
+}
@@ -1,5 +1,5 @@
 use super::*;
-use crate::{Intersection, Material, Matrix4x4, Point, Vector};
+use crate::{Color, Intersection, Material, Matrix4x4, Pattern, Point, Vector};
 
 #[test]
 fn testshape_has_default_material() {
@@ -31,7 +31,7 @@ fn test_shape_can_have_transform_applied() {
 fn ray_sphere_intersection_at_two_points() {
     let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
     let s = Shape::sphere();
-    let result = vec![Intersection{ray: &r, object: &s, t: 4.0}, Intersection{ray: &r, object: &s, t: 6.0}];
+    let result = vec![Intersection::new(&r, 4.0, &s), Intersection::new(&r, 6.0, &s)];
 
     assert_eq!(result, s.intersects(&r));
 }
@@ -40,7 +40,7 @@ fn ray_sphere_intersection_at_two_points() {
 fn ray_sphere_intersection_at_tangent() {
     let r = Ray::new(Point::new(0.0, 1.0, -5.0), Vector::new(0.0, 0.0, 1.0));
     let s = Shape::sphere();
-    let result = vec![Intersection{ray: &r, object: &s, t: 5.0}, Intersection{ray: &r, object: &s, t: 5.0}];
+    let result = vec![Intersection::new(&r, 5.0, &s), Intersection::new(&r, 5.0, &s)];
 
     assert_eq!(result, s.intersects(&r));
 }
@@ -59,7 +59,7 @@ fn ray_originates_inside_a_sphere() {
     let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
     let s = Shape::sphere();
 
-    let result = vec![Intersection{ray: &r, object: &s, t: -1.0}, Intersection{ray: &r, object: &s, t: 1.0}];
+    let result = vec![Intersection::new(&r, -1.0, &s), Intersection::new(&r, 1.0, &s)];
 
     assert_eq!(result, s.intersects(&r));
 }
@@ -69,7 +69,7 @@ fn ray_originates_after_sphere() {
     let r = Ray::new(Point::new(0.0, 0.0, 5.0), Vector::new(0.0, 0.0, 1.0));
     let s = Shape::sphere();
 
-    let result = vec![Intersection{ray: &r, object: &s, t: -6.0}, Intersection{ray: &r, object: &s, t: -4.0}];
+    let result = vec![Intersection::new(&r, -6.0, &s), Intersection::new(&r, -4.0, &s)];
 
     assert_eq!(result, s.intersects(&r));
 }
@@ -78,7 +78,7 @@ fn ray_originates_after_sphere() {
 fn intersect_scaled_sphere_with_ray() {
     let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
     let s = Shape::sphere().transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0));
-    let result = vec![Intersection{ray: &r, object: &s, t: 3.0}, Intersection{ray: &r, object: &s, t: 7.0}];
+    let result = vec![Intersection::new(&r, 3.0, &s), Intersection::new(&r, 7.0, &s)];
 
     assert_eq!(result, s.intersects(&r));
 }
@@ -111,6 +111,7 @@ fn normal_at_point_on_non_axial_point() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn normal_at_on_translated_sphere() {
     let s = Shape::sphere().transform(Matrix4x4::identity().translate(0.0, 1.0, 0.0));
     let p = Point::new(0.0, 1.70711, -0.70711);
@@ -131,6 +132,7 @@ fn normal_at_on_transformed_sphere() {
 //// Planes
 
 #[test]
+#[allow(clippy::four_forward_slashes)]
 fn normal_at_on_a_plane() {
     let p = Shape::plane();
     let n = Vector::new(0.0, 1.0, 0.0);
@@ -161,7 +163,7 @@ fn plane_interesction_from_above() {
     let r = Ray::new(Point::new(0.0, 1.0, 0.0),  Vector::new(0.0, -1.0, 0.0));
 
     let result = vec![
-        Intersection{ray: &r, object: &p, t: 1.0}
+        Intersection::new(&r, 1.0, &p)
     ];
 
     assert_eq!(result, p.intersects(&r));
@@ -173,8 +175,1164 @@ fn plane_interesction_from_below() {
     let r = Ray::new(Point::new(0.0, -1.0, 0.0),  Vector::new(0.0, 1.0, 0.0));
 
     let result = vec![
-        Intersection{ray: &r, object: &p, t: 1.0}
+        Intersection::new(&r, 1.0, &p)
     ];
 
     assert_eq!(result, p.intersects(&r));
 }
+
+// Cubes
+
+#[test]
+fn ray_intersects_a_cube() {
+    let c = Shape::cube();
+    let examples = vec![
+        (Point::new(5.0, 0.5, 0.0), Vector::new(-1.0, 0.0, 0.0), 4.0, 6.0),
+        (Point::new(-5.0, 0.5, 0.0), Vector::new(1.0, 0.0, 0.0), 4.0, 6.0),
+        (Point::new(0.5, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 4.0, 6.0),
+        (Point::new(0.5, -5.0, 0.0), Vector::new(0.0, 1.0, 0.0), 4.0, 6.0),
+        (Point::new(0.5, 0.0, 5.0), Vector::new(0.0, 0.0, -1.0), 4.0, 6.0),
+        (Point::new(0.5, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0), 4.0, 6.0),
+        (Point::new(0.0, 0.5, 0.0), Vector::new(0.0, 0.0, 1.0), -1.0, 1.0)
+    ];
+
+    for (origin, direction, t1, t2) in examples {
+        let r = Ray::new(origin, direction);
+        let result = vec![Intersection::new(&r, t1, &c), Intersection::new(&r, t2, &c)];
+        assert_eq!(result, c.intersects(&r));
+    }
+}
+
+#[test]
+fn ray_misses_a_cube() {
+    let c = Shape::cube();
+    let examples = vec![
+        (Point::new(-2.0, 0.0, 0.0), Vector::new(0.2673, 0.5345, 0.8018)),
+        (Point::new(0.0, -2.0, 0.0), Vector::new(0.8018, 0.2673, 0.5345)),
+        (Point::new(0.0, 0.0, -2.0), Vector::new(0.5345, 0.8018, 0.2673)),
+        (Point::new(2.0, 0.0, 2.0), Vector::new(0.0, 0.0, -1.0)),
+        (Point::new(0.0, 2.0, 2.0), Vector::new(0.0, -1.0, 0.0)),
+        (Point::new(2.0, 2.0, 0.0), Vector::new(-1.0, 0.0, 0.0))
+    ];
+
+    for (origin, direction) in examples {
+        let r = Ray::new(origin, direction);
+        assert!(c.intersects(&r).is_empty());
+    }
+}
+
+#[test]
+fn normal_on_the_surface_of_a_cube() {
+    let c = Shape::cube();
+    let examples = vec![
+        (Point::new(1.0, 0.5, -0.8), Vector::new(1.0, 0.0, 0.0)),
+        (Point::new(-1.0, -0.2, 0.9), Vector::new(-1.0, 0.0, 0.0)),
+        (Point::new(-0.4, 1.0, -0.1), Vector::new(0.0, 1.0, 0.0)),
+        (Point::new(0.3, -1.0, -0.7), Vector::new(0.0, -1.0, 0.0)),
+        (Point::new(-0.6, 0.3, 1.0), Vector::new(0.0, 0.0, 1.0)),
+        (Point::new(0.4, 0.4, -1.0), Vector::new(0.0, 0.0, -1.0)),
+        (Point::new(1.0, 1.0, 1.0), Vector::new(1.0, 0.0, 0.0)),
+        (Point::new(-1.0, -1.0, -1.0), Vector::new(-1.0, 0.0, 0.0))
+    ];
+
+    for (point, normal) in examples {
+        assert_eq!(normal, c.normal_at(point));
+    }
+}
+
+#[test]
+fn normal_on_a_transformed_cube() {
+    let c = Shape::cube().transform(Matrix4x4::identity().scale(2.0, 1.0, 1.0).translate(0.0, 3.0, 0.0));
+    assert_eq!(Vector::new(0.0, 1.0, 0.0), c.normal_at(Point::new(0.5, 4.0, 0.2)));
+    assert_eq!(Vector::new(1.0, 0.0, 0.0), c.normal_at(Point::new(2.0, 3.2, 0.2)));
+}
+
+// Cylinders
+
+#[test]
+fn ray_misses_a_cylinder() {
+    let cyl = Shape::cylinder(f64::NEG_INFINITY, f64::INFINITY, false);
+    let examples = vec![
+        (Point::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0)),
+        (Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0)),
+        (Point::new(0.0, 0.0, -5.0), Vector::new(1.0, 1.0, 1.0))
+    ];
+
+    for (origin, direction) in examples {
+        let r = Ray::new(origin, direction.normalize());
+        assert!(cyl.intersects(&r).is_empty());
+    }
+}
+
+#[test]
+fn ray_strikes_a_cylinder() {
+    let cyl = Shape::cylinder(f64::NEG_INFINITY, f64::INFINITY, false);
+    let examples = vec![
+        (Point::new(1.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0), 5.0, 5.0),
+        (Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0), 4.0, 6.0),
+        (Point::new(0.5, 0.0, -5.0), Vector::new(0.1, 1.0, 1.0), 6.80798, 7.08872)
+    ];
+
+    for (origin, direction, t0, t1) in examples {
+        let r = Ray::new(origin, direction.normalize());
+        let xs = cyl.intersects(&r);
+        assert_eq!(2, xs.len());
+        assert!((xs[0].t - t0).abs() < 0.0001);
+        assert!((xs[1].t - t1).abs() < 0.0001);
+    }
+}
+
+#[test]
+fn normal_on_a_cylinder() {
+    let cyl = Shape::cylinder(f64::NEG_INFINITY, f64::INFINITY, false);
+    let examples = vec![
+        (Point::new(1.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0)),
+        (Point::new(0.0, 5.0, -1.0), Vector::new(0.0, 0.0, -1.0)),
+        (Point::new(0.0, -2.0, 1.0), Vector::new(0.0, 0.0, 1.0)),
+        (Point::new(-1.0, 1.0, 0.0), Vector::new(-1.0, 0.0, 0.0))
+    ];
+
+    for (point, normal) in examples {
+        assert_eq!(normal, cyl.normal_at(point));
+    }
+}
+
+#[test]
+fn intersecting_a_truncated_cylinder() {
+    let cyl = Shape::cylinder(1.0, 2.0, false);
+    let examples = vec![
+        (Point::new(0.0, 1.5, 0.0), Vector::new(0.1, 1.0, 0.0), 0),
+        (Point::new(0.0, 3.0, -5.0), Vector::new(0.0, 0.0, 1.0), 0),
+        (Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0), 0),
+        (Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0), 0),
+        (Point::new(0.0, 1.0, -5.0), Vector::new(0.0, 0.0, 1.0), 0),
+        (Point::new(0.0, 1.5, -2.0), Vector::new(0.0, 0.0, 1.0), 2)
+    ];
+
+    for (origin, direction, count) in examples {
+        let r = Ray::new(origin, direction.normalize());
+        assert_eq!(count, cyl.intersects(&r).len());
+    }
+}
+
+#[test]
+fn intersecting_the_caps_of_a_closed_cylinder() {
+    let cyl = Shape::cylinder(1.0, 2.0, true);
+    let examples = vec![
+        (Point::new(0.0, 3.0, 0.0), Vector::new(0.0, -1.0, 0.0), 2),
+        (Point::new(0.0, 3.0, -2.0), Vector::new(0.0, -1.0, 2.0), 2),
+        (Point::new(0.0, 4.0, -2.0), Vector::new(0.0, -1.0, 1.0), 2),
+        (Point::new(0.0, 0.0, -2.0), Vector::new(0.0, 1.0, 2.0), 2),
+        (Point::new(0.0, -1.0, -2.0), Vector::new(0.0, 1.0, 1.0), 2)
+    ];
+
+    for (origin, direction, count) in examples {
+        let r = Ray::new(origin, direction.normalize());
+        assert_eq!(count, cyl.intersects(&r).len());
+    }
+}
+
+#[test]
+fn normal_on_the_caps_of_a_closed_cylinder() {
+    let cyl = Shape::cylinder(1.0, 2.0, true);
+    let examples = vec![
+        (Point::new(0.0, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0)),
+        (Point::new(0.5, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0)),
+        (Point::new(0.0, 1.0, 0.5), Vector::new(0.0, -1.0, 0.0)),
+        (Point::new(0.0, 2.0, 0.0), Vector::new(0.0, 1.0, 0.0)),
+        (Point::new(0.5, 2.0, 0.0), Vector::new(0.0, 1.0, 0.0)),
+        (Point::new(0.0, 2.0, 0.5), Vector::new(0.0, 1.0, 0.0))
+    ];
+
+    for (point, normal) in examples {
+        assert_eq!(normal, cyl.normal_at(point));
+    }
+}
+
+// Cones
+
+#[test]
+fn intersecting_a_cone_with_a_ray() {
+    let shape = Shape::cone(f64::NEG_INFINITY, f64::INFINITY, false);
+    let examples = vec![
+        (Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0), 5.0, 5.0),
+        (Point::new(0.0, 0.0, -5.0), Vector::new(1.0, 1.0, 1.0), 8.66025, 8.66025),
+        (Point::new(1.0, 1.0, -5.0), Vector::new(-0.5, -1.0, 1.0), 4.55006, 49.44994)
+    ];
+
+    for (origin, direction, t0, t1) in examples {
+        let r = Ray::new(origin, direction.normalize());
+        let xs = shape.intersects(&r);
+        assert_eq!(2, xs.len());
+        assert!((xs[0].t - t0).abs() < 0.0001);
+        assert!((xs[1].t - t1).abs() < 0.0001);
+    }
+}
+
+#[test]
+fn intersecting_a_cone_with_a_ray_parallel_to_one_of_its_halves() {
+    let shape = Shape::cone(f64::NEG_INFINITY, f64::INFINITY, false);
+    let r = Ray::new(Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 1.0, 1.0).normalize());
+    let xs = shape.intersects(&r);
+    assert_eq!(1, xs.len());
+    assert!((xs[0].t - 0.35355).abs() < 0.0001);
+}
+
+#[test]
+fn intersecting_the_caps_of_a_closed_cone() {
+    let shape = Shape::cone(-0.5, 0.5, true);
+    let examples = vec![
+        (Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 1.0, 0.0), 0),
+        (Point::new(0.0, 0.0, -0.25), Vector::new(0.0, 1.0, 1.0), 2),
+        (Point::new(0.0, 0.0, -0.25), Vector::new(0.0, 1.0, 0.0), 4)
+    ];
+
+    for (origin, direction, count) in examples {
+        let r = Ray::new(origin, direction.normalize());
+        assert_eq!(count, shape.intersects(&r).len());
+    }
+}
+
+#[test]
+fn normal_on_a_cone() {
+    let examples = vec![
+        (Point::new(1.0, 1.0, 1.0), Vector::new(1.0, -(2.0_f64.sqrt()), 1.0)),
+        (Point::new(-1.0, -1.0, 0.0), Vector::new(-1.0, 1.0, 0.0))
+    ];
+
+    for (point, normal) in examples {
+        assert_eq!(normal, ShapeType::cone_normal_at(point, f64::NEG_INFINITY, f64::INFINITY));
+    }
+
+    let shape = Shape::cone(f64::NEG_INFINITY, f64::INFINITY, false);
+    assert_eq!(Vector::new(1.0, -(2.0_f64.sqrt()), 1.0).normalize(), shape.normal_at(Point::new(1.0, 1.0, 1.0)));
+}
+
+#[test]
+fn normal_on_the_caps_of_a_closed_cone() {
+    let shape = Shape::cone(-1.0, 2.0, true);
+    assert_eq!(Vector::new(0.0, 1.0, 0.0), shape.normal_at(Point::new(0.5, 2.0, 0.5)));
+    assert_eq!(Vector::new(0.0, -1.0, 0.0), shape.normal_at(Point::new(0.2, -1.0, 0.3)));
+}
+
+// Triangles
+
+fn default_triangle() -> Shape {
+    Shape::triangle(Point::new(0.0, 1.0, 0.0), Point::new(-1.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0))
+}
+
+#[test]
+fn constructing_a_triangle() {
+    let t = default_triangle();
+
+    match t.shape_type {
+        ShapeType::Triangle { e1, e2, normal, .. } => {
+            assert_eq!(Vector::new(-1.0, -1.0, 0.0), e1);
+            assert_eq!(Vector::new(1.0, -1.0, 0.0), e2);
+            assert_eq!(Vector::new(0.0, 0.0, -1.0), normal);
+        },
+        _ => panic!("expected a triangle")
+    }
+}
+
+#[test]
+fn normal_on_a_triangle() {
+    let t = default_triangle();
+    let n = Vector::new(0.0, 0.0, -1.0);
+    assert_eq!(n, t.normal_at(Point::new(0.0, 0.5, 0.0)));
+    assert_eq!(n, t.normal_at(Point::new(-0.5, 0.75, 0.0)));
+    assert_eq!(n, t.normal_at(Point::new(0.5, 0.25, 0.0)));
+}
+
+#[test]
+fn ray_parallel_to_a_triangle() {
+    let t = default_triangle();
+    let r = Ray::new(Point::new(0.0, -1.0, -2.0), Vector::new(0.0, 1.0, 0.0));
+    assert!(t.intersects(&r).is_empty());
+}
+
+#[test]
+fn ray_misses_the_edges_of_a_triangle() {
+    let t = default_triangle();
+    let examples = vec![
+        Point::new(1.0, 1.0, -2.0),
+        Point::new(-1.0, 1.0, -2.0),
+        Point::new(0.0, -1.0, -2.0)
+    ];
+
+    for origin in examples {
+        let r = Ray::new(origin, Vector::new(0.0, 0.0, 1.0));
+        assert!(t.intersects(&r).is_empty());
+    }
+}
+
+#[test]
+fn ray_strikes_a_triangle() {
+    let t = default_triangle();
+    let r = Ray::new(Point::new(0.0, 0.5, -2.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = t.intersects(&r);
+    assert_eq!(1, xs.len());
+    assert_eq!(2.0, xs[0].t);
+}
+
+// Smooth triangles
+
+fn default_smooth_triangle() -> Shape {
+    Shape::smooth_triangle(
+        Point::new(0.0, 1.0, 0.0),
+        Point::new(-1.0, 0.0, 0.0),
+        Point::new(1.0, 0.0, 0.0),
+        Vector::new(0.0, 1.0, 0.0),
+        Vector::new(-1.0, 0.0, 0.0),
+        Vector::new(1.0, 0.0, 0.0))
+}
+
+#[test]
+fn intersection_with_smooth_triangle_stores_u_and_v() {
+    let t = default_smooth_triangle();
+    let r = Ray::new(Point::new(-0.2, 0.3, -2.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = t.intersects(&r);
+    assert_eq!(1, xs.len());
+    assert!((xs[0].u - 0.45).abs() < 0.0001);
+    assert!((xs[0].v - 0.25).abs() < 0.0001);
+}
+
+#[test]
+fn smooth_triangle_uses_u_and_v_to_interpolate_the_normal() {
+    let t = default_smooth_triangle();
+    let r = Ray::new(Point::new(-0.2, 0.3, -2.0), Vector::new(0.0, 0.0, 1.0));
+    let i = Intersection::new(&r, 1.0, &t).with_uv(0.45, 0.25);
+    assert_eq!(Vector::new(-0.5547, 0.83205, 0.0), t.normal_at_uv(i.point(), i.u, i.v));
+}
+
+#[test]
+fn smooth_triangle_normal_at_point_matches_interpolated_normal() {
+    let t = default_smooth_triangle();
+    assert_eq!(Vector::new(-0.5547, 0.83205, 0.0), t.normal_at(Point::new(-0.2, 0.3, 0.0)));
+}
+
+// Groups
+
+#[test]
+fn creating_a_new_group() {
+    let g = Shape::group(vec![]);
+    assert_eq!(Matrix4x4::identity(), g.transform);
+    assert!(g.children().is_empty());
+}
+
+#[test]
+fn group_owns_its_children() {
+    let g = Shape::group(vec![Shape::sphere(), Shape::cube()]);
+    assert_eq!(vec![Shape::sphere(), Shape::cube()], g.children());
+}
+
+#[test]
+fn composite_shapes_have_no_normal_of_their_own() {
+    let p = Point::new(1.0, 0.0, 0.0);
+
+    assert_eq!(None, Shape::group(vec![Shape::sphere()]).try_normal_at(p));
+    assert_eq!(None, Shape::csg(CsgOperation::Union, Shape::sphere(), Shape::cube()).try_normal_at(p));
+    assert_eq!(None, Shape::instance(Arc::new(Shape::sphere())).try_normal_at(p));
+}
+
+#[test]
+fn intersecting_a_ray_with_an_empty_group() {
+    let g = Shape::group(vec![]);
+    let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+    assert!(g.intersects(&r).is_empty());
+}
+
+#[test]
+fn intersecting_a_ray_with_a_nonempty_group() {
+    let g = Shape::group(vec![
+        Shape::sphere(),
+        Shape::sphere().transform(Matrix4x4::identity().translate(0.0, 0.0, -3.0)),
+        Shape::sphere().transform(Matrix4x4::identity().translate(5.0, 0.0, 0.0))
+    ]);
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = g.intersects(&r);
+    let s1 = &g.children()[0];
+    let s2 = &g.children()[1];
+
+    assert_eq!(4, xs.len());
+    assert!(std::ptr::eq(s2, xs[0].object));
+    assert!(std::ptr::eq(s2, xs[1].object));
+    assert!(std::ptr::eq(s1, xs[2].object));
+    assert!(std::ptr::eq(s1, xs[3].object));
+}
+
+#[test]
+fn intersecting_a_transformed_group() {
+    let g = Shape::group(vec![
+        Shape::sphere().transform(Matrix4x4::identity().translate(5.0, 0.0, 0.0))
+    ]).transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0));
+    let r = Ray::new(Point::new(10.0, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0));
+    assert_eq!(2, g.intersects(&r).len());
+}
+
+#[test]
+fn converting_a_point_from_world_to_object_space() {
+    let g1 = Shape::group(vec![
+        Shape::group(vec![
+            Shape::sphere().transform(Matrix4x4::identity().translate(5.0, 0.0, 0.0))
+        ]).transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0))
+    ]).transform(Matrix4x4::identity().rotation_y(std::f64::consts::PI / 2.0));
+    let s = &g1.children()[0].children()[0];
+
+    assert_eq!(Point::new(0.0, 0.0, -1.0), s.world_to_object(Point::new(-2.0, 0.0, -10.0)));
+}
+
+#[test]
+fn converting_a_normal_from_object_to_world_space() {
+    let g1 = Shape::group(vec![
+        Shape::group(vec![
+            Shape::sphere().transform(Matrix4x4::identity().translate(5.0, 0.0, 0.0))
+        ]).transform(Matrix4x4::identity().scale(1.0, 2.0, 3.0))
+    ]).transform(Matrix4x4::identity().rotation_y(std::f64::consts::PI / 2.0));
+    let s = &g1.children()[0].children()[0];
+    let n = Vector::new(3.0_f64.sqrt() / 3.0, 3.0_f64.sqrt() / 3.0, 3.0_f64.sqrt() / 3.0);
+
+    assert_eq!(Vector::new(0.2857, 0.4286, -0.8571), s.normal_to_world(n));
+}
+
+#[test]
+fn finding_the_normal_on_a_child_object() {
+    let g1 = Shape::group(vec![
+        Shape::group(vec![
+            Shape::sphere().transform(Matrix4x4::identity().translate(5.0, 0.0, 0.0))
+        ]).transform(Matrix4x4::identity().scale(1.0, 2.0, 3.0))
+    ]).transform(Matrix4x4::identity().rotation_y(std::f64::consts::PI / 2.0));
+    let s = &g1.children()[0].children()[0];
+
+    assert_eq!(Vector::new(0.2857, 0.4286, -0.8571), s.normal_at(Point::new(1.7321, 1.1547, -5.5774)));
+}
+
+// Constructive solid geometry
+
+#[test]
+fn csg_is_created_with_an_operation_and_two_shapes() {
+    let c = Shape::csg(CsgOperation::Union, Shape::sphere(), Shape::cube());
+    assert_eq!(vec![Shape::sphere(), Shape::cube()], c.children());
+}
+
+#[test]
+fn evaluating_the_rule_for_a_csg_operation() {
+    let examples = vec![
+        (CsgOperation::Union, true, true, true, false),
+        (CsgOperation::Union, true, true, false, true),
+        (CsgOperation::Union, true, false, true, false),
+        (CsgOperation::Union, true, false, false, true),
+        (CsgOperation::Union, false, true, true, false),
+        (CsgOperation::Union, false, true, false, false),
+        (CsgOperation::Union, false, false, true, true),
+        (CsgOperation::Union, false, false, false, true),
+        (CsgOperation::Intersection, true, true, true, true),
+        (CsgOperation::Intersection, true, true, false, false),
+        (CsgOperation::Intersection, true, false, true, true),
+        (CsgOperation::Intersection, true, false, false, false),
+        (CsgOperation::Intersection, false, true, true, true),
+        (CsgOperation::Intersection, false, true, false, true),
+        (CsgOperation::Intersection, false, false, true, false),
+        (CsgOperation::Intersection, false, false, false, false),
+        (CsgOperation::Difference, true, true, true, false),
+        (CsgOperation::Difference, true, true, false, true),
+        (CsgOperation::Difference, true, false, true, false),
+        (CsgOperation::Difference, true, false, false, true),
+        (CsgOperation::Difference, false, true, true, true),
+        (CsgOperation::Difference, false, true, false, true),
+        (CsgOperation::Difference, false, false, true, false),
+        (CsgOperation::Difference, false, false, false, false)
+    ];
+
+    for (operation, left_hit, in_left, in_right, result) in examples {
+        assert_eq!(result, operation.allows(left_hit, in_left, in_right));
+    }
+}
+
+#[test]
+fn filtering_a_list_of_intersections() {
+    let examples = vec![
+        (CsgOperation::Union, 0, 3),
+        (CsgOperation::Intersection, 1, 2),
+        (CsgOperation::Difference, 0, 1)
+    ];
+
+    for (operation, x0, x1) in examples {
+        let c = Shape::csg(operation, Shape::sphere(), Shape::cube());
+        let s1 = &c.children()[0];
+        let s2 = &c.children()[1];
+        let r = Ray::new(Point::origin(), Vector::new(0.0, 0.0, 1.0));
+        let xs = [
+            Intersection::new(&r, 1.0, s1),
+            Intersection::new(&r, 2.0, s2),
+            Intersection::new(&r, 3.0, s1),
+            Intersection::new(&r, 4.0, s2)
+        ];
+        let tagged = xs.iter().map(|i| (std::ptr::eq(i.object, s1), *i)).collect();
+        let result = Shape::filter_intersections(operation, tagged);
+
+        assert_eq!(vec![xs[x0], xs[x1]], result);
+    }
+}
+
+#[test]
+fn ray_misses_a_csg_object() {
+    let c = Shape::csg(CsgOperation::Union, Shape::sphere(), Shape::cube());
+    let r = Ray::new(Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+    assert!(c.intersects(&r).is_empty());
+}
+
+#[test]
+fn ray_hits_a_csg_object() {
+    let c = Shape::csg(
+        CsgOperation::Union,
+        Shape::sphere(),
+        Shape::sphere().transform(Matrix4x4::identity().translate(0.0, 0.0, 0.5)));
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = c.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert_eq!(4.0, xs[0].t);
+    assert!(std::ptr::eq(&c.children()[0], xs[0].object));
+    assert_eq!(6.5, xs[1].t);
+    assert!(std::ptr::eq(&c.children()[1], xs[1].object));
+}
+
+#[test]
+fn csg_of_instances_of_the_same_geometry() {
+    let geometry = Arc::new(Shape::sphere());
+    let left = Shape::instance(geometry.clone());
+    let right = Shape::instance(geometry).transform(Matrix4x4::identity().translate(0.0, 0.0, 0.5));
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let examples = vec![
+        (CsgOperation::Union, vec![4.0, 6.5]),
+        (CsgOperation::Intersection, vec![4.5, 6.0]),
+        (CsgOperation::Difference, vec![4.0, 4.5])
+    ];
+
+    for (operation, ts) in examples {
+        let c = Shape::csg(operation, left.clone(), right.clone());
+        assert_eq!(ts, c.intersects(&r).iter().map(|i| i.t).collect::<Vec<f64>>());
+    }
+}
+
+// Tori
+
+#[test]
+fn ray_strikes_a_torus() {
+    let torus = Shape::torus(1.0, 0.25);
+    let examples = vec![
+        (Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), vec![3.75, 4.25, 5.75, 6.25]),
+        (Point::new(1.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), vec![4.75, 5.25]),
+        (Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 2.0), vec![-0.625, -0.375, 0.375, 0.625]),
+        (Point::new(-2000.0, 0.1, 0.0), Vector::new(1.0, 0.0, 0.0), vec![1998.77087, 1999.22913, 2000.77087, 2001.22913])
+    ];
+
+    for (origin, direction, ts) in examples {
+        let r = Ray::new(origin, direction);
+        let xs = torus.intersects(&r);
+        assert_eq!(ts.len(), xs.len());
+
+        for (t, i) in ts.iter().zip(xs) {
+            assert!((t - i.t).abs() < 0.0001, "expected {} but got {}", t, i.t);
+        }
+    }
+}
+
+#[test]
+fn ray_misses_a_torus() {
+    let torus = Shape::torus(1.0, 0.25);
+    let examples = vec![
+        (Point::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0)),
+        (Point::new(-5.0, 0.5, 0.0), Vector::new(1.0, 0.0, 0.0)),
+        (Point::new(-5.0, 0.0, 3.0), Vector::new(1.0, 0.0, 0.0))
+    ];
+
+    for (origin, direction) in examples {
+        let r = Ray::new(origin, direction);
+        assert!(torus.intersects(&r).is_empty());
+    }
+}
+
+#[test]
+fn normal_on_a_torus() {
+    let torus = Shape::torus(1.0, 0.25);
+    let examples = vec![
+        (Point::new(1.25, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0)),
+        (Point::new(0.75, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0)),
+        (Point::new(0.0, 0.25, 1.0), Vector::new(0.0, 1.0, 0.0)),
+        (Point::new(0.0, 0.0, -1.25), Vector::new(0.0, 0.0, -1.0)),
+        (Point::new(1.0 + 0.25 * 0.5_f64.sqrt(), 0.25 * 0.5_f64.sqrt(), 0.0), Vector::new(0.5_f64.sqrt(), 0.5_f64.sqrt(), 0.0))
+    ];
+
+    for (point, normal) in examples {
+        assert_eq!(normal, torus.normal_at(point));
+    }
+}
+
+// Disks and rectangles
+
+#[test]
+fn ray_strikes_a_disk() {
+    let disk = Shape::disk(2.0);
+    let examples = vec![
+        (Point::new(0.0, 1.0, 0.0), 1),
+        (Point::new(1.5, 1.0, 0.5), 1),
+        (Point::new(0.0, 1.0, 2.0), 1),
+        (Point::new(2.0, 1.0, 1.0), 0),
+        (Point::new(0.0, 1.0, -2.5), 0)
+    ];
+
+    for (origin, count) in examples {
+        let r = Ray::new(origin, Vector::new(0.0, -1.0, 0.0));
+        let xs = disk.intersects(&r);
+        assert_eq!(count, xs.len());
+        assert!(xs.iter().all(|i| i.t == 1.0));
+    }
+}
+
+#[test]
+fn ray_passes_through_the_hole_in_an_annulus() {
+    let annulus = Shape::annulus(0.5, 1.0);
+    let examples = vec![
+        (Point::new(0.0, 1.0, 0.0), 0),
+        (Point::new(0.3, 1.0, 0.3), 0),
+        (Point::new(0.5, 1.0, 0.0), 1),
+        (Point::new(0.0, 1.0, -0.75), 1),
+        (Point::new(1.0, 1.0, 1.0), 0)
+    ];
+
+    for (origin, count) in examples {
+        let r = Ray::new(origin, Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(count, annulus.intersects(&r).len());
+    }
+}
+
+#[test]
+fn ray_parallel_to_a_disk() {
+    let disk = Shape::disk(1.0);
+    let r = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+    assert!(disk.intersects(&r).is_empty());
+}
+
+#[test]
+fn ray_strikes_a_rectangle() {
+    let rect = Shape::rectangle(4.0, 2.0);
+    let examples = vec![
+        (Point::new(0.0, 1.0, 0.0), 1),
+        (Point::new(1.9, 1.0, 0.9), 1),
+        (Point::new(-2.0, 1.0, -1.0), 1),
+        (Point::new(2.1, 1.0, 0.0), 0),
+        (Point::new(0.0, 1.0, 1.1), 0)
+    ];
+
+    for (origin, count) in examples {
+        let r = Ray::new(origin, Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(count, rect.intersects(&r).len());
+    }
+}
+
+#[test]
+fn normal_on_disks_and_rectangles() {
+    let n = Vector::new(0.0, 1.0, 0.0);
+    assert_eq!(n, Shape::disk(1.0).normal_at(Point::new(0.5, 0.0, 0.0)));
+    assert_eq!(n, Shape::annulus(0.5, 1.0).normal_at(Point::new(0.0, 0.0, 0.75)));
+    assert_eq!(n, Shape::rectangle(1.0, 1.0).normal_at(Point::new(0.1, 0.0, -0.2)));
+}
+
+#[test]
+fn transformed_rectangle_is_a_mirror_on_a_wall() {
+    let mirror = Shape::rectangle(2.0, 1.0).
+        transform(Matrix4x4::identity().rotation_x(std::f64::consts::PI / 2.0).translate(0.0, 0.0, 5.0));
+    let r = Ray::new(Point::new(0.5, 0.25, 0.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = mirror.intersects(&r);
+
+    assert_eq!(1, xs.len());
+    assert_eq!(5.0, xs[0].t);
+    assert_eq!(Vector::new(0.0, 0.0, 1.0), mirror.normal_at(xs[0].point()));
+}
+
+// Distance fields
+
+#[test]
+fn ray_strikes_a_transformed_distance_field() {
+    let shape = Shape::distance_field(DistanceField::new(sdf::rounded_box(Vector::new(1.0, 1.0, 1.0), 0.2))).
+        transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0));
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = shape.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert!((xs[0].t - 3.0).abs() < 0.001);
+    assert!((xs[1].t - 7.0).abs() < 0.001);
+    assert_eq!(Vector::new(0.0, 0.0, -1.0), shape.normal_at(xs[0].point()));
+}
+
+#[test]
+fn distance_field_as_a_csg_operand() {
+    let shape = Shape::csg(
+        CsgOperation::Intersection,
+        Shape::distance_field(DistanceField::new(sdf::sphere(1.0))),
+        Shape::cube().transform(Matrix4x4::identity().translate(0.0, 0.0, 1.0)));
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = shape.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert!((xs[0].t - 5.0).abs() < 0.001);
+    assert!((xs[1].t - 6.0).abs() < 0.001);
+}
+
+#[test]
+fn ray_starting_inside_a_distance_field_csg_operand() {
+    let shape = Shape::csg(
+        CsgOperation::Difference,
+        Shape::distance_field(DistanceField::new(sdf::sphere(1.0))),
+        Shape::cube().transform(Matrix4x4::identity().scale(0.1, 0.1, 0.1).translate(0.0, 0.0, 0.5)));
+    let r = Ray::new(Point::origin(), Vector::new(0.0, 0.0, 1.0));
+    let ts: Vec<f64> = shape.intersects(&r).iter().map(|i| i.t).collect();
+
+    assert_eq!(4, ts.len());
+
+    for (t, expected) in ts.iter().zip([-1.0, 0.4, 0.6, 1.0]) {
+        assert!((t - expected).abs() < 0.001);
+    }
+}
+
+#[test]
+fn distance_field_in_a_world_casts_shadows() {
+    let w = crate::World {
+        light: crate::PointLight::new(Point::new(0.0, 10.0, 0.0), crate::Color::white()),
+        objects: vec![
+            Shape::plane(),
+            Shape::distance_field(DistanceField::new(sdf::sphere(1.0))).
+                transform(Matrix4x4::identity().translate(0.0, 3.0, 0.0))
+        ]
+    };
+
+    assert!(w.is_shadowed(Point::new(0.0, 0.0, 0.0)));
+    assert!(!w.is_shadowed(Point::new(3.0, 0.0, 0.0)));
+}
+
+// Heightfields
+
+#[test]
+fn ray_strikes_a_scaled_heightfield() {
+    let terrain = Shape::heightfield(Heightfield::new(vec![
+        vec![0.0, 0.0, 0.0],
+        vec![0.0, 1.0, 0.0],
+        vec![0.0, 0.0, 0.0]
+    ])).transform(Matrix4x4::identity().scale(10.0, 2.0, 10.0));
+    let r = Ray::new(Point::new(5.0, 10.0, 5.0), Vector::new(0.0, -1.0, 0.0));
+    let xs = terrain.intersects(&r);
+
+    assert_eq!(1, xs.len());
+    assert!((xs[0].t - 8.0).abs() < 0.0001);
+    assert_eq!(Vector::new(0.0, 1.0, 0.0), terrain.normal_at(xs[0].point()));
+}
+
+// Blobs
+
+#[test]
+fn ray_strikes_a_translated_blob() {
+    let blob = Shape::blob(Blob::new(0.25).component(Point::origin(), 1.0, 1.0)).
+        transform(Matrix4x4::identity().translate(0.0, 0.0, 2.0));
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = blob.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert!((xs[0].t - (7.0 - 0.5_f64.sqrt())).abs() < 0.0001);
+    assert_eq!(Vector::new(0.0, 0.0, -1.0), blob.normal_at(xs[0].point()));
+}
+
+#[test]
+fn ray_strikes_a_hugely_scaled_blob() {
+    let blob = Shape::blob(Blob::new(0.25).component(Point::origin(), 1.0, 1.0)).
+        transform(Matrix4x4::identity().scale(1000.0, 1000.0, 1000.0));
+    let r = Ray::new(Point::new(0.0, 0.0, -5000.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = blob.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert!((xs[0].t - (5000.0 - 1000.0 * 0.5_f64.sqrt())).abs() < 0.001);
+}
+
+// Bézier patches
+
+#[test]
+fn intersecting_a_bezier_patch_records_its_surface_coordinates() {
+    let mut points = [Point::origin(); 16];
+    for (i, point) in points.iter_mut().enumerate() {
+        *point = Point::new((i % 4) as f64, 0.0, (i / 4) as f64);
+    }
+    let patch = Shape::bezier_patch(BezierPatch::new(points)).
+        transform(Matrix4x4::identity().translate(0.0, 1.0, 0.0));
+    let r = Ray::new(Point::new(1.5, 5.0, 0.75), Vector::new(0.0, -1.0, 0.0));
+    let xs = patch.intersects(&r);
+
+    assert_eq!(1, xs.len());
+    assert!((xs[0].t - 4.0).abs() < 0.0001);
+    assert!((xs[0].u - 0.5).abs() < 0.0001);
+    assert!((xs[0].v - 0.25).abs() < 0.0001);
+    assert_eq!(Vector::new(0.0, -1.0, 0.0), patch.normal_at_uv(xs[0].point(), xs[0].u, xs[0].v));
+}
+
+// Instances
+
+#[test]
+fn instances_share_their_geometry() {
+    let geometry = Arc::new(Shape::sphere());
+    let instances: Vec<Shape> = (0..3).
+        map(|i| Shape::instance(geometry.clone()).transform(Matrix4x4::identity().translate(i as f64 * 3.0, 0.0, 0.0))).
+        collect();
+
+    assert_eq!(4, Arc::strong_count(&geometry));
+    assert!(std::ptr::eq(&*geometry, &instances[2].children()[0]));
+}
+
+#[test]
+fn intersecting_a_transformed_instance() {
+    let geometry = Arc::new(Shape::sphere().transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0)));
+    let instance = Shape::instance(geometry.clone()).transform(Matrix4x4::identity().translate(5.0, 0.0, 0.0));
+    let r = Ray::new(Point::new(5.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = instance.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert_eq!(3.0, xs[0].t);
+    assert_eq!(7.0, xs[1].t);
+    assert!(std::ptr::eq(&*geometry, xs[0].object));
+}
+
+#[test]
+fn instances_inside_a_group_use_the_group_transform() {
+    let geometry = Arc::new(Shape::sphere());
+    let group = Shape::group(vec![
+        Shape::instance(geometry).transform(Matrix4x4::identity().translate(0.0, 0.0, 2.0))
+    ]).transform(Matrix4x4::identity().rotation_y(std::f64::consts::PI / 2.0));
+    let r = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+    let xs = group.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert_eq!(6.0, xs[0].t);
+    assert_eq!(Vector::new(-1.0, 0.0, 0.0), xs[0].normal());
+}
+
+#[test]
+fn instances_can_be_nested() {
+    let geometry = Arc::new(Shape::sphere());
+    let pair = Arc::new(Shape::group(vec![
+        Shape::instance(geometry.clone()).transform(Matrix4x4::identity().translate(-2.0, 0.0, 0.0)),
+        Shape::instance(geometry).transform(Matrix4x4::identity().translate(2.0, 0.0, 0.0))
+    ]));
+    let instance = Shape::instance(pair).transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0));
+    let r = Ray::new(Point::new(4.0, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = instance.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert_eq!(8.0, xs[0].t);
+    assert_eq!(Vector::new(0.0, 0.0, -1.0), xs[0].normal());
+    assert_eq!(Point::new(0.0, 0.0, -1.0), xs[0].object_point());
+}
+
+#[test]
+fn instances_keep_their_geometry_materials_unless_overridden() {
+    let geometry = Arc::new(Shape::sphere().material(Material::new().ambient(0.5)));
+    let plain = Shape::instance(geometry.clone());
+    let overridden = Shape::instance(geometry).material(Material::new().ambient(1.0));
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+
+    assert_eq!(&Material::new().ambient(0.5), plain.intersects(&r)[0].material);
+    assert_eq!(&Material::new().ambient(1.0), overridden.intersects(&r)[0].material);
+}
+
+// Quadrics
+
+#[test]
+fn normal_on_a_transformed_quadric() {
+    let q = Shape::quadric(Quadric::ellipsoid(1.0, 2.0, 1.0)).
+        transform(Matrix4x4::identity().translate(0.0, 1.0, 0.0));
+    let r = Ray::new(Point::new(0.0, 10.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+    let xs = q.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert!((xs[0].t - 7.0).abs() < 0.0001);
+    assert_eq!(Vector::new(0.0, 1.0, 0.0), q.normal_at(xs[0].point()));
+}
+
+#[test]
+fn ray_strikes_a_hugely_scaled_quadric() {
+    let q = Shape::quadric(Quadric::ellipsoid(1.0, 2.0, 1.0)).
+        transform(Matrix4x4::identity().scale(1e5, 1e5, 1e5));
+    let r = Ray::new(Point::new(0.0, 0.0, -3e5), Vector::new(0.0, 0.0, 1.0));
+    let xs = q.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert!((xs[0].t - 2e5).abs() < 0.001);
+    assert!((xs[1].t - 4e5).abs() < 0.001);
+}
+
+// Bounds
+
+#[test]
+fn bounds_of_primitive_shapes() {
+    let unit = Bounds::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+    let examples = vec![
+        (Shape::sphere(), unit),
+        (Shape::cube(), unit),
+        (Shape::cylinder(-5.0, 3.0, false), Bounds::new(Point::new(-1.0, -5.0, -1.0), Point::new(1.0, 3.0, 1.0))),
+        (Shape::cone(-5.0, 3.0, true), Bounds::new(Point::new(-5.0, -5.0, -5.0), Point::new(5.0, 3.0, 5.0))),
+        (Shape::torus(2.0, 0.5), Bounds::new(Point::new(-2.5, -0.5, -2.5), Point::new(2.5, 0.5, 2.5))),
+        (Shape::disk(2.0), Bounds::new(Point::new(-2.0, 0.0, -2.0), Point::new(2.0, 0.0, 2.0))),
+        (Shape::rectangle(4.0, 2.0), Bounds::new(Point::new(-2.0, 0.0, -1.0), Point::new(2.0, 0.0, 1.0))),
+        (
+            Shape::triangle(Point::new(-3.0, 7.0, 2.0), Point::new(6.0, 2.0, -4.0), Point::new(2.0, -1.0, -1.0)),
+            Bounds::new(Point::new(-3.0, -1.0, -4.0), Point::new(6.0, 7.0, 2.0))
+        )
+    ];
+
+    for (shape, expected) in examples {
+        assert_eq!(expected, shape.object_bounds());
+    }
+}
+
+#[test]
+fn bounds_of_unbounded_shapes() {
+    let plane = Shape::plane().object_bounds();
+    assert_eq!(f64::NEG_INFINITY, plane.min.x);
+    assert_eq!(0.0, plane.min.y);
+    assert_eq!(f64::INFINITY, plane.max.z);
+
+    let cylinder = Shape::cylinder(f64::NEG_INFINITY, f64::INFINITY, false).object_bounds();
+    assert_eq!(-1.0, cylinder.min.x);
+    assert_eq!(f64::INFINITY, cylinder.max.y);
+
+    assert!(Shape::distance_field(DistanceField::new(sdf::sphere(1.0))).object_bounds().is_infinite());
+}
+
+#[test]
+fn bounds_of_a_transformed_shape() {
+    let s = Shape::sphere().
+        transform(Matrix4x4::identity().scale(0.5, 2.0, 4.0).translate(1.0, -3.0, 5.0));
+
+    assert_eq!(Point::new(0.5, -5.0, 1.0), s.bounds().min);
+    assert_eq!(Point::new(1.5, -1.0, 9.0), s.bounds().max);
+}
+
+#[test]
+fn bounds_of_a_group_contain_its_children() {
+    let s = Shape::sphere().
+        transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0).translate(2.0, 5.0, -3.0));
+    let c = Shape::cylinder(-2.0, 2.0, false).
+        transform(Matrix4x4::identity().scale(0.5, 1.0, 0.5).translate(-4.0, -1.0, 4.0));
+    let g = Shape::group(vec![s, c]);
+
+    assert_eq!(Point::new(-4.5, -3.0, -5.0), g.object_bounds().min);
+    assert_eq!(Point::new(4.0, 7.0, 4.5), g.object_bounds().max);
+    assert!(Shape::group(vec![]).object_bounds().is_empty());
+}
+
+#[test]
+fn bounds_of_csg_and_instances() {
+    let csg = Shape::csg(
+        CsgOperation::Difference,
+        Shape::sphere(),
+        Shape::sphere().transform(Matrix4x4::identity().translate(2.0, 3.0, 4.0)));
+    assert_eq!(Point::new(-1.0, -1.0, -1.0), csg.object_bounds().min);
+    assert_eq!(Point::new(3.0, 4.0, 5.0), csg.object_bounds().max);
+
+    let geometry = Arc::new(Shape::cube().transform(Matrix4x4::identity().scale(2.0, 1.0, 1.0)));
+    let instance = Shape::instance(geometry).transform(Matrix4x4::identity().translate(0.0, 1.0, 0.0));
+    assert_eq!(Point::new(-2.0, 0.0, -1.0), instance.bounds().min);
+    assert_eq!(Point::new(2.0, 2.0, 1.0), instance.bounds().max);
+}
+
+#[test]
+fn world_bounds_pass_through_every_group() {
+    let g = Shape::group(vec![
+        Shape::group(vec![
+            Shape::sphere().transform(Matrix4x4::identity().translate(5.0, 0.0, 0.0))
+        ]).transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0))
+    ]).transform(Matrix4x4::identity().rotation_y(std::f64::consts::PI / 2.0));
+    let s = &g.children()[0].children()[0];
+
+    assert_eq!(Point::new(-2.0, -2.0, -12.0), s.world_bounds().min);
+    assert_eq!(Point::new(2.0, 2.0, -8.0), s.world_bounds().max);
+}
+
+#[test]
+fn groups_skip_rays_that_miss_their_bounds() {
+    let g = Shape::group(vec![
+        Shape::sphere().transform(Matrix4x4::identity().translate(-2.0, 0.0, 0.0)),
+        Shape::sphere().transform(Matrix4x4::identity().translate(2.0, 0.0, 0.0))
+    ]);
+    let miss = Ray::new(Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let hit = Ray::new(Point::new(2.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+
+    assert!(!g.object_bounds().intersects(&miss));
+    assert!(g.intersects(&miss).is_empty());
+    assert_eq!(2, g.intersects(&hit).len());
+}
+
+#[test]
+fn groups_containing_planes_are_never_skipped() {
+    let g = Shape::group(vec![Shape::plane()]).
+        transform(Matrix4x4::identity().rotation_x(std::f64::consts::PI / 2.0));
+    let r = Ray::new(Point::new(100.0, 50.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+
+    assert_eq!(1, g.intersects(&r).len());
+}
+
+// Motion
+
+#[test]
+fn intersecting_a_moving_shape_at_different_times() {
+    let s = Shape::sphere().motion(Matrix4x4::identity().translate(4.0, 0.0, 0.0));
+    let r = Ray::new(Point::new(2.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+
+    assert!(s.intersects(&r).is_empty());
+    assert!(s.intersects(&r.time(1.0)).is_empty());
+
+    let r = r.time(0.5);
+    let xs = s.intersects(&r);
+    assert_eq!(2, xs.len());
+    assert_eq!(4.0, xs[0].t);
+    assert_eq!(Vector::new(0.0, 0.0, -1.0), xs[0].normal());
+    assert_eq!(Point::new(0.0, 0.0, -1.0), xs[0].object_point());
+}
+
+#[test]
+fn children_of_a_moving_group_move_with_it() {
+    let g = Shape::group(vec![
+        Shape::sphere().transform(Matrix4x4::identity().translate(0.0, 0.0, 3.0))
+    ]).keyframes(vec![
+        (0.0, Matrix4x4::identity()),
+        (1.0, Matrix4x4::identity().rotation_y(std::f64::consts::PI / 2.0))
+    ]);
+    let r = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0)).time(1.0);
+    let xs = g.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert_eq!(7.0, xs[0].t);
+    assert_eq!(Vector::new(-1.0, 0.0, 0.0), xs[0].normal());
+}
+
+#[test]
+fn giving_a_shape_a_transform_stops_its_motion() {
+    let s = Shape::sphere().
+        motion(Matrix4x4::identity().translate(4.0, 0.0, 0.0)).
+        transform(Matrix4x4::identity());
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)).time(1.0);
+
+    assert_eq!(2, s.intersects(&r).len());
+}
+
+#[test]
+fn bounds_of_a_moving_shape_cover_its_motion() {
+    let s = Shape::sphere().motion(Matrix4x4::identity().translate(4.0, 0.0, 0.0));
+    let b = s.bounds();
+
+    assert!(b.contains(Point::new(-1.0, -1.0, -1.0)));
+    assert!(b.contains(Point::new(5.0, 1.0, 1.0)));
+
+    // The group around it mustn't skip rays that only meet it late on.
+    let g = Shape::group(vec![s]);
+    let r = Ray::new(Point::new(4.5, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)).time(1.0);
+    assert_eq!(2, g.intersects(&r).len());
+}
+
+// Displacement
+
+#[test]
+fn tessellating_a_sphere() {
+    let s = Shape::sphere().transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0));
+    let mesh = s.tessellate(4).unwrap();
+
+    assert_eq!(9 * 5, mesh.vertices.len());
+    assert_eq!(2 * 8 * 4, mesh.triangles.len());
+
+    for (p, n) in mesh.vertices.iter().zip(&mesh.normals) {
+        assert!(((*p - Point::origin()).magnitude() - 2.0).abs() < 0.0001);
+        assert_eq!((*p - Point::origin()).normalize(), *n);
+    }
+}
+
+#[test]
+fn tessellating_a_group_places_its_children() {
+    let g = Shape::group(vec![
+        Shape::cube().transform(Matrix4x4::identity().translate(3.0, 0.0, 0.0)),
+        Shape::triangle(Point::new(0.0, 1.0, 0.0), Point::new(-1.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0))
+    ]);
+    let mesh = g.tessellate(1).unwrap();
+    let b = Bounds::from_points(mesh.vertices.clone());
+
+    assert_eq!(6 * 4 + 3, mesh.vertices.len());
+    assert_eq!(Point::new(-1.0, -1.0, -1.0), b.min);
+    assert_eq!(Point::new(4.0, 1.0, 1.0), b.max);
+}
+
+#[test]
+fn some_shapes_cannot_be_tessellated() {
+    assert_eq!(None, Shape::plane().tessellate(4));
+    assert_eq!(None, Shape::cylinder(f64::NEG_INFINITY, f64::INFINITY, false).tessellate(4));
+    assert_eq!(None, Shape::group(vec![Shape::sphere(), Shape::plane()]).tessellate(4));
+    assert!(Shape::cylinder(0.0, 1.0, true).tessellate(4).is_some());
+}
+
+#[test]
+fn displacing_a_sphere_grows_it() {
+    let s = Shape::sphere().displace(&Pattern::solid(Color::white()), 0.5, 16).unwrap();
+    let r = Ray::new(Point::new(0.1, 0.2, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = s.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert!((xs[0].t - (5.0 - (1.5_f64.powi(2) - 0.05).sqrt())).abs() < 0.05);
+    assert!(xs[0].normal().z < -0.9);
+}
+
+#[test]
+fn displacement_follows_the_pattern() {
+    let s = Shape::rectangle(2.0, 2.0).displace(&Pattern::stripe(Color::white(), Color::black()), 0.5, 8).unwrap();
+    let down = Vector::new(0.0, -1.0, 0.0);
+
+    let raised = Ray::new(Point::new(0.5, 5.0, 0.3), down);
+    let flat = Ray::new(Point::new(-0.5, 5.0, 0.3), down);
+
+    assert!((s.intersects(&raised)[0].t - 4.5).abs() < 0.0001);
+    assert!((s.intersects(&flat)[0].t - 5.0).abs() < 0.0001);
+}
+
+#[test]
+fn displaced_shapes_keep_their_transform_and_material() {
+    let m = Material::new().ambient(0.7);
+    let t = Matrix4x4::identity().translate(0.0, 2.0, 0.0);
+    let s = Shape::torus(1.0, 0.25).
+        material(m.clone()).
+        transform(t).
+        displace(&Pattern::solid(Color::black()), 1.0, 4).
+        unwrap();
+
+    assert_eq!(t, s.transform);
+    assert_eq!(m, s.material);
+    assert!(s.children().iter().all(|child| child.material == m));
+    assert_eq!(Point::origin(), s.children()[0].world_to_object(Point::new(0.0, 2.0, 0.0)));
+}
+
+#[test]
+fn displaced_cubes_stay_closed() {
+    let s = Shape::cube().displace(&Pattern::solid(Color::white()), 0.5, 2).unwrap();
+    let mesh = s.tessellate(1).unwrap().weld();
+    let mut edges = std::collections::HashMap::new();
+
+    for t in &mesh.triangles {
+        for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+            *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+        }
+    }
+
+    assert!(edges.values().all(|&count| count == 2));
+}
+
+#[test]
+fn shapes_that_cannot_be_tessellated_cannot_be_displaced() {
+    let pattern = Pattern::solid(Color::white());
+
+    assert_eq!(None, Shape::plane().displace(&pattern, 0.5, 4));
+    assert_eq!(None, Shape::group(vec![Shape::sphere(), Shape::plane()]).displace(&pattern, 0.5, 4));
+}
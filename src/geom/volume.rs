@@ -0,0 +1,199 @@
+// Participating media, such as fog and smoke, filling the inside of a
+// boundary shape with a constant density of tiny particles.
+//
+// A volume has no surface of its own, so rays pass straight through it.
+// Instead the world finds the stretches of each ray that lie inside a
+// volume with `Shape::media`, dims whatever lies beyond by how much of the
+// light the particles absorb or scatter away, and adds the light scattered
+// towards the eye along the way.
+
+use super::{Shape, ShapeType};
+use crate::{Color, Matrix4x4, Ray};
+use std::f64::consts::PI;
+
+// How light is spread when it scatters off a particle, as a function of the
+// cosine of the angle between the light's direction of travel before and
+// after scattering.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Phase {
+    // Scatters equally in every direction.
+    Isotropic,
+    // The Henyey-Greenstein phase function, which favours scattering
+    // forward for `g` between 0 and 1 and back for `g` between -1 and 0.
+    HenyeyGreenstein(f64)
+}
+
+impl Phase {
+    pub fn value(&self, cos_theta: f64) -> f64 {
+        match self {
+            Self::Isotropic => 1.0 / (4.0 * PI),
+            Self::HenyeyGreenstein(g) => {
+                let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+                (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Medium {
+    // The chance of light meeting a particle per unit of distance travelled
+    // in world space.
+    pub density: f64,
+    // The fraction of light meeting a particle that is scattered rather
+    // than absorbed, for each channel.
+    pub albedo: Color,
+    pub phase: Phase
+}
+
+impl Medium {
+    pub fn new(density: f64) -> Medium {
+        Medium {
+            density,
+            albedo: Color::white(),
+            phase: Phase::Isotropic
+        }
+    }
+
+    pub fn albedo(self, albedo: Color) -> Medium {
+        Medium {
+            albedo,
+            ..self
+        }
+    }
+
+    pub fn phase(self, phase: Phase) -> Medium {
+        Medium {
+            phase,
+            ..self
+        }
+    }
+
+    // The fraction of light that passes through `distance` of the medium
+    // without meeting a particle.
+    pub fn transmittance(&self, distance: f64) -> f64 {
+        (-self.density * distance).exp()
+    }
+}
+
+impl Shape {
+    // Fills the inside of `boundary` with a medium. The boundary should be
+    // closed, so that rays always enter and leave it in pairs.
+    pub fn volume(boundary: Shape, medium: Medium) -> Shape {
+        let mut volume = Self::new(ShapeType::Volume { boundary: Box::new(boundary), medium });
+        volume.set_parent_inverse(Matrix4x4::identity());
+        volume
+    }
+
+    // The stretches of the ray, from t0 to t1, that pass through a volume,
+    // clipped to start no earlier than the ray's origin.
+    pub fn media<'a>(&'a self, ray: &'a Ray) -> Vec<(f64, f64, &'a Medium)> {
+        self.parent_space_media(ray, *ray, Matrix4x4::identity())
+    }
+
+    fn parent_space_media<'a>(&'a self, ray: &'a Ray, parent_ray: Ray, world_to_parent: Matrix4x4) -> Vec<(f64, f64, &'a Medium)> {
+        let inverse = self.inverse_transform_at(ray.time);
+        let object_ray = parent_ray.transform(inverse);
+        let world_to_object = inverse * world_to_parent;
+
+        match &self.shape_type {
+            ShapeType::Group { bounds, .. } if !bounds.intersects(&object_ray) => vec![],
+            ShapeType::Group { children, .. } =>
+                children.
+                    iter().
+                    flat_map(|child| child.parent_space_media(ray, object_ray, world_to_object)).
+                    collect(),
+            ShapeType::Instance { geometry, .. } =>
+                geometry.parent_space_media(ray, object_ray, world_to_object),
+            ShapeType::Volume { boundary, medium } => {
+                let mut ts: Vec<f64> = boundary.
+                    parent_space_intersects(ray, object_ray, world_to_object).
+                    iter().
+                    map(|i| i.t).
+                    collect();
+                ts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+                ts.
+                    chunks_exact(2).
+                    map(|pair| (pair[0].max(0.0), pair[1])).
+                    filter(|(t0, t1)| t0 < t1).
+                    map(|(t0, t1)| (t0, t1, medium)).
+                    collect()
+            },
+            _ => vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geom::{sdf, DistanceField};
+    use crate::{Point, Vector};
+
+    #[test]
+    fn henyey_greenstein_phase() {
+        assert!((Phase::HenyeyGreenstein(0.0).value(0.3) - Phase::Isotropic.value(0.3)).abs() < 1e-12);
+
+        let forward = Phase::HenyeyGreenstein(0.6);
+        assert!(forward.value(1.0) > forward.value(0.0));
+        assert!(forward.value(0.0) > forward.value(-1.0));
+        assert!((forward.value(1.0) - 0.64 / (4.0 * PI * 0.064)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn phase_functions_integrate_to_one() {
+        for phase in [Phase::Isotropic, Phase::HenyeyGreenstein(0.7), Phase::HenyeyGreenstein(-0.3)] {
+            // Over the sphere of directions, d(omega) = 2 pi d(cos theta).
+            let steps = 10000;
+            let total: f64 = (0..steps).
+                map(|i| -1.0 + (i as f64 + 0.5) * 2.0 / steps as f64).
+                map(|cos_theta| phase.value(cos_theta) * 2.0 * PI * 2.0 / steps as f64).
+                sum();
+
+            assert!((total - 1.0).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn transmittance_falls_off_exponentially() {
+        let m = Medium::new(0.5);
+        assert_eq!(1.0, m.transmittance(0.0));
+        assert!((m.transmittance(2.0) - (-1.0_f64).exp()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn media_along_a_ray() {
+        let s = Shape::volume(Shape::sphere(), Medium::new(1.0)).
+            transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let media = s.media(&r);
+
+        assert_eq!(1, media.len());
+        assert_eq!((3.0, 7.0), (media[0].0, media[0].1));
+        assert_eq!(Medium::new(1.0), *media[0].2);
+        assert!(s.intersects(&r).is_empty());
+    }
+
+    #[test]
+    fn media_starting_inside_a_volume() {
+        let g = Shape::group(vec![Shape::volume(Shape::cube(), Medium::new(1.0))]);
+        let r = Ray::new(Point::origin(), Vector::new(1.0, 0.0, 0.0));
+        let media = g.media(&r);
+
+        assert_eq!(1, media.len());
+        assert_eq!((0.0, 1.0), (media[0].0, media[0].1));
+        assert!(g.media(&Ray::new(Point::new(0.0, 5.0, 0.0), Vector::new(1.0, 0.0, 0.0))).is_empty());
+    }
+
+    #[test]
+    fn media_starting_inside_a_distance_field() {
+        let s = Shape::volume(Shape::distance_field(DistanceField::new(sdf::sphere(1.0))), Medium::new(1.0));
+        let r = Ray::new(Point::origin(), Vector::new(0.0, 0.0, 1.0));
+        let media = s.media(&r);
+
+        assert_eq!(1, media.len());
+        assert_eq!(0.0, media[0].0);
+        assert!((media[0].1 - 1.0).abs() < 0.0001);
+    }
+}
@@ -0,0 +1,321 @@
+// Just enough of a JSON parser to read glTF files. Errors are reported on
+// the line they were found on.
+
+use crate::ParseError;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// Arrays and objects are read recursively, so nesting is limited to keep
+// malformed files from overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    // Members in the order they appear.
+    Object(Vec<(String, Value)>)
+}
+
+impl Value {
+    // Looks up a member of an object. Anything else has no members.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser { chars: input.chars().peekable(), line: 1, depth: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+
+    match parser.next() {
+        None => Ok(value),
+        Some(c) => Err(parser.error(&format!("unexpected '{}' after the end of the JSON", c)))
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    // How many arrays and objects enclose the current value.
+    depth: usize
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError::new(self.line, message)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+
+        if c == Some('\n') {
+            self.line += 1;
+        }
+
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.chars.peek() {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(&format!("expected '{}' but found '{}'", expected, c))),
+            None => Err(self.error(&format!("expected '{}' but the input ended", expected)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        self.skip_whitespace();
+
+        match self.chars.peek() {
+            Some('{' | '[') if self.depth == MAX_DEPTH =>
+                Err(self.error(&format!("arrays and objects are nested more than {} deep", MAX_DEPTH))),
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            Some('n') => self.keyword("null", Value::Null),
+            Some('-' | '0'..='9') => self.number(),
+            Some(&c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("expected a value but the input ended"))
+        }
+    }
+
+    fn nested(&mut self, read: fn(&mut Self) -> Result<Value, ParseError>) -> Result<Value, ParseError> {
+        self.depth += 1;
+        let value = read(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn keyword(&mut self, word: &str, value: Value) -> Result<Value, ParseError> {
+        for c in word.chars() {
+            self.expect(c)?;
+        }
+
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let mut text = String::new();
+
+        while let Some(&c @ ('-' | '+' | '.' | 'e' | 'E' | '0'..='9')) = self.chars.peek() {
+            text.push(c);
+            self.next();
+        }
+
+        text.
+            parse::<f64>().
+            map(Value::Number).
+            map_err(|_| self.error(&format!("invalid number '{}'", text)))
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut s = String::new();
+
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => s.push(self.unicode_escape()?),
+                    Some(c) => return Err(self.error(&format!("invalid escape '\\{}'", c))),
+                    None => return Err(self.error("unterminated string"))
+                },
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string"))
+            }
+        }
+    }
+
+    // Characters outside the basic multilingual plane are escaped as a
+    // pair of UTF-16 surrogates.
+    fn unicode_escape(&mut self) -> Result<char, ParseError> {
+        let high = self.hex()?;
+
+        let code = if (0xD800..0xDC00).contains(&high) {
+            self.expect('\\')?;
+            self.expect('u')?;
+            let low = self.hex()?;
+
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error(&format!("invalid surrogate pair '\\u{:04x}\\u{:04x}'", high, low)));
+            }
+
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        std::char::from_u32(code).ok_or_else(|| self.error(&format!("invalid escape '\\u{:04x}'", code)))
+    }
+
+    fn hex(&mut self) -> Result<u32, ParseError> {
+        let digits: String = (0..4).filter_map(|_| self.next()).collect();
+
+        match u32::from_str_radix(&digits, 16) {
+            Ok(n) if digits.len() == 4 && digits.chars().all(|c| c.is_ascii_hexdigit()) => Ok(n),
+            _ => Err(self.error(&format!("invalid escape '\\u{}'", digits)))
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        self.expect('[')?;
+        let mut items = vec![];
+
+        if !self.at_end(']') {
+            loop {
+                items.push(self.value()?);
+
+                if !self.separator(']')? {
+                    break;
+                }
+            }
+        }
+
+        Ok(Value::Array(items))
+    }
+
+    fn object(&mut self) -> Result<Value, ParseError> {
+        self.expect('{')?;
+        let mut members = vec![];
+
+        if !self.at_end('}') {
+            loop {
+                self.skip_whitespace();
+                let key = self.string()?;
+                self.skip_whitespace();
+                self.expect(':')?;
+                members.push((key, self.value()?));
+
+                if !self.separator('}')? {
+                    break;
+                }
+            }
+        }
+
+        Ok(Value::Object(members))
+    }
+
+    // Reads the end of an empty array or object.
+    fn at_end(&mut self, close: char) -> bool {
+        self.skip_whitespace();
+
+        if self.chars.peek() == Some(&close) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Reads the comma before another item, returning false at the end of
+    // the array or object instead.
+    fn separator(&mut self, close: char) -> Result<bool, ParseError> {
+        self.skip_whitespace();
+
+        match self.next() {
+            Some(',') => Ok(true),
+            Some(c) if c == close => Ok(false),
+            Some(c) => Err(self.error(&format!("expected ',' or '{}' but found '{}'", close, c))),
+            None => Err(self.error(&format!("expected ',' or '{}' but the input ended", close)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_values() {
+        assert_eq!(Ok(Value::Null), parse(" null "));
+        assert_eq!(Ok(Value::Bool(true)), parse("true"));
+        assert_eq!(Ok(Value::Number(-1.5e3)), parse("-1.5e3"));
+        assert_eq!(Ok(Value::String("a\"b\\/\n\u{e9}\u{1F600}".to_string())), parse(r#""a\"b\\\/\n\u00e9\ud83d\ude00""#));
+        assert_eq!(Ok(Value::Array(vec![])), parse("[ ]"));
+        assert_eq!(Ok(Value::Object(vec![])), parse("{}"));
+    }
+
+    #[test]
+    fn parsing_nested_values() {
+        let value = parse("{\n  \"a\": [1, {\"b\": null}],\n  \"c\": \"d\"\n}").unwrap();
+
+        assert_eq!(Some(1.0), value.get("a").unwrap().as_array().unwrap()[0].as_f64());
+        assert_eq!(Some(&Value::Null), value.get("a").unwrap().as_array().unwrap()[1].get("b"));
+        assert_eq!(Some("d"), value.get("c").and_then(|c| c.as_str()));
+        assert_eq!(None, value.get("e"));
+        assert_eq!(None, Value::Number(1.0).get("a"));
+    }
+
+    #[test]
+    fn reporting_malformed_json() {
+        let examples = vec![
+            ("{\n\"a\": 1\n\"b\": 2}", 3, "expected ',' or '}' but found '\"'"),
+            ("[1, 2", 1, "expected ',' or ']' but the input ended"),
+            ("[\n1.2.3]", 2, "invalid number '1.2.3'"),
+            ("\"abc", 1, "unterminated string"),
+            ("\"\\x\"", 1, "invalid escape '\\x'"),
+            ("\"\\u12g4\"", 1, "invalid escape '\\u12g4'"),
+            ("nul", 1, "expected 'l' but the input ended"),
+            ("{} x", 1, "unexpected 'x' after the end of the JSON"),
+            ("{1: 2}", 1, "expected '\"' but found '1'")
+        ];
+
+        for (input, line, message) in examples {
+            assert_eq!(Err(ParseError::new(line, message)), parse(input));
+        }
+    }
+
+    #[test]
+    fn limiting_how_deeply_values_nest() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(Err(ParseError::new(1, "arrays and objects are nested more than 128 deep")), parse(&nested(MAX_DEPTH + 1)));
+        assert!(parse(&"[".repeat(200000)).is_err());
+    }
+}
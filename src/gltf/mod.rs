@@ -0,0 +1,685 @@
+// Reading glTF 2.0 scenes, either from a `.gltf` file, whose buffers are
+// kept in separate files or embedded as base64 data URIs, or from a binary
+// `.glb` file, which packs the JSON and a buffer together.
+//
+// Each node of the scene becomes a group transformed like the node,
+// holding its mesh and its children. Meshes are shared between the nodes
+// that use them as instances, with a group of triangles for each of their
+// primitives. The first perspective camera found becomes the scene's
+// camera, and point and spot lights from the KHR_lights_punctual extension
+// become point lights.
+//
+// glTF uses a right-handed coordinate system, whereas this ray tracer uses
+// a left-handed one, so the scene is mirrored in z as it is read. It looks
+// the same through the scene's camera as it does in other viewers.
+
+mod json;
+
+use self::json::Value;
+use crate::geom::Mesh;
+use crate::matrix;
+use crate::{Camera, Color, Material, Matrix4x4, ParseError, Point, PointLight, Shape, Vector};
+use std::path::{self, Path, PathBuf};
+use std::sync::Arc;
+
+const GLB_MAGIC: &[u8] = b"glTF";
+const JSON_CHUNK: u32 = 0x4E4F_534A;
+const BIN_CHUNK: u32 = 0x004E_4942;
+
+// Primitive modes, of which points and lines are skipped.
+const TRIANGLES: usize = 4;
+const TRIANGLE_STRIP: usize = 5;
+const TRIANGLE_FAN: usize = 6;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GltfScene {
+    pub objects: Vec<Shape>,
+    pub lights: Vec<PointLight>,
+    // The vertical field of view and the world to camera transform of the
+    // scene's camera.
+    camera: Option<(f64, Matrix4x4)>
+}
+
+impl GltfScene {
+    // The scene's camera, rendering an image of the given size. glTF gives
+    // the field of view from top to bottom, whereas `Camera` takes it
+    // across the longer side of the image.
+    pub fn camera(&self, hsize: usize, vsize: usize) -> Option<Camera> {
+        self.camera.map(|(yfov, transform)| {
+            let aspect = hsize as f64 / vsize as f64;
+            let field_of_view = if aspect > 1.0 {
+                2.0 * ((yfov / 2.0).tan() * aspect).atan()
+            } else {
+                yfov
+            };
+
+            Camera::new(hsize, vsize, field_of_view).transform(transform)
+        })
+    }
+}
+
+// Reads a `.gltf` or `.glb` file, along with any buffers it refers to by a
+// path relative to it.
+pub fn read_gltf(path: impl AsRef<Path>) -> Result<GltfScene, ParseError> {
+    let path = path.as_ref();
+    let input = read_file(path)?;
+    parse_gltf(&input, path.parent().unwrap_or_else(|| Path::new("")))
+}
+
+// Reads the contents of a `.gltf` or `.glb` file, loading any buffers kept
+// in separate files from `dir`. Errors in the JSON are reported on their
+// line, and any others on line 0.
+pub fn parse_gltf(input: &[u8], dir: &Path) -> Result<GltfScene, ParseError> {
+    let (text, binary) = if input.starts_with(GLB_MAGIC) {
+        parse_glb(input)?
+    } else {
+        (input, None)
+    };
+
+    let text = std::str::from_utf8(text).map_err(|_| error("the JSON is not valid UTF-8"))?;
+    let json = json::parse(text)?;
+    let buffers = load_buffers(&json, binary, dir)?;
+
+    Document { json, buffers }.scene()
+}
+
+fn error(message: &str) -> ParseError {
+    ParseError::new(0, message)
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, ParseError> {
+    std::fs::read(path).map_err(|e| error(&format!("couldn't read '{}': {}", path.display(), e)))
+}
+
+// A binary file has a 12 byte header followed by chunks, each starting
+// with its length and type. The first chunk holds the JSON and the second,
+// if there is one, the contents of the first buffer.
+fn parse_glb(input: &[u8]) -> Result<(&[u8], Option<&[u8]>), ParseError> {
+    let word = |i: usize| input.get(i..i + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+    if word(4) != Some(2) {
+        return Err(error("only version 2 binary files are supported"));
+    }
+
+    let mut chunks = vec![];
+    let mut offset = 12;
+
+    while offset < input.len() {
+        let (length, kind) = match (word(offset), word(offset + 4)) {
+            (Some(length), Some(kind)) => (length as usize, kind),
+            _ => return Err(error("the binary file ends in a chunk header"))
+        };
+
+        let data = input.
+            get(offset + 8..offset + 8 + length).
+            ok_or_else(|| error("the binary file ends in a chunk"))?;
+
+        chunks.push((kind, data));
+        offset += 8 + length;
+    }
+
+    match chunks[..] {
+        [(JSON_CHUNK, text), (BIN_CHUNK, binary), ..] => Ok((text, Some(binary))),
+        [(JSON_CHUNK, text), ..] => Ok((text, None)),
+        _ => Err(error("the binary file doesn't start with a JSON chunk"))
+    }
+}
+
+fn load_buffers(json: &Value, binary: Option<&[u8]>, dir: &Path) -> Result<Vec<Vec<u8>>, ParseError> {
+    array(json, "buffers")?.
+        iter().
+        enumerate().
+        map(|(i, buffer)| {
+            let data = match string(buffer, "uri")? {
+                None if i == 0 && binary.is_some() => binary.unwrap().to_vec(),
+                None => return Err(error(&format!("buffers[{}] has no uri", i))),
+                Some(uri) if uri.starts_with("data:") => decode_data_uri(uri).
+                    ok_or_else(|| error(&format!("buffers[{}] has an invalid data uri", i)))?,
+                Some(uri) => read_file(&buffer_path(dir, uri).
+                    ok_or_else(|| error(&format!("buffers[{}] has a uri outside the scene's directory", i)))?)?
+            };
+
+            let length = required(buffer, "byteLength", "buffers", i)?;
+
+            if data.len() < length {
+                return Err(error(&format!("buffers[{}] should be {} bytes but is {}", i, length, data.len())));
+            }
+
+            Ok(data)
+        }).
+        collect()
+}
+
+// Buffers in separate files are given by a relative, percent-encoded uri.
+// Only files in `dir` or below it can be read, so absolute paths and any
+// uri stepping up out of the directory are refused.
+fn buffer_path(dir: &Path, uri: &str) -> Option<PathBuf> {
+    let relative = PathBuf::from(percent_decode(uri)?);
+
+    if relative.components().all(|c| matches!(c, path::Component::Normal(_) | path::Component::CurDir)) {
+        Some(dir.join(relative))
+    } else {
+        None
+    }
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut rest = text.as_bytes();
+
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = tail.get(..2).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+// Only base64 data URIs, like "data:application/octet-stream;base64,...",
+// are supported.
+fn decode_data_uri(uri: &str) -> Option<Vec<u8>> {
+    let (header, data) = uri.split_at(uri.find(',')?);
+
+    if !header.ends_with(";base64") {
+        return None;
+    }
+
+    decode_base64(&data[1..])
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut bits = 0;
+    let mut count = 0;
+
+    for c in text.bytes().filter(|&c| c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None
+        };
+
+        bits = (bits << 6 | value as u32) & 0xFFFF;
+        count += 6;
+
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+
+    Some(bytes)
+}
+
+// Converts a transform from glTF's coordinate system to this one's and back
+// again, by mirroring it in z.
+fn mirror(m: Matrix4x4) -> Matrix4x4 {
+    let flip = matrix::scale(1.0, 1.0, -1.0);
+    flip * m * flip
+}
+
+// Approximates a metallic-roughness material with the Phong model. Metals
+// have no diffuse reflection and brighter highlights than other materials,
+// and the highlights of rougher surfaces are broader.
+fn material(material: &Value) -> Result<Material, ParseError> {
+    let pbr = material.get("pbrMetallicRoughness").unwrap_or(&Value::Null);
+    let base = numbers(pbr, "baseColorFactor", 4)?.unwrap_or_else(|| vec![1.0; 4]);
+    let metallic = number(pbr, "metallicFactor")?.unwrap_or(1.0).clamp(0.0, 1.0);
+    let roughness = number(pbr, "roughnessFactor")?.unwrap_or(1.0).clamp(0.0, 1.0);
+
+    // The Phong exponent whose highlight is about as wide as that of a
+    // microfacet distribution with this roughness.
+    let alpha = (roughness * roughness).max(0.01);
+    let shininess = (2.0 / (alpha * alpha) - 2.0).max(1.0);
+
+    Ok(Material::new().
+        color(Color::new(base[0], base[1], base[2])).
+        diffuse(0.9 * (1.0 - metallic)).
+        specular(0.04 + 0.86 * metallic).
+        shininess(shininess))
+}
+
+fn quaternion_rotation(q: &[f64]) -> Matrix4x4 {
+    let (x, y, z, w) = (q[0], q[1], q[2], q[3]);
+
+    Matrix4x4::from_elements([
+        [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w), 0.0],
+        [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w), 0.0],
+        [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y), 0.0],
+        [0.0, 0.0, 0.0, 1.0]
+    ])
+}
+
+// A node's transform is either a column-major matrix or a translation,
+// rotation and scale, applied in reverse order.
+fn local_transform(node: &Value) -> Result<Matrix4x4, ParseError> {
+    if let Some(m) = numbers(node, "matrix", 16)? {
+        let mut elements = [[0.0; 4]; 4];
+
+        for (i, row) in elements.iter_mut().enumerate() {
+            for (j, element) in row.iter_mut().enumerate() {
+                *element = m[j * 4 + i];
+            }
+        }
+
+        return Ok(Matrix4x4::from_elements(elements));
+    }
+
+    let t = numbers(node, "translation", 3)?.unwrap_or_else(|| vec![0.0; 3]);
+    let r = numbers(node, "rotation", 4)?.unwrap_or_else(|| vec![0.0, 0.0, 0.0, 1.0]);
+    let s = numbers(node, "scale", 3)?.unwrap_or_else(|| vec![1.0; 3]);
+
+    Ok(matrix::translate(t[0], t[1], t[2]) * quaternion_rotation(&r) * matrix::scale(s[0], s[1], s[2]))
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], ParseError> {
+    match value.get(key) {
+        None => Ok(&[]),
+        Some(v) => v.as_array().ok_or_else(|| error(&format!("'{}' should be an array", key)))
+    }
+}
+
+fn string<'a>(value: &'a Value, key: &str) -> Result<Option<&'a str>, ParseError> {
+    value.
+        get(key).
+        map(|v| v.as_str().ok_or_else(|| error(&format!("'{}' should be a string", key)))).
+        transpose()
+}
+
+fn number(value: &Value, key: &str) -> Result<Option<f64>, ParseError> {
+    value.
+        get(key).
+        map(|v| v.as_f64().ok_or_else(|| error(&format!("'{}' should be a number", key)))).
+        transpose()
+}
+
+fn numbers(value: &Value, key: &str, count: usize) -> Result<Option<Vec<f64>>, ParseError> {
+    let invalid = || error(&format!("'{}' should be {} numbers", key, count));
+
+    match value.get(key) {
+        None => Ok(None),
+        Some(v) => {
+            let numbers = v.
+                as_array().
+                ok_or_else(invalid)?.
+                iter().
+                map(|n| n.as_f64().ok_or_else(invalid)).
+                collect::<Result<Vec<f64>, _>>()?;
+
+            if numbers.len() == count { Ok(Some(numbers)) } else { Err(invalid()) }
+        }
+    }
+}
+
+// Indices, counts and offsets are all whole numbers.
+fn integer(value: &Value, key: &str) -> Result<Option<usize>, ParseError> {
+    match number(value, key)? {
+        Some(n) if n < 0.0 || n.fract() != 0.0 => Err(error(&format!("'{}' should be a whole number", key))),
+        n => Ok(n.map(|n| n as usize))
+    }
+}
+
+fn integers(value: &Value, key: &str) -> Result<Vec<usize>, ParseError> {
+    array(value, key)?.
+        iter().
+        map(|n| match n.as_f64() {
+            Some(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+            _ => Err(error(&format!("'{}' should hold whole numbers", key)))
+        }).
+        collect()
+}
+
+fn required(value: &Value, key: &str, collection: &str, index: usize) -> Result<usize, ParseError> {
+    integer(value, key)?.ok_or_else(|| error(&format!("{}[{}] has no '{}'", collection, index, key)))
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Component {
+    I8,
+    U8,
+    I16,
+    U16,
+    U32,
+    F32
+}
+
+impl Component {
+    fn parse(code: usize) -> Option<Component> {
+        match code {
+            5120 => Some(Self::I8),
+            5121 => Some(Self::U8),
+            5122 => Some(Self::I16),
+            5123 => Some(Self::U16),
+            5125 => Some(Self::U32),
+            5126 => Some(Self::F32),
+            _ => None
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::U32 | Self::F32 => 4
+        }
+    }
+
+    fn read(&self, bytes: &[u8]) -> f64 {
+        match self {
+            Self::I8 => bytes[0] as i8 as f64,
+            Self::U8 => bytes[0] as f64,
+            Self::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Self::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Self::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Self::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+        }
+    }
+
+    // Normalized integers run from 0, or -1 if they are signed, to 1 at
+    // the largest value of their type.
+    fn normalize(&self, value: f64) -> f64 {
+        match self {
+            Self::I8 => (value / 127.0).max(-1.0),
+            Self::U8 => value / 255.0,
+            Self::I16 => (value / 32767.0).max(-1.0),
+            Self::U16 => value / 65535.0,
+            Self::U32 => value / 4294967295.0,
+            Self::F32 => value
+        }
+    }
+}
+
+struct Document {
+    json: Value,
+    buffers: Vec<Vec<u8>>
+}
+
+impl Document {
+    // Looks up an item in one of the top level arrays, such as "meshes".
+    fn item(&self, collection: &str, index: usize) -> Result<&Value, ParseError> {
+        array(&self.json, collection)?.
+            get(index).
+            ok_or_else(|| error(&format!("{}[{}] doesn't exist", collection, index)))
+    }
+
+    fn scene(&self) -> Result<GltfScene, ParseError> {
+        let nodes = array(&self.json, "nodes")?;
+        let scenes = array(&self.json, "scenes")?;
+
+        // Without any scenes, every node that isn't a child of another is
+        // taken to be part of the scene.
+        let roots = if scenes.is_empty() {
+            let children = nodes.
+                iter().
+                map(|node| integers(node, "children")).
+                collect::<Result<Vec<Vec<usize>>, _>>()?.
+                concat();
+
+            (0..nodes.len()).filter(|i| !children.contains(i)).collect()
+        } else {
+            let index = integer(&self.json, "scene")?.unwrap_or(0);
+            integers(self.item("scenes", index)?, "nodes")?
+        };
+
+        let meshes = array(&self.json, "meshes")?.
+            iter().
+            map(|mesh| self.mesh(mesh).map(Arc::new)).
+            collect::<Result<Vec<Arc<Shape>>, _>>()?;
+
+        let mut scene = GltfScene { objects: vec![], lights: vec![], camera: None };
+        let mut visited = vec![false; nodes.len()];
+
+        for root in roots {
+            if let Some(shape) = self.node(root, Matrix4x4::identity(), &meshes, &mut visited, &mut scene)? {
+                scene.objects.push(shape);
+            }
+        }
+
+        Ok(scene)
+    }
+
+    // Builds the shape for a node and everything below it, in the space of
+    // its parent, whose transform from the scene is `parent`. Nodes without
+    // a mesh anywhere below them have no shape. Cameras and lights are
+    // added to the scene as they are found.
+    fn node(&self, index: usize, parent: Matrix4x4, meshes: &[Arc<Shape>], visited: &mut [bool], scene: &mut GltfScene) -> Result<Option<Shape>, ParseError> {
+        let node = self.item("nodes", index)?;
+
+        if std::mem::replace(&mut visited[index], true) {
+            return Err(error(&format!("nodes[{}] appears more than once in the scene", index)));
+        }
+
+        let local = mirror(local_transform(node)?);
+        let world = parent * local;
+        let singular = || error(&format!("nodes[{}] has a transform that can't be inverted", index));
+
+        if let Some(camera) = integer(node, "camera")? {
+            let camera = self.item("cameras", camera)?;
+
+            // glTF cameras look down -z, with x to the right, whereas this
+            // ray tracer's look down -z with x to the left. After the
+            // mirroring, the camera looks down +z, so it is turned around.
+            if let (None, Some(perspective)) = (scene.camera, camera.get("perspective")) {
+                let yfov = number(perspective, "yfov")?.ok_or_else(|| error("a perspective camera has no 'yfov'"))?;
+                let transform = matrix::scale(-1.0, 1.0, -1.0) * world.inverse().ok_or_else(singular)?;
+                scene.camera = Some((yfov, transform));
+            }
+        }
+
+        let extension = node.
+            get("extensions").
+            and_then(|e| e.get("KHR_lights_punctual")).
+            unwrap_or(&Value::Null);
+
+        if let Some(light) = integer(extension, "light")? {
+            if let Some(light) = self.light(light, world * Point::origin())? {
+                scene.lights.push(light);
+            }
+        }
+
+        let mut children = vec![];
+
+        if let Some(mesh) = integer(node, "mesh")? {
+            let mesh = meshes.get(mesh).ok_or_else(|| error(&format!("meshes[{}] doesn't exist", mesh)))?;
+            children.push(Shape::instance(mesh.clone()));
+        }
+
+        for child in integers(node, "children")? {
+            if let Some(shape) = self.node(child, world, meshes, visited, scene)? {
+                children.push(shape);
+            }
+        }
+
+        if children.is_empty() {
+            return Ok(None);
+        }
+
+        local.inverse().ok_or_else(singular)?;
+        Ok(Some(Shape::group(children).transform(local)))
+    }
+
+    // Point and spot lights become point lights, with the light's color
+    // scaled by its intensity. Directional lights are left out.
+    fn light(&self, index: usize, position: Point) -> Result<Option<PointLight>, ParseError> {
+        let light = self.
+            json.
+            get("extensions").
+            and_then(|e| e.get("KHR_lights_punctual")).
+            and_then(|e| e.get("lights")).
+            and_then(|lights| lights.as_array()).
+            and_then(|lights| lights.get(index)).
+            ok_or_else(|| error(&format!("lights[{}] doesn't exist", index)))?;
+
+        match string(light, "type")? {
+            Some("point") | Some("spot") => {
+                let color = numbers(light, "color", 3)?.unwrap_or_else(|| vec![1.0; 3]);
+                let intensity = number(light, "intensity")?.unwrap_or(1.0);
+                let color = Color::new(color[0], color[1], color[2]) * intensity;
+                Ok(Some(PointLight::new(position, color)))
+            },
+            _ => Ok(None)
+        }
+    }
+
+    fn mesh(&self, mesh: &Value) -> Result<Shape, ParseError> {
+        let mut primitives = vec![];
+
+        for primitive in array(mesh, "primitives")? {
+            if let Some(shape) = self.primitive(primitive)? {
+                primitives.push(shape);
+            }
+        }
+
+        Ok(Shape::group(primitives))
+    }
+
+    // Primitives without a material use the default `Material`, rather
+    // than glTF's default, which is a fully metallic one.
+    fn primitive(&self, primitive: &Value) -> Result<Option<Shape>, ParseError> {
+        let mode = integer(primitive, "mode")?.unwrap_or(TRIANGLES);
+
+        if mode < TRIANGLES {
+            return Ok(None);
+        }
+
+        let attributes = primitive.get("attributes").unwrap_or(&Value::Null);
+        let position = integer(attributes, "POSITION")?.ok_or_else(|| error("a primitive has no POSITION attribute"))?;
+
+        let vertices: Vec<Point> = self.
+            accessor(position, 3)?.
+            iter().
+            map(|v| Point::new(v[0], v[1], -v[2])).
+            collect();
+
+        let normals = match integer(attributes, "NORMAL")? {
+            Some(normal) => self.
+                accessor(normal, 3)?.
+                iter().
+                map(|n| Vector::new(n[0], n[1], -n[2])).
+                collect(),
+            None => vec![]
+        };
+
+        let indices: Vec<usize> = match integer(primitive, "indices")? {
+            Some(indices) => self.accessor(indices, 1)?.iter().map(|i| i[0] as usize).collect(),
+            None => (0..vertices.len()).collect()
+        };
+
+        if let Some(i) = indices.iter().find(|&&i| i >= vertices.len()) {
+            return Err(error(&format!("a primitive refers to vertex {} of {}", i, vertices.len())));
+        }
+
+        // Mirroring the vertices reverses their winding, which leaves them
+        // wound the way `Shape::triangle` expects.
+        let triangles = match mode {
+            TRIANGLES => indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            TRIANGLE_STRIP => (2..indices.len()).
+                map(|i| if i % 2 == 0 {
+                    [indices[i - 2], indices[i - 1], indices[i]]
+                } else {
+                    [indices[i - 2], indices[i], indices[i - 1]]
+                }).
+                collect(),
+            TRIANGLE_FAN => (2..indices.len()).map(|i| [indices[0], indices[i - 1], indices[i]]).collect(),
+            _ => return Err(error(&format!("unknown primitive mode {}", mode)))
+        };
+
+        let material = match integer(primitive, "material")? {
+            Some(i) => material(self.item("materials", i)?)?,
+            None => Material::new()
+        };
+
+        Ok(Some(Mesh { vertices, normals, colors: vec![], triangles }.to_shape(&material)))
+    }
+
+    // Reads each element of an accessor, which should have `size`
+    // components.
+    fn accessor(&self, index: usize, size: usize) -> Result<Vec<Vec<f64>>, ParseError> {
+        let accessor = self.item("accessors", index)?;
+        let invalid = |message: &str| error(&format!("accessors[{}] {}", index, message));
+
+        let components = match string(accessor, "type")? {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            _ => return Err(invalid("has an unsupported type"))
+        };
+
+        if components != size {
+            return Err(invalid(&format!("should have {} components but has {}", size, components)));
+        }
+
+        if accessor.get("sparse").is_some() {
+            return Err(invalid("is sparse, which isn't supported"));
+        }
+
+        let component = required(accessor, "componentType", "accessors", index)?;
+        let component = Component::parse(component).ok_or_else(|| invalid("has an unknown component type"))?;
+        let normalized = accessor.get("normalized") == Some(&Value::Bool(true));
+        let count = required(accessor, "count", "accessors", index)?;
+
+        let view_index = integer(accessor, "bufferView")?.ok_or_else(|| invalid("has no buffer view"))?;
+        let view = self.item("bufferViews", view_index)?;
+        let buffer_index = required(view, "buffer", "bufferViews", view_index)?;
+        let buffer = self.
+            buffers.
+            get(buffer_index).
+            ok_or_else(|| error(&format!("buffers[{}] doesn't exist", buffer_index)))?;
+
+        // Offsets and counts come straight from the file, so the arithmetic
+        // is checked rather than trusted not to overflow.
+        let past_the_end = || invalid("runs past the end of its buffer view");
+        let view_start = integer(view, "byteOffset")?.unwrap_or(0);
+        let view_end = view_start.
+            checked_add(required(view, "byteLength", "bufferViews", view_index)?).
+            ok_or_else(past_the_end)?;
+        let start = view_start.
+            checked_add(integer(accessor, "byteOffset")?.unwrap_or(0)).
+            ok_or_else(past_the_end)?;
+        let element_size = component.size() * components;
+        let stride = integer(view, "byteStride")?.unwrap_or(element_size);
+
+        if stride < element_size {
+            return Err(invalid(&format!(
+                "has {} byte elements, which don't fit its buffer view's byteStride of {}",
+                element_size,
+                stride)));
+        }
+
+        if count > 0 {
+            let end = stride.
+                checked_mul(count - 1).
+                and_then(|n| n.checked_add(start)).
+                and_then(|n| n.checked_add(element_size));
+
+            match end {
+                Some(end) if end <= view_end.min(buffer.len()) => (),
+                _ => return Err(past_the_end())
+            }
+        }
+
+        Ok((0..count).
+            map(|i| {
+                (0..components).
+                    map(|c| {
+                        let value = component.read(&buffer[start + i * stride + c * component.size()..]);
+                        if normalized { component.normalize(value) } else { value }
+                    }).
+                    collect()
+            }).
+            collect())
+    }
+}
+
+#[cfg(test)]
+mod tests;
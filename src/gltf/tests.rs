@@ -0,0 +1,232 @@
+use super::*;
+use crate::Ray;
+use std::f64::consts::PI;
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let alphabet = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    bytes.
+        chunks(3).
+        flat_map(|chunk| {
+            let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+            let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+
+            (0..4).map(move |i| if i <= chunk.len() { alphabet[n >> (18 - 6 * i) & 63] as char } else { '=' })
+        }).
+        collect()
+}
+
+// A triangle facing +z, followed by its indices.
+fn triangle_buffer() -> Vec<u8> {
+    let mut data = vec![];
+
+    for f in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+        data.extend(f.to_le_bytes());
+    }
+
+    for i in [0u16, 1, 2] {
+        data.extend(i.to_le_bytes());
+    }
+
+    data
+}
+
+// A file with one node showing the triangle, with the node's transform and
+// the buffer's JSON filled in.
+fn triangle_gltf(node: &str, buffer: &str) -> String {
+    r#"{
+        "asset": {"version": "2.0"},
+        "scene": 0,
+        "scenes": [{"nodes": [0]}],
+        "nodes": [{"mesh": 0 NODE}],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1, "material": 0}]}],
+        "materials": [{"pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0, "roughnessFactor": 0.5}}],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 0, "byteOffset": 36, "componentType": 5123, "count": 3, "type": "SCALAR"}
+        ],
+        "bufferViews": [{"buffer": 0, "byteLength": 42}],
+        "buffers": [BUFFER]
+    }"#.
+        replace("NODE", node).
+        replace("BUFFER", buffer)
+}
+
+fn embedded_triangle(node: &str) -> String {
+    let uri = format!("data:application/octet-stream;base64,{}", encode_base64(&triangle_buffer()));
+    triangle_gltf(node, &format!(r#"{{"byteLength": 42, "uri": "{}"}}"#, uri))
+}
+
+fn parse(input: &str) -> Result<GltfScene, ParseError> {
+    parse_gltf(input.as_bytes(), Path::new(""))
+}
+
+// Checks that the scene holds the triangle, facing the -z axis once it is
+// mirrored, moved to z = -1.
+fn assert_triangle(scene: &GltfScene) {
+    assert_eq!(1, scene.objects.len());
+
+    let r = Ray::new(Point::new(0.25, 0.25, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = scene.objects[0].intersects(&r);
+
+    assert_eq!(1, xs.len());
+    assert_eq!(4.0, xs[0].t);
+    assert_eq!(Vector::new(0.0, 0.0, -1.0), xs[0].normal());
+}
+
+#[test]
+fn decoding_base64() {
+    assert_eq!(Some(b"Hello".to_vec()), decode_base64("SGVsbG8="));
+    assert_eq!(Some(b"Hello".to_vec()), decode_base64("SGVsbG8"));
+    assert_eq!(Some(vec![0xfb, 0xff]), decode_base64("-_8="));
+    assert_eq!(None, decode_base64("SGV*"));
+    assert_eq!("SGVsbG8=", encode_base64(b"Hello"));
+}
+
+#[test]
+fn reading_a_triangle_from_a_data_uri() {
+    let scene = parse(&embedded_triangle(r#", "translation": [0, 0, 1]"#)).unwrap();
+
+    assert_triangle(&scene);
+    assert_eq!(mirror(matrix::translate(0.0, 0.0, 1.0)), scene.objects[0].transform);
+    assert!(scene.lights.is_empty());
+    assert_eq!(None, scene.camera(10, 10).map(|c| c.transform));
+}
+
+#[test]
+fn reading_a_binary_file() {
+    let mut text = triangle_gltf(r#", "matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1]"#, r#"{"byteLength": 42}"#).into_bytes();
+    text.resize(text.len().div_ceil(4) * 4, b' ');
+
+    let mut binary = triangle_buffer();
+    binary.resize(44, 0);
+
+    let mut glb = b"glTF".to_vec();
+    glb.extend(2u32.to_le_bytes());
+    glb.extend((12 + 8 + text.len() as u32 + 8 + binary.len() as u32).to_le_bytes());
+
+    for (kind, data) in [(JSON_CHUNK, text), (BIN_CHUNK, binary)] {
+        glb.extend((data.len() as u32).to_le_bytes());
+        glb.extend(kind.to_le_bytes());
+        glb.extend(data);
+    }
+
+    assert_triangle(&parse_gltf(&glb, Path::new("")).unwrap());
+}
+
+#[test]
+fn reading_buffers_from_separate_files() {
+    let dir = std::env::temp_dir().join(format!("ray_tracer_gltf_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("triangle.bin"), triangle_buffer()).unwrap();
+    std::fs::write(
+        dir.join("triangle.gltf"),
+        triangle_gltf(r#", "translation": [0, 0, 1]"#, r#"{"byteLength": 42, "uri": "triangle.bin"}"#)).unwrap();
+
+    let scene = read_gltf(dir.join("triangle.gltf"));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_triangle(&scene.unwrap());
+}
+
+#[test]
+fn decoding_buffer_uris() {
+    let dir = Path::new("scenes");
+
+    assert_eq!(Some(dir.join("my triangle.bin")), buffer_path(dir, "my%20triangle.bin"));
+    assert_eq!(Some(dir.join("data/triangle.bin")), buffer_path(dir, "./data/triangle.bin"));
+    assert_eq!(None, buffer_path(dir, "../triangle.bin"));
+    assert_eq!(None, buffer_path(dir, "data/%2e%2e/%2E%2E/triangle.bin"));
+    assert_eq!(None, buffer_path(dir, "/etc/passwd"));
+    assert_eq!(None, buffer_path(dir, "triangle%2"));
+    assert_eq!(None, buffer_path(dir, "triangle%+1.bin"));
+}
+
+#[test]
+fn approximating_metallic_roughness_materials() {
+    let scene = parse(&embedded_triangle("")).unwrap();
+    let r = Ray::new(Point::new(0.25, 0.25, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = scene.objects[0].intersects(&r);
+
+    assert_eq!(
+        Material::new().color(Color::new(1.0, 0.0, 0.0)).diffuse(0.9).specular(0.04).shininess(30.0),
+        xs[0].object.material);
+
+    let metal = json::parse(r#"{"pbrMetallicRoughness": {"roughnessFactor": 0}}"#).unwrap();
+    let metal = material(&metal).unwrap();
+    assert_eq!(0.0, metal.diffuse);
+    assert_eq!(0.9, metal.specular);
+    assert!(metal.shininess > 10000.0);
+}
+
+#[test]
+fn transforming_lights_through_the_node_hierarchy() {
+    let input = r#"{
+        "nodes": [
+            {"children": [1, 2], "rotation": [0, 0.7071067811865476, 0, 0.7071067811865476]},
+            {"translation": [1, 0, 0], "extensions": {"KHR_lights_punctual": {"light": 0}}},
+            {"extensions": {"KHR_lights_punctual": {"light": 1}}}
+        ],
+        "extensions": {"KHR_lights_punctual": {"lights": [
+            {"type": "point", "color": [1, 0.5, 0.5], "intensity": 2},
+            {"type": "directional"}
+        ]}}
+    }"#;
+    let scene = parse(input).unwrap();
+
+    // Turning x a quarter turn about y in glTF's right-handed space takes
+    // it to -z, which is +z once mirrored.
+    assert_eq!(vec![PointLight::new(Point::new(0.0, 0.0, 1.0), Color::new(2.0, 1.0, 1.0))], scene.lights);
+    assert!(scene.objects.is_empty());
+}
+
+#[test]
+fn reading_the_camera() {
+    let input = r#"{
+        "scenes": [{"nodes": [0]}],
+        "nodes": [{"camera": 0, "translation": [0, 0, 5]}],
+        "cameras": [{"type": "perspective", "perspective": {"yfov": 1.5707963267948966, "znear": 0.1}}]
+    }"#;
+    let scene = parse(input).unwrap();
+    let camera = scene.camera(101, 101).unwrap();
+
+    assert_eq!(PI / 2.0, camera.field_of_view);
+
+    let centre = camera.ray_for_pixel(50, 50);
+    assert_eq!(Point::new(0.0, 0.0, -5.0), centre.origin);
+    assert_eq!(Vector::new(0.0, 0.0, 1.0), centre.direction);
+    assert!(camera.ray_for_pixel(0, 50).direction.x < 0.0);
+    assert!(camera.ray_for_pixel(50, 0).direction.y > 0.0);
+
+    let wide = scene.camera(200, 100).unwrap();
+    assert_eq!(2.0 * 2.0_f64.atan(), wide.field_of_view);
+}
+
+#[test]
+fn reporting_malformed_files() {
+    let triangle = embedded_triangle("");
+    let short_uri = format!("base64,{}", encode_base64(&triangle_buffer()[..36]));
+    let examples = vec![
+        ("{\n\"a\" 1}".to_string(), 2, "expected ':' but found '1'"),
+        (triangle.replace(r#""POSITION": 0"#, r#""POSITION": 5"#), 0, "accessors[5] doesn't exist"),
+        (triangle.replace(r#""count": 3, "type": "VEC3""#, r#""count": 4, "type": "VEC3""#), 0, "accessors[0] runs past the end of its buffer view"),
+        (triangle.replace(r#""count": 3, "type": "VEC3""#, r#""count": 1e19, "type": "VEC3""#), 0, "accessors[0] runs past the end of its buffer view"),
+        (triangle.replace(r#""byteOffset": 36"#, r#""byteOffset": 18446744073709551615"#), 0, "accessors[1] runs past the end of its buffer view"),
+        (triangle.replace(r#""byteLength": 42}]"#, r#""byteLength": 42, "byteStride": 4}]"#), 0, "accessors[0] has 12 byte elements, which don't fit its buffer view's byteStride of 4"),
+        (triangle.replace(r#""type": "VEC3""#, r#""type": "VEC2""#), 0, "accessors[0] should have 3 components but has 2"),
+        (triangle.replacen(r#""indices": 1"#, r#""indices": 0.5"#, 1), 0, "'indices' should be a whole number"),
+        (triangle.replacen("base64,", "base64,!", 1), 0, "buffers[0] has an invalid data uri"),
+        (triangle.replacen(&triangle[triangle.find("base64,").unwrap()..triangle.rfind('"').unwrap()], &short_uri, 1), 0, "buffers[0] should be 42 bytes but is 36"),
+        (triangle_gltf("", r#"{"byteLength": 42, "uri": "../triangle.bin"}"#), 0, "buffers[0] has a uri outside the scene's directory"),
+        ("[".repeat(200000), 1, "arrays and objects are nested more than 128 deep"),
+        (r#"{"scenes": [{"nodes": [0]}], "nodes": [{"children": [0]}]}"#.to_string(), 0, "nodes[0] appears more than once in the scene")
+    ];
+
+    for (input, line, message) in examples {
+        assert_eq!(Err(ParseError::new(line, message)), parse(&input));
+    }
+
+    let mut glb = b"glTF".to_vec();
+    glb.extend(1u32.to_le_bytes());
+    assert_eq!(Err(ParseError::new(0, "only version 2 binary files are supported")), parse_gltf(&glb, Path::new("")));
+}
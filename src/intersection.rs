@@ -1,15 +1,41 @@
-use crate::{Color, PointLight, Point, Ray, Shape, Vector};
+use crate::{Color, Material, Matrix4x4, PointLight, Point, Ray, Shape, Vector};
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Intersection<'a> {
     pub ray: &'a Ray,
     pub t: f64,
-    pub object: &'a Shape
+    pub object: &'a Shape,
+    pub u: f64,
+    pub v: f64,
+    // The material to shade with, which an instance may override.
+    pub material: &'a Material,
+    // Takes world space into the object space of `object` at the time of
+    // the ray, which may differ from its own record of its transforms when
+    // it moves or was reached through an instance.
+    pub world_to_object: Matrix4x4
 }
 
-impl Intersection<'_> {
+impl<'a> Intersection<'a> {
+    pub fn new(ray: &'a Ray, t: f64, object: &'a Shape) -> Intersection<'a> {
+        Intersection {
+            ray,
+            t,
+            object,
+            u: 0.0,
+            v: 0.0,
+            material: &object.material,
+            world_to_object: object.world_inverse()
+        }
+    }
+
+    // Records where on the surface the ray hit, for shapes such as
+    // triangles whose normal varies across their surface.
+    pub fn with_uv(self, u: f64, v: f64) -> Intersection<'a> {
+        Intersection { u, v, ..self }
+    }
+
     pub fn lighting(&self, light: PointLight, in_shadow: bool) -> Color {
-        self.object.material.light_intersection(light, self, in_shadow)
+        self.material.light_intersection(light, self, in_shadow)
     }
 
     pub fn point(&self) -> Point {
@@ -17,7 +43,7 @@ impl Intersection<'_> {
     }
 
     pub fn object_point(&self) -> Point {
-        self.object.inverse_transform * self.point()
+        self.world_to_object * self.point()
     }
 
     pub fn over_point(&self) -> Point {
@@ -43,7 +69,8 @@ impl Intersection<'_> {
     }
 
     fn raw_normal(&self) -> Vector {
-        self.object.normal_at(self.point())
+        let normal = self.object.local_normal_at(self.object_point(), self.u, self.v);
+        (self.world_to_object.transpose() * normal).normalize()
     }
 }
 
@@ -72,13 +99,14 @@ pub fn hit<'a>(intersections: &'a [Intersection]) -> Option<Intersection<'a>> {
 mod tests {
     use crate::{Ray, Point, Vector, Matrix4x4};
     use super::*;
+    use std::sync::Arc;
 
     #[test]
     fn hit_when_all_intersections_are_positive() {
         let ray = Ray::new(Point::origin(), Vector::new(1.0, 0.0, 0.0));
         let s = Shape::sphere();
-        let i1 = Intersection{ray: &ray, t: 1.0, object: &s};
-        let i2 = Intersection{ray: &ray, t: 2.0, object: &s};
+        let i1 = Intersection::new(&ray, 1.0, &s);
+        let i2 = Intersection::new(&ray, 2.0, &s);
         let is = vec![i1, i2];
         let hit = hit(&is).unwrap();
 
@@ -89,8 +117,8 @@ mod tests {
     fn hit_when_some_intersections_are_negative() {
         let ray = Ray::new(Point::origin(), Vector::new(1.0, 0.0, 0.0));
         let s = Shape::sphere();
-        let i1 = Intersection{ray: &ray, t: -1.0, object: &s};
-        let i2 = Intersection{ray: &ray, t: 1.0, object: &s};
+        let i1 = Intersection::new(&ray, -1.0, &s);
+        let i2 = Intersection::new(&ray, 1.0, &s);
         let is = vec![i1, i2];
         let hit = hit(&is).unwrap();
 
@@ -101,8 +129,8 @@ mod tests {
     fn hit_when_all_intersections_are_negative() {
         let ray = Ray::new(Point::origin(), Vector::new(1.0, 0.0, 0.0));
         let s = Shape::sphere();
-        let i1 = Intersection{ray: &ray, t: -1.0, object: &s};
-        let i2 = Intersection{ray: &ray, t: -2.0, object: &s};
+        let i1 = Intersection::new(&ray, -1.0, &s);
+        let i2 = Intersection::new(&ray, -2.0, &s);
         let is = vec![i1, i2];
         let hit = hit(&is);
 
@@ -113,10 +141,10 @@ mod tests {
     fn the_hit_is_always_the_lowest_non_negative_intersection() {
         let ray = Ray::new(Point::origin(), Vector::new(1.0, 0.0, 0.0));
         let s = Shape::sphere();
-        let i1 = Intersection{ray: &ray, t: 5.0, object: &s};
-        let i2 = Intersection{ray: &ray, t: 7.0, object: &s};
-        let i3 = Intersection{ray: &ray, t: -3.0, object: &s};
-        let i4 = Intersection{ray: &ray, t: 2.0, object: &s};
+        let i1 = Intersection::new(&ray, 5.0, &s);
+        let i2 = Intersection::new(&ray, 7.0, &s);
+        let i3 = Intersection::new(&ray, -3.0, &s);
+        let i4 = Intersection::new(&ray, 2.0, &s);
         let is = vec![i1, i2, i3, i4];
         let hit = hit(&is).unwrap();
 
@@ -124,10 +152,11 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn computing_state_of_an_intersection() {
         let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let shape = Shape::sphere();
-        let i = Intersection{ray: &ray, t: 4.0, object: &shape};
+        let i = Intersection::new(&ray, 4.0, &shape);
         assert_eq!(Point::new(0.0, 0.0, -1.0), i.point());
         assert_eq!(Vector::new(0.0, 0.0, -1.0), i.eyev());
         assert_eq!(Vector::new(0.0, 0.0, -1.0), i.normal());
@@ -135,10 +164,11 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn computing_state_of_an_intersection_in_inside() {
         let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
         let shape = Shape::sphere();
-        let i = Intersection{ray: &ray, t: 1.0, object: &shape};
+        let i = Intersection::new(&ray, 1.0, &shape);
         assert_eq!(true, i.is_inside());
         assert_eq!(Point::new(0.0, 0.0, 1.0), i.point());
         assert_eq!(Vector::new(0.0, 0.0, -1.0), i.eyev());
@@ -149,9 +179,51 @@ mod tests {
     fn the_hit_should_offset_the_point() {
         let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let shape = Shape::sphere().transform(Matrix4x4::identity().translate(0.0, 0.0, 1.0));
-        let i = Intersection{ray: &ray, t: 5.0, object: &shape};
+        let i = Intersection::new(&ray, 5.0, &shape);
         let over_point = i.over_point();
         assert!(over_point.z < -0.00005);
         assert!(over_point.z < i.point().z);
     }
+
+    #[test]
+    fn object_point_of_a_child_in_a_transformed_group() {
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let group = Shape::group(vec![
+            Shape::sphere().transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0))
+        ]).transform(Matrix4x4::identity().translate(0.0, 0.0, 1.0));
+        let xs = group.intersects(&ray);
+
+        assert_eq!(2, xs.len());
+        assert_eq!(Point::new(0.0, 0.0, -1.0), xs[0].object_point());
+        assert_eq!(Vector::new(0.0, 0.0, -1.0), xs[0].normal());
+    }
+
+    #[test]
+    fn object_point_and_normal_of_an_instanced_object() {
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let geometry = Arc::new(Shape::group(vec![
+            Shape::sphere().transform(Matrix4x4::identity().scale(2.0, 2.0, 2.0))
+        ]));
+        let instance = Shape::instance(geometry).transform(Matrix4x4::identity().translate(0.0, 1.0, 1.0));
+        let xs = instance.intersects(&ray);
+
+        assert_eq!(2, xs.len());
+        assert!((xs[0].t - (6.0 - 3.0_f64.sqrt())).abs() < 0.0001);
+        assert_eq!(Point::new(0.0, -0.5, -0.866025), xs[0].object_point());
+        assert_eq!(Vector::new(0.0, -0.5, -0.866025), xs[0].normal());
+    }
+
+    #[test]
+    fn normal_of_a_smooth_triangle_is_interpolated_from_u_and_v() {
+        let ray = Ray::new(Point::new(-0.2, 0.3, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let shape = Shape::smooth_triangle(
+            Point::new(0.0, 1.0, 0.0),
+            Point::new(-1.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(-1.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0));
+        let i = Intersection::new(&ray, 2.0, &shape).with_uv(0.45, 0.25);
+        assert_eq!(Vector::new(-0.5547, 0.83205, 0.0), i.normal());
+    }
 }
@@ -9,6 +9,7 @@ pub use point::Point;
 pub use ray::Ray;
 pub use lighting::PointLight;
 pub use geom::Shape;
+pub use parse_error::ParseError;
 pub use vector::Vector;
 pub use world::World;
 
@@ -25,3 +26,9 @@ pub mod vector;
 pub mod world;
 pub mod camera;
 pub mod pattern;
+pub mod polynomial;
+pub mod parse_error;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+pub mod gltf;
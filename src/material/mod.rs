@@ -10,6 +10,7 @@ pub struct Material {
 }
 
 impl Material {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Material {
         Material {
             pattern: Pattern::solid(Color::white()),
@@ -23,6 +23,7 @@ struct Matrix3x3 {
 }
 
 impl Matrix3x3 {
+    #[allow(clippy::redundant_field_names)]
     fn from_elements(elements: [[f64; 3]; 3]) -> Matrix3x3 {
         Matrix3x3 {
             elements: elements
@@ -48,6 +49,7 @@ impl Matrix3x3 {
         }
     }
 
+    #[allow(clippy::needless_range_loop)]
     fn determinant(&self) -> f64 {
         let mut d = 0.0;
         let row = self.elements[0];
@@ -63,6 +65,7 @@ impl Matrix3x3 {
         self.submatrix(i, j).determinant()
     }
 
+    #[allow(clippy::needless_range_loop)]
     fn submatrix(&self, row: usize, col: usize) -> Matrix2x2 {
         let mut sub = [[0.0; 2]; 2];
 
@@ -94,6 +97,7 @@ impl Matrix4x4 {
         ])
     }
 
+    #[allow(clippy::redundant_field_names)]
     pub fn from_elements(elements: [[f64; 4]; 4]) -> Matrix4x4 {
         Matrix4x4 {
             elements: elements
@@ -160,6 +164,7 @@ impl Matrix4x4 {
         }
     }
 
+    #[allow(clippy::needless_range_loop)]
     pub fn determinant(&self) -> f64 {
         let mut d = 0.0;
         let row = self.elements[0];
@@ -171,6 +176,7 @@ impl Matrix4x4 {
         d
     }
 
+    #[allow(clippy::needless_range_loop)]
     pub fn inverse(&self) -> Option<Self> {
         let d = self.determinant();
 
@@ -193,6 +199,7 @@ impl Matrix4x4 {
         self.submatrix(i, j).determinant()
     }
 
+    #[allow(clippy::needless_range_loop)]
     fn submatrix(&self, row: usize, col: usize) -> Matrix3x3 {
         let mut sub = [[0.0; 3]; 3];
 
@@ -237,6 +244,7 @@ impl std::cmp::PartialEq<Matrix4x4> for Matrix4x4 {
 impl std::ops::Mul for Matrix4x4 {
     type Output = Matrix4x4;
 
+    #[allow(clippy::op_ref)]
     fn mul(self, m: Matrix4x4) -> Matrix4x4 {
         self * &m
     }
@@ -245,6 +253,7 @@ impl std::ops::Mul for Matrix4x4 {
 impl std::ops::Mul<&Matrix4x4> for Matrix4x4 {
     type Output = Matrix4x4;
 
+    #[allow(clippy::needless_range_loop)]
     fn mul(self, m: &Matrix4x4) -> Matrix4x4 {
         let mut result = [[0.0; 4]; 4];
 
@@ -0,0 +1,572 @@
+// Reading and writing Wavefront OBJ models.
+//
+// Vertices, normals and texture coordinates are collected as they appear,
+// and each face is split into a fan of triangles around its first vertex.
+// Faces whose vertices all have normals become smooth triangles. Faces
+// after a `g` or `o` statement go into a group of that name, and any before
+// the first one go into the default group. Statements this parser doesn't
+// understand, such as materials and smoothing groups, are counted and
+// skipped, as are colors given after a vertex's coordinates.
+//
+// Scenes are written out by tessellating each of their objects, for looking
+// at them in other modelling programs, along with an MTL library of their
+// materials. Coordinates are written as they are, so reading the file back
+// in gives the same triangles the ray tracer draws. Meshes colored by their
+// vertices have the colors written after each vertex's coordinates, as
+// many programs understand, with a white material.
+
+use crate::geom::Mesh;
+use crate::{Color, Material, ParseError, Point, Shape, Vector, World};
+use std::io::Write;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ObjFile {
+    pub vertices: Vec<Point>,
+    pub normals: Vec<Vector>,
+    pub texture_coordinates: Vec<(f64, f64)>,
+    pub default_group: Vec<Shape>,
+    // Named groups in the order they first appear. A name used again later
+    // in the file adds to the same group.
+    pub groups: Vec<(String, Vec<Shape>)>,
+    // The number of statements that were skipped.
+    pub ignored: usize
+}
+
+impl ObjFile {
+    pub fn group(&self, name: &str) -> Option<&[Shape]> {
+        self.groups.
+            iter().
+            find(|(group, _)| group == name).
+            map(|(_, triangles)| &triangles[..])
+    }
+
+    // Puts every triangle in a single group, with each named group as a
+    // group of its own inside it.
+    pub fn into_group(self) -> Shape {
+        let mut children = self.default_group;
+        children.extend(self.groups.into_iter().map(|(_, triangles)| Shape::group(triangles)));
+        Shape::group(children)
+    }
+
+    fn add_triangles(&mut self, group: &Option<String>, triangles: Vec<Shape>) {
+        let group = match group {
+            None => &mut self.default_group,
+            Some(name) => match self.groups.iter().position(|(group, _)| group == name) {
+                Some(i) => &mut self.groups[i].1,
+                None => {
+                    self.groups.push((name.clone(), vec![]));
+                    &mut self.groups.last_mut().unwrap().1
+                }
+            }
+        };
+
+        group.extend(triangles);
+    }
+}
+
+pub fn parse_obj(input: &str) -> Result<ObjFile, ParseError> {
+    let mut obj = ObjFile::default();
+    let mut group = None;
+
+    for (i, line) in input.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.split('#').next().unwrap();
+        let mut words = line.split_whitespace();
+
+        let keyword = match words.next() {
+            None => continue,
+            Some(keyword) => keyword
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        match keyword {
+            "v" => {
+                let count = if arguments.len() == 6 { 6 } else { 4 };
+                let v = parse_numbers(line_number, &arguments, 3, count)?;
+                obj.vertices.push(Point::new(v[0], v[1], v[2]));
+            },
+            "vn" => {
+                let n = parse_numbers(line_number, &arguments, 3, 3)?;
+                obj.normals.push(Vector::new(n[0], n[1], n[2]));
+            },
+            "vt" => {
+                let uv = parse_numbers(line_number, &arguments, 1, 3)?;
+                obj.texture_coordinates.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            },
+            "f" => {
+                let triangles = parse_face(line_number, &arguments, &obj)?;
+                obj.add_triangles(&group, triangles);
+            },
+            "g" | "o" => {
+                group = if arguments.is_empty() { None } else { Some(arguments.join(" ")) };
+            },
+            _ => obj.ignored += 1
+        }
+    }
+
+    Ok(obj)
+}
+
+fn parse_numbers(line_number: usize, arguments: &[&str], min: usize, max: usize) -> Result<Vec<f64>, ParseError> {
+    if arguments.len() < min || arguments.len() > max {
+        let expected = if min == max { format!("{}", min) } else { format!("{} to {}", min, max) };
+        return Err(ParseError::new(line_number, &format!("expected {} numbers but found {}", expected, arguments.len())));
+    }
+
+    arguments.
+        iter().
+        map(|s| s.parse::<f64>().map_err(|_| ParseError::new(line_number, &format!("invalid number '{}'", s)))).
+        collect()
+}
+
+// Looks up a one-based index, where negative indices count back from the
+// end of what has been read so far.
+fn resolve<T: Copy>(line_number: usize, index: &str, items: &[T], kind: &str) -> Result<T, ParseError> {
+    let invalid = || ParseError::new(line_number, &format!("no {} '{}'", kind, index));
+    let n = index.parse::<i64>().map_err(|_| invalid())?;
+
+    let i = match n {
+        n if n > 0 => n - 1,
+        n if n < 0 => items.len() as i64 + n,
+        _ => return Err(invalid())
+    };
+
+    if i < 0 {
+        return Err(invalid());
+    }
+
+    items.get(i as usize).copied().ok_or_else(invalid)
+}
+
+// Each face vertex is `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+fn parse_face(line_number: usize, arguments: &[&str], obj: &ObjFile) -> Result<Vec<Shape>, ParseError> {
+    if arguments.len() < 3 {
+        return Err(ParseError::new(line_number, "a face needs at least 3 vertices"));
+    }
+
+    let mut points = vec![];
+    let mut normals = vec![];
+
+    for argument in arguments {
+        let parts: Vec<&str> = argument.split('/').collect();
+
+        if parts.len() > 3 {
+            return Err(ParseError::new(line_number, &format!("invalid face vertex '{}'", argument)));
+        }
+
+        points.push(resolve(line_number, parts[0], &obj.vertices, "vertex")?);
+
+        if let Some(t) = parts.get(1).filter(|t| !t.is_empty()) {
+            resolve(line_number, t, &obj.texture_coordinates, "texture coordinate")?;
+        }
+
+        if let Some(n) = parts.get(2).filter(|n| !n.is_empty()) {
+            normals.push(resolve(line_number, n, &obj.normals, "normal")?);
+        }
+    }
+
+    let smooth = normals.len() == points.len();
+
+    Ok((1..points.len() - 1).
+        map(|i| {
+            if smooth {
+                Shape::smooth_triangle(points[0], points[i], points[i + 1], normals[0], normals[i], normals[i + 1])
+            } else {
+                Shape::triangle(points[0], points[i], points[i + 1])
+            }
+        }).
+        collect())
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjWriter {
+    // How finely curved surfaces are tessellated.
+    pub resolution: usize,
+    // The width of the square each plane is clipped to.
+    pub plane_size: f64,
+    // The name the MTL file is saved under, for the OBJ file to refer to.
+    pub material_library: String
+}
+
+impl Default for ObjWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ObjWriter {
+    pub fn new() -> ObjWriter {
+        ObjWriter {
+            resolution: 16,
+            plane_size: 100.0,
+            material_library: "scene.mtl".to_string()
+        }
+    }
+
+    pub fn resolution(self, resolution: usize) -> ObjWriter {
+        ObjWriter {
+            resolution,
+            ..self
+        }
+    }
+
+    pub fn plane_size(self, plane_size: f64) -> ObjWriter {
+        ObjWriter {
+            plane_size,
+            ..self
+        }
+    }
+
+    pub fn material_library(self, material_library: &str) -> ObjWriter {
+        ObjWriter {
+            material_library: material_library.to_string(),
+            ..self
+        }
+    }
+
+    // Writes each of the world's objects that can be tessellated to `obj`
+    // as an object of its own, and the materials they use to `mtl`. Parts
+    // that can't be tessellated, such as CSG and volumes, are left out.
+    pub fn write(&self, world: &World, obj: &mut dyn Write, mtl: &mut dyn Write) -> std::io::Result<()> {
+        let mut materials: Vec<String> = vec![];
+        let mut vertex_count = 0;
+        let mut normal_count = 0;
+
+        writeln!(obj, "mtllib {}", self.material_library)?;
+
+        for (i, object) in world.objects.iter().enumerate() {
+            // Neighbouring parts with the same material, like the triangles
+            // of a mesh, are written as one, as long as they agree on having
+            // normals and colors so that neither is lost.
+            let mut parts: Vec<(Mesh, String)> = vec![];
+
+            for (mesh, material) in object.tessellate_parts(self.resolution, self.plane_size) {
+                let description = describe(material);
+
+                match parts.last_mut() {
+                    Some((previous, d)) if *d == description &&
+                        previous.has_normals() == mesh.has_normals() &&
+                        previous.has_colors() == mesh.has_colors() => previous.append(mesh),
+                    _ => parts.push((mesh, description))
+                }
+            }
+
+            if parts.is_empty() {
+                continue;
+            }
+
+            writeln!(obj, "o object{}", i + 1)?;
+
+            for (mesh, description) in parts {
+                let material = match materials.iter().position(|m| *m == description) {
+                    Some(index) => index,
+                    None => {
+                        materials.push(description);
+                        materials.len() - 1
+                    }
+                };
+
+                writeln!(obj, "usemtl material{}", material + 1)?;
+
+                for (i, v) in mesh.vertices.iter().enumerate() {
+                    if mesh.has_colors() {
+                        let c = mesh.colors[i];
+                        writeln!(obj, "v {} {} {} {} {} {}", v.x, v.y, v.z, c.r, c.g, c.b)?;
+                    } else {
+                        writeln!(obj, "v {} {} {}", v.x, v.y, v.z)?;
+                    }
+                }
+
+                for n in &mesh.normals {
+                    writeln!(obj, "vn {} {} {}", n.x, n.y, n.z)?;
+                }
+
+                for triangle in &mesh.triangles {
+                    let corners: Vec<String> = triangle.
+                        iter().
+                        map(|i| if mesh.has_normals() {
+                            format!("{}//{}", vertex_count + i + 1, normal_count + i + 1)
+                        } else {
+                            format!("{}", vertex_count + i + 1)
+                        }).
+                        collect();
+
+                    writeln!(obj, "f {}", corners.join(" "))?;
+                }
+
+                vertex_count += mesh.vertices.len();
+                normal_count += mesh.normals.len();
+            }
+        }
+
+        for (i, description) in materials.iter().enumerate() {
+            writeln!(mtl, "newmtl material{}", i + 1)?;
+            write!(mtl, "{}", description)?;
+        }
+
+        Ok(())
+    }
+}
+
+// MTL has nothing like patterns, so materials are given the color of their
+// pattern at the origin. Vertex colors are written with the vertices
+// instead, leaving the material white.
+fn describe(material: &Material) -> String {
+    let color = if material.pattern.is_vertex_colors() {
+        Color::white()
+    } else {
+        material.pattern.color_at(Point::origin())
+    };
+    let rgb = |c: Color, k: f64| format!("{} {} {}", c.r * k, c.g * k, c.b * k);
+
+    format!(
+        "Ka {}\nKd {}\nKs {}\nNs {}\nillum 2\n",
+        rgb(color, material.ambient),
+        rgb(color, material.diffuse),
+        rgb(Color::white(), material.specular),
+        material.shininess.min(1000.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geom::CsgOperation;
+    use crate::{Matrix4x4, PointLight, Ray};
+
+    #[test]
+    fn ignoring_unrecognized_lines() {
+        let input = "There was a young lady named Bright\n\
+                     who traveled much faster than light.\n\
+                     \n\
+                     # She set out one day\n\
+                     mtllib in.mtl";
+        let obj = parse_obj(input).unwrap();
+
+        assert_eq!(3, obj.ignored);
+        assert!(obj.vertices.is_empty());
+    }
+
+    #[test]
+    fn vertex_records() {
+        let input = "v -1 1 0\n\
+                     v -1.0000 0.5000 0.0000\n\
+                     v 1 0 0  # a comment\n\
+                     v 1 1 0 1.0";
+        let obj = parse_obj(input).unwrap();
+
+        assert_eq!(vec![
+            Point::new(-1.0, 1.0, 0.0),
+            Point::new(-1.0, 0.5, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(1.0, 1.0, 0.0)
+        ], obj.vertices);
+    }
+
+    #[test]
+    fn normal_and_texture_coordinate_records() {
+        let input = "vn 0 0 1\n\
+                     vn 0.707 0 -0.707\n\
+                     vt 0.5\n\
+                     vt 0.25 0.75 0";
+        let obj = parse_obj(input).unwrap();
+
+        assert_eq!(vec![Vector::new(0.0, 0.0, 1.0), Vector::new(0.707, 0.0, -0.707)], obj.normals);
+        assert_eq!(vec![(0.5, 0.0), (0.25, 0.75)], obj.texture_coordinates);
+    }
+
+    #[test]
+    fn parsing_triangle_faces() {
+        let input = "v -1 1 0\n\
+                     v -1 0 0\n\
+                     v 1 0 0\n\
+                     v 1 1 0\n\
+                     \n\
+                     f 1 2 3\n\
+                     f 1 3 4";
+        let obj = parse_obj(input).unwrap();
+        let v = &obj.vertices;
+
+        assert_eq!(vec![
+            Shape::triangle(v[0], v[1], v[2]),
+            Shape::triangle(v[0], v[2], v[3])
+        ], obj.default_group);
+    }
+
+    #[test]
+    fn triangulating_polygons() {
+        let input = "v -1 1 0\n\
+                     v -1 0 0\n\
+                     v 1 0 0\n\
+                     v 1 1 0\n\
+                     v 0 2 0\n\
+                     \n\
+                     f 1 2 3 4 5";
+        let obj = parse_obj(input).unwrap();
+        let v = &obj.vertices;
+
+        assert_eq!(vec![
+            Shape::triangle(v[0], v[1], v[2]),
+            Shape::triangle(v[0], v[2], v[3]),
+            Shape::triangle(v[0], v[3], v[4])
+        ], obj.default_group);
+    }
+
+    #[test]
+    fn faces_with_normals_and_texture_coordinates() {
+        let input = "v 0 1 0\n\
+                     v -1 0 0\n\
+                     v 1 0 0\n\
+                     vt 0 0\n\
+                     vn -1 0 0\n\
+                     vn 1 0 0\n\
+                     vn 0 1 0\n\
+                     \n\
+                     f 1//3 2//1 3//2\n\
+                     f 1/1/3 2/1/1 3/1/2\n\
+                     f -3/-1 -2/-1 -1/-1";
+        let obj = parse_obj(input).unwrap();
+        let v = &obj.vertices;
+        let n = &obj.normals;
+        let smooth = Shape::smooth_triangle(v[0], v[1], v[2], n[2], n[0], n[1]);
+
+        assert_eq!(vec![smooth.clone(), smooth, Shape::triangle(v[0], v[1], v[2])], obj.default_group);
+    }
+
+    #[test]
+    fn triangles_in_groups() {
+        let input = "v -1 1 0\n\
+                     v -1 0 0\n\
+                     v 1 0 0\n\
+                     v 1 1 0\n\
+                     \n\
+                     f 1 2 3\n\
+                     g FirstGroup\n\
+                     f 1 2 3\n\
+                     g SecondGroup\n\
+                     f 1 3 4\n\
+                     o FirstGroup\n\
+                     f 1 3 4";
+        let obj = parse_obj(input).unwrap();
+        let v = &obj.vertices;
+
+        assert_eq!(1, obj.default_group.len());
+        assert_eq!(2, obj.group("FirstGroup").unwrap().len());
+        assert_eq!(Shape::triangle(v[0], v[2], v[3]), obj.group("SecondGroup").unwrap()[0]);
+        assert_eq!(None, obj.group("ThirdGroup"));
+
+        let g = obj.into_group();
+        assert_eq!(3, g.children().len());
+        assert_eq!(2, g.children()[1].children().len());
+    }
+
+    #[test]
+    fn reporting_malformed_lines() {
+        let examples = vec![
+            ("v 1 2", 1, "expected 3 to 4 numbers but found 2"),
+            ("v 1 2 3\nvn 1 x 3", 2, "invalid number 'x'"),
+            ("v 1 2 3\nv 1 2 4\nf 1 2", 3, "a face needs at least 3 vertices"),
+            ("v 1 2 3\nv 1 2 4\nv 1 3 3\n\nf 1 2 4", 5, "no vertex '4'"),
+            ("v 1 2 3\nv 1 2 4\nv 1 3 3\nf 1 2 0", 4, "no vertex '0'"),
+            ("v 1 2 3\nv 1 2 4\nv 1 3 3\nf 1 2 -4", 4, "no vertex '-4'"),
+            ("v 1 2 3\nv 1 2 4\nv 1 3 3\nf 1//1 2 3", 4, "no normal '1'"),
+            ("v 1 2 3\nv 1 2 4\nv 1 3 3\nf 1/2 2 3", 4, "no texture coordinate '2'"),
+            ("v 1 2 3\nv 1 2 4\nv 1 3 3\nf 1/1/1/1 2 3", 4, "invalid face vertex '1/1/1/1'")
+        ];
+
+        for (input, line, message) in examples {
+            assert_eq!(Err(ParseError::new(line, message)), parse_obj(input));
+        }
+    }
+
+    #[test]
+    fn writing_a_world() {
+        let world = World {
+            light: PointLight::new(Point::new(-10.0, 10.0, -10.0), Color::white()),
+            objects: vec![
+                Shape::sphere().material(Material::new().color(Color::new(1.0, 0.0, 0.0))),
+                Shape::csg(CsgOperation::Union, Shape::sphere(), Shape::cube()),
+                Shape::plane().transform(Matrix4x4::identity().translate(0.0, -1.0, 0.0)),
+                Shape::group(vec![Shape::cube(), Shape::cube()]).transform(Matrix4x4::identity().translate(0.0, 0.0, 3.0))
+            ]
+        };
+        let mut obj = vec![];
+        let mut mtl = vec![];
+        ObjWriter::new().resolution(2).plane_size(10.0).write(&world, &mut obj, &mut mtl).unwrap();
+
+        let obj = String::from_utf8(obj).unwrap();
+        let mtl = String::from_utf8(mtl).unwrap();
+
+        assert!(obj.starts_with("mtllib scene.mtl\no object1\nusemtl material1\nv "));
+        assert_eq!(2, mtl.matches("newmtl").count());
+        assert!(mtl.starts_with("newmtl material1\nKa 0.1 0 0\nKd 0.9 0 0\nKs 0.9 0.9 0.9\nNs 200\nillum 2\n"));
+
+        // Reading the file back gives the same surfaces.
+        let read = parse_obj(&obj).unwrap();
+        assert_eq!(None, read.group("object2"));
+        assert_eq!(2, read.group("object3").unwrap().len());
+        assert_eq!(2 * 6 * 2 * 2 * 2, read.group("object4").unwrap().len());
+
+        let r = Ray::new(Point::new(0.3, 0.6, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let cubes = Shape::group(read.group("object4").unwrap().to_vec());
+        assert_eq!(vec![7.0, 7.0, 9.0, 9.0], cubes.intersects(&r).iter().map(|i| i.t).collect::<Vec<f64>>());
+
+        let down = Ray::new(Point::new(4.0, 0.0, 4.0), Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(1.0, Shape::group(read.group("object3").unwrap().to_vec()).intersects(&down)[0].t);
+    }
+
+    #[test]
+    fn writing_vertex_colors() {
+        let mesh = Mesh {
+            vertices: vec![Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0), Point::new(0.0, 0.0, 1.0), Point::new(1.0, 0.0, 1.0)],
+            normals: vec![],
+            colors: vec![Color::new(1.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0), Color::new(0.0, 0.0, 1.0), Color::white()],
+            triangles: vec![[0, 2, 1], [1, 2, 3]]
+        };
+        let world = World {
+            light: PointLight::new(Point::new(-10.0, 10.0, -10.0), Color::white()),
+            objects: vec![mesh.to_shape(&Material::new())]
+        };
+        let mut obj = vec![];
+        let mut mtl = vec![];
+        ObjWriter::new().write(&world, &mut obj, &mut mtl).unwrap();
+
+        let obj = String::from_utf8(obj).unwrap();
+        let mtl = String::from_utf8(mtl).unwrap();
+
+        assert_eq!(1, mtl.matches("newmtl").count());
+        assert!(mtl.contains("Kd 0.9 0.9 0.9\n"));
+        assert_eq!(1, obj.matches("usemtl").count());
+        assert!(obj.contains("v 0 0 0 1 0 0\n"));
+        assert!(obj.contains("v 1 0 1 1 1 1\n"));
+
+        let read = parse_obj(&obj).unwrap();
+        assert_eq!(2, read.group("object1").unwrap().len());
+        assert_eq!(Err(ParseError::new(1, "expected 3 to 4 numbers but found 5")), parse_obj("v 1 2 3 4 5"));
+    }
+
+    #[test]
+    fn writing_smooth_and_flat_parts_with_the_same_material() {
+        let (p1, p2, p3) = (Point::new(0.0, 1.0, 0.0), Point::new(-1.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0));
+        let (n1, n2, n3) = (Vector::new(0.0, 1.0, 0.0), Vector::new(-1.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let world = World {
+            light: PointLight::new(Point::new(-10.0, 10.0, -10.0), Color::white()),
+            objects: vec![Shape::group(vec![
+                Shape::smooth_triangle(p1, p2, p3, n1, n2, n3),
+                Shape::triangle(p1, p2, p3)
+            ])]
+        };
+        let mut obj = vec![];
+        let mut mtl = vec![];
+        ObjWriter::new().write(&world, &mut obj, &mut mtl).unwrap();
+
+        let obj = String::from_utf8(obj).unwrap();
+        let read = parse_obj(&obj).unwrap();
+
+        assert_eq!(2, obj.matches("usemtl material1").count());
+        assert_eq!(1, String::from_utf8(mtl).unwrap().matches("newmtl").count());
+        assert_eq!(3, read.normals.len());
+        assert_eq!(
+            vec![Shape::smooth_triangle(p1, p2, p3, n1, n2, n3), Shape::triangle(p1, p2, p3)],
+            read.group("object1").unwrap());
+    }
+}
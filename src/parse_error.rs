@@ -0,0 +1,26 @@
+// An error found while reading a model file, with the line it was found on.
+// Binary data has no lines, so errors in it, and errors that aren't tied to
+// any one line, are reported on line 0.
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String
+}
+
+impl ParseError {
+    pub fn new(line: usize, message: &str) -> ParseError {
+        ParseError {
+            line,
+            message: message.to_string()
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
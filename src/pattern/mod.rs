@@ -6,17 +6,20 @@ pub enum PatternType {
     Solid(Color),
     Stripe(Box<Pattern>, Box<Pattern>),
     LinearGradient(Color, Color),
-    Ring(Box<Pattern>, Box<Pattern>)
+    Ring(Box<Pattern>, Box<Pattern>),
+    VertexColors([Point; 3], [Color; 3])
 }
 
 impl PatternType {
+    #[allow(clippy::borrow_deref_ref)]
     pub fn color_at(&self, p: Point) -> Color {
         match self {
             Self::Checkers(p1, p2) => Self::checkers_color_at(&*p1, &*p2, p),
             Self::LinearGradient(c1, c2) => Self::linear_gradient_color_at(*c1, *c2, p),
             Self::Ring(p1, p2) => Self::ring_color_at(&*p1, &*p2, p),
             Self::Solid(c) => *c,
-            Self::Stripe(p1, p2) => Self::stripe_color_at(&*p1, &*p2, p)
+            Self::Stripe(p1, p2) => Self::stripe_color_at(&*p1, &*p2, p),
+            Self::VertexColors(points, colors) => Self::vertex_colors_color_at(points, colors, p)
         }
     }
 
@@ -45,6 +48,27 @@ impl PatternType {
         }
     }
 
+    // Blends the colors by the barycentric coordinates of the point's
+    // projection onto the plane of the triangle.
+    fn vertex_colors_color_at(points: &[Point; 3], colors: &[Color; 3], p: Point) -> Color {
+        let e1 = points[1] - points[0];
+        let e2 = points[2] - points[0];
+        let to_p = p - points[0];
+
+        let (d11, d12, d22) = (e1.dot(e1), e1.dot(e2), e2.dot(e2));
+        let (d1p, d2p) = (e1.dot(to_p), e2.dot(to_p));
+        let denominator = d11 * d22 - d12 * d12;
+
+        if denominator.abs() < 1e-12 {
+            return colors[0];
+        }
+
+        let u = (d22 * d1p - d12 * d2p) / denominator;
+        let v = (d11 * d2p - d12 * d1p) / denominator;
+
+        colors[0] * (1.0 - u - v) + colors[1] * u + colors[2] * v
+    }
+
     fn stripe_color_at(p1: &Pattern, p2: &Pattern, p: Point) -> Color {
         if p.x.floor() as i64 % 2 == 0 {
             p1.color_at(p)
@@ -86,6 +110,17 @@ impl Pattern {
         Self::new(PatternType::Stripe(Box::new(Self::solid(c1)), Box::new(Self::solid(c2))))
     }
 
+    // Colors a triangle by blending the colors given for its corners.
+    pub fn vertex_colors(points: [Point; 3], colors: [Color; 3]) -> Pattern {
+        Self::new(PatternType::VertexColors(points, colors))
+    }
+
+    // Whether the color comes from the corners of a triangle, rather than
+    // being the same for every triangle that shares the pattern.
+    pub fn is_vertex_colors(&self) -> bool {
+        matches!(self.pattern_type, PatternType::VertexColors(..))
+    }
+
     pub fn linear_gradient(c1: Color, c2: Color) -> Pattern {
         Self::new(PatternType::LinearGradient(c1, c2))
     }
@@ -101,6 +136,13 @@ impl Pattern {
     pub fn color_at(&self, p: Point) -> Color {
         self.pattern_type.color_at(self.inverse_transform * p)
     }
+
+    // The brightness of the pattern at a point, for uses that want a single
+    // value rather than a color, such as displacement.
+    pub fn value_at(&self, p: Point) -> f64 {
+        let c = self.color_at(p);
+        (c.r + c.g + c.b) / 3.0
+    }
 }
 
 #[cfg(test)]
@@ -89,3 +89,23 @@ fn checkers_pattern_test() {
     assert_eq!(Color::white(), p.color_at(Point::new(0.0, 0.0, 0.99)));
     assert_eq!(Color::black(), p.color_at(Point::new(0.0, 0.0, 1.01)));
 }
+
+#[test]
+fn value_of_a_pattern_is_its_brightness() {
+    let p = Pattern::stripe(Color::white(), Color::new(0.5, 0.25, 0.0));
+
+    assert_eq!(1.0, p.value_at(Point::new(0.0, 0.0, 0.0)));
+    assert_eq!(0.25, p.value_at(Point::new(1.0, 0.0, 0.0)));
+}
+
+#[test]
+fn vertex_colors_pattern_blends_across_a_triangle() {
+    let p = Pattern::vertex_colors(
+        [Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0), Point::new(0.0, 0.0, 1.0)],
+        [Color::new(1.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0), Color::new(0.0, 0.0, 1.0)]);
+
+    assert_eq!(Color::new(1.0, 0.0, 0.0), p.color_at(Point::new(0.0, 0.0, 0.0)));
+    assert_eq!(Color::new(0.0, 0.0, 1.0), p.color_at(Point::new(0.0, 0.0, 1.0)));
+    assert_eq!(Color::new(0.5, 0.25, 0.25), p.color_at(Point::new(0.25, 0.0, 0.25)));
+    assert_eq!(Color::new(0.5, 0.25, 0.25), p.color_at(Point::new(0.25, 3.0, 0.25)));
+}
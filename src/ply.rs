@@ -0,0 +1,503 @@
+// Reading PLY (Stanford polygon file format) models.
+//
+// A PLY file starts with a text header declaring a list of elements, each
+// with a count and a list of properties, followed by the data for every
+// element in order, either as text or as little-endian binary. Vertices
+// give the positions, and optionally the normals and colors, of a mesh,
+// and faces give lists of vertex indices, which are split into fans of
+// triangles. Any other elements are read past and ignored.
+
+use crate::geom::Mesh;
+use crate::{Color, ParseError, Point, Vector};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Type {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64
+}
+
+impl Type {
+    fn parse(line_number: usize, name: &str) -> Result<Type, ParseError> {
+        match name {
+            "char" | "int8" => Ok(Self::I8),
+            "uchar" | "uint8" => Ok(Self::U8),
+            "short" | "int16" => Ok(Self::I16),
+            "ushort" | "uint16" => Ok(Self::U16),
+            "int" | "int32" => Ok(Self::I32),
+            "uint" | "uint32" => Ok(Self::U32),
+            "float" | "float32" => Ok(Self::F32),
+            "double" | "float64" => Ok(Self::F64),
+            _ => Err(ParseError::new(line_number, &format!("unknown property type '{}'", name)))
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8
+        }
+    }
+
+    fn read(&self, bytes: &[u8]) -> f64 {
+        match self {
+            Self::I8 => bytes[0] as i8 as f64,
+            Self::U8 => bytes[0] as f64,
+            Self::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Self::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Self::I32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Self::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Self::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Self::F64 => {
+                let mut b = [0; 8];
+                b.copy_from_slice(&bytes[..8]);
+                f64::from_le_bytes(b)
+            }
+        }
+    }
+
+    // Colors stored as integers run from 0 to the largest value of their
+    // type, and those stored as floats from 0 to 1. Negative values of
+    // signed types are as dark as 0.
+    fn color(&self, value: f64) -> f64 {
+        let scale = match self {
+            Self::I8 => i8::MAX as f64,
+            Self::U8 => u8::MAX as f64,
+            Self::I16 => i16::MAX as f64,
+            Self::U16 => u16::MAX as f64,
+            Self::I32 => i32::MAX as f64,
+            Self::U32 => u32::MAX as f64,
+            Self::F32 | Self::F64 => 1.0
+        };
+
+        (value / scale).max(0.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Property {
+    Scalar(String, Type),
+    List(String, Type, Type)
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Self::Scalar(name, _) | Self::List(name, _, _) => name
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Element {
+    name: String,
+    // The header line that declared the element.
+    line: usize,
+    count: usize,
+    properties: Vec<Property>
+}
+
+impl Element {
+    fn position(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|p| p.name() == name)
+    }
+}
+
+// Where the element data is read from. Text data has a line for each
+// element; binary data has no lines, so errors in it are reported as being
+// on line 0.
+enum Body<'a> {
+    Ascii(Box<dyn Iterator<Item = (usize, &'a str)> + 'a>),
+    Binary(&'a [u8])
+}
+
+impl<'a> Body<'a> {
+    // Reads the values of each property of one element, returning them
+    // with the line they were found on.
+    fn read(&mut self, element: &Element, index: usize) -> Result<(usize, Vec<Vec<f64>>), ParseError> {
+        match self {
+            Self::Ascii(lines) => {
+                let (line_number, line) = lines.
+                    next().
+                    ok_or_else(|| ParseError::new(0, &format!("missing {} {}", element.name, index)))?;
+                let mut words = line.split_whitespace();
+                let mut next = || -> Result<f64, ParseError> {
+                    let word = words.
+                        next().
+                        ok_or_else(|| ParseError::new(line_number, &format!("too few values for {} {}", element.name, index)))?;
+                    word.parse::<f64>().map_err(|_| ParseError::new(line_number, &format!("invalid number '{}'", word)))
+                };
+
+                let mut values = vec![];
+
+                for property in &element.properties {
+                    match property {
+                        Property::Scalar(..) => values.push(vec![next()?]),
+                        Property::List(..) => {
+                            let count = next()? as usize;
+                            values.push((0..count).map(|_| next()).collect::<Result<_, _>>()?);
+                        }
+                    }
+                }
+
+                if words.next().is_some() {
+                    return Err(ParseError::new(line_number, &format!("too many values for {} {}", element.name, index)));
+                }
+
+                Ok((line_number, values))
+            },
+            Self::Binary(data) => {
+                let mut next = |t: Type| -> Result<f64, ParseError> {
+                    if data.len() < t.size() {
+                        return Err(ParseError::new(0, &format!("data ends in {} {}", element.name, index)));
+                    }
+
+                    let value = t.read(data);
+                    *data = &data[t.size()..];
+                    Ok(value)
+                };
+
+                let mut values = vec![];
+
+                for property in &element.properties {
+                    match property {
+                        Property::Scalar(_, t) => values.push(vec![next(*t)?]),
+                        Property::List(_, count_type, item_type) => {
+                            let count = next(*count_type)? as usize;
+                            values.push((0..count).map(|_| next(*item_type)).collect::<Result<_, _>>()?);
+                        }
+                    }
+                }
+
+                Ok((0, values))
+            }
+        }
+    }
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), ParseError> {
+    let mut lines = header.lines().enumerate().map(|(i, line)| (i + 1, line.trim()));
+
+    if lines.next().map(|(_, line)| line) != Some("ply") {
+        return Err(ParseError::new(1, "not a PLY file"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut last_line = 1;
+
+    for (line_number, line) in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        last_line = line_number;
+
+        match words[..] {
+            [] | ["comment", ..] | ["obj_info", ..] => (),
+            ["end_header"] => break,
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", name, _] =>
+                return Err(ParseError::new(line_number, &format!("unsupported format '{}'", name))),
+            ["element", name, count] => {
+                let count = count.
+                    parse::<usize>().
+                    map_err(|_| ParseError::new(line_number, &format!("invalid count '{}'", count)))?;
+                elements.push(Element { name: name.to_string(), line: line_number, count, properties: vec![] });
+            },
+            ["property", "list", count_type, item_type, name] => {
+                let property = Property::List(
+                    name.to_string(),
+                    Type::parse(line_number, count_type)?,
+                    Type::parse(line_number, item_type)?);
+
+                elements.
+                    last_mut().
+                    ok_or_else(|| ParseError::new(line_number, "property before any element"))?.
+                    properties.push(property);
+            },
+            ["property", t, name] => {
+                let property = Property::Scalar(name.to_string(), Type::parse(line_number, t)?);
+
+                elements.
+                    last_mut().
+                    ok_or_else(|| ParseError::new(line_number, "property before any element"))?.
+                    properties.push(property);
+            },
+            _ => return Err(ParseError::new(line_number, &format!("invalid header line '{}'", line)))
+        }
+    }
+
+    let format = format.ok_or_else(|| ParseError::new(last_line, "missing format"))?;
+    Ok((format, elements))
+}
+
+// The line that the text after `before` starts on.
+fn line_of(before: &[u8]) -> usize {
+    before.iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+fn find_end_of_header(input: &[u8]) -> Option<usize> {
+    let marker = b"end_header";
+
+    (0..input.len()).
+        find(|&i| input[i..].starts_with(marker) && (i == 0 || input[i - 1] == b'\n')).
+        map(|i| {
+            let end = i + marker.len();
+            match input[end..] {
+                [b'\r', b'\n', ..] => end + 2,
+                [b'\n', ..] => end + 1,
+                _ => end
+            }
+        })
+}
+
+// Reads a mesh from the vertex and face elements of a PLY file. Vertices
+// need x, y and z properties, and may also have nx, ny and nz for normals
+// and red, green and blue for colors. Faces need a list of vertex indices.
+pub fn parse_ply(input: &[u8]) -> Result<Mesh, ParseError> {
+    let body_start = find_end_of_header(input).
+        ok_or_else(|| ParseError::new(String::from_utf8_lossy(input).lines().count().max(1), "missing end_header"))?;
+    let header = std::str::from_utf8(&input[..body_start]).
+        map_err(|e| ParseError::new(line_of(&input[..e.valid_up_to()]), "header is not text"))?;
+    let header_lines = header.lines().count();
+    let (format, elements) = parse_header(header)?;
+
+    let mut body = match format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(&input[body_start..]).
+                map_err(|_| ParseError::new(header_lines + 1, "data is not text"))?;
+
+            Body::Ascii(Box::new(
+                text.
+                    lines().
+                    enumerate().
+                    map(move |(i, line)| (header_lines + i + 1, line)).
+                    filter(|(_, line)| !line.trim().is_empty())))
+        },
+        Format::BinaryLittleEndian => Body::Binary(&input[body_start..])
+    };
+
+    let mut mesh = Mesh::new();
+    let mut faces = vec![];
+
+    for element in &elements {
+        let position = |name: &str| element.position(name);
+
+        match element.name.as_str() {
+            "vertex" => {
+                let xyz = [position("x"), position("y"), position("z")];
+                let normal = [position("nx"), position("ny"), position("nz")];
+                let color = [position("red"), position("green"), position("blue")];
+
+                let xyz = match xyz {
+                    [Some(x), Some(y), Some(z)] => [x, y, z],
+                    _ => return Err(ParseError::new(element.line, "vertices need x, y and z properties"))
+                };
+
+                for index in 0..element.count {
+                    let (_, values) = body.read(element, index)?;
+                    let value = |i: usize| values[i].first().copied().unwrap_or(0.0);
+                    let channel = |i: usize| match &element.properties[i] {
+                        Property::Scalar(_, t) => t.color(value(i)),
+                        Property::List(..) => value(i)
+                    };
+
+                    mesh.vertices.push(Point::new(value(xyz[0]), value(xyz[1]), value(xyz[2])));
+
+                    if let [Some(x), Some(y), Some(z)] = normal {
+                        mesh.normals.push(Vector::new(value(x), value(y), value(z)));
+                    }
+
+                    if let [Some(r), Some(g), Some(b)] = color {
+                        mesh.colors.push(Color::new(channel(r), channel(g), channel(b)));
+                    }
+                }
+            },
+            "face" => {
+                let indices = position("vertex_indices").
+                    or_else(|| position("vertex_index")).
+                    ok_or_else(|| ParseError::new(element.line, "faces need a vertex_indices property"))?;
+
+                for index in 0..element.count {
+                    let (line_number, mut values) = body.read(element, index)?;
+                    faces.push((line_number, index, values.swap_remove(indices)));
+                }
+            },
+            _ => {
+                for index in 0..element.count {
+                    body.read(element, index)?;
+                }
+            }
+        }
+    }
+
+    for (line_number, index, face) in faces {
+        if face.len() < 3 {
+            return Err(ParseError::new(line_number, &format!("face {} has fewer than 3 vertices", index)));
+        }
+
+        let vertices: Vec<usize> = face.
+            iter().
+            map(|&v| {
+                if v >= 0.0 && (v as usize) < mesh.vertices.len() {
+                    Ok(v as usize)
+                } else {
+                    Err(ParseError::new(line_number, &format!("face {} refers to missing vertex {}", index, v)))
+                }
+            }).
+            collect::<Result<_, _>>()?;
+
+        for i in 1..vertices.len() - 1 {
+            mesh.triangles.push([vertices[0], vertices[i], vertices[i + 1]]);
+        }
+    }
+
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE_CORNER: &str = "ply\n\
+        format ascii 1.0\n\
+        comment a quad and a triangle\n\
+        element vertex 5\n\
+        property float x\n\
+        property float y\n\
+        property float z\n\
+        property uchar red\n\
+        property uchar green\n\
+        property uchar blue\n\
+        element face 2\n\
+        property list uchar int vertex_indices\n\
+        element edge 1\n\
+        property int vertex1\n\
+        property int vertex2\n\
+        end_header\n\
+        0 0 0 255 0 0\n\
+        1 0 0 0 255 0\n\
+        1 0 1 0 0 255\n\
+        0 0 1 255 255 255\n\
+        0 1 0 0 0 0\n\
+        4 0 1 2 3\n\
+        3 0 4 1\n\
+        0 1\n";
+
+    fn binary_triangle() -> Vec<u8> {
+        let mut data = b"ply\n\
+            format binary_little_endian 1.0\n\
+            element vertex 3\n\
+            property float x\n\
+            property float y\n\
+            property float z\n\
+            property float nx\n\
+            property float ny\n\
+            property float nz\n\
+            element face 1\n\
+            property list uchar uint vertex_index\n\
+            end_header\n".to_vec();
+
+        for p in [[0.0_f32, 1.0, 0.0], [-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]] {
+            for c in p.iter().chain(&[0.0, 0.0, -1.0]) {
+                data.extend(c.to_le_bytes());
+            }
+        }
+
+        data.push(3);
+        for i in [0_u32, 1, 2] {
+            data.extend(i.to_le_bytes());
+        }
+
+        data
+    }
+
+    #[test]
+    fn reading_an_ascii_file() {
+        let mesh = parse_ply(CUBE_CORNER.as_bytes()).unwrap();
+
+        assert_eq!(5, mesh.vertices.len());
+        assert_eq!(Point::new(1.0, 0.0, 1.0), mesh.vertices[2]);
+        assert!(!mesh.has_normals());
+        assert_eq!(vec![[0, 1, 2], [0, 2, 3], [0, 4, 1]], mesh.triangles);
+    }
+
+    #[test]
+    fn reading_vertex_colors() {
+        let mesh = parse_ply(CUBE_CORNER.as_bytes()).unwrap();
+
+        assert_eq!(Color::new(0.0, 1.0, 0.0), mesh.colors[1]);
+        assert_eq!(Color::white(), mesh.colors[3]);
+    }
+
+    #[test]
+    fn reading_signed_and_wide_vertex_colors() {
+        let ply = CUBE_CORNER.
+            replacen("uchar red", "char red", 1).
+            replacen("uchar green", "uint green", 1).
+            replacen("0 0 0 255 0 0", "0 0 0 127 4294967295 0", 1).
+            replacen("1 0 0 0 255 0", "1 0 0 -5 0 0", 1);
+        let mesh = parse_ply(ply.as_bytes()).unwrap();
+
+        assert_eq!(Color::new(1.0, 1.0, 0.0), mesh.colors[0]);
+        assert_eq!(Color::black(), mesh.colors[1]);
+    }
+
+    #[test]
+    fn reading_a_binary_file() {
+        let mesh = parse_ply(&binary_triangle()).unwrap();
+
+        assert_eq!(vec![Point::new(0.0, 1.0, 0.0), Point::new(-1.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0)], mesh.vertices);
+        assert_eq!(vec![Vector::new(0.0, 0.0, -1.0); 3], mesh.normals);
+        assert_eq!(vec![[0, 1, 2]], mesh.triangles);
+        assert!(!mesh.has_colors());
+    }
+
+    #[test]
+    fn loaded_meshes_become_shapes() {
+        let shape = parse_ply(CUBE_CORNER.as_bytes()).unwrap().to_shape(&crate::Material::new());
+        assert_eq!(3, shape.children().len());
+        assert_eq!(Color::new(1.0, 0.0, 0.0), shape.children()[0].material.pattern.color_at(Point::origin()));
+    }
+
+    #[test]
+    fn reporting_malformed_files() {
+        let replace = |from: &str, to: &str| CUBE_CORNER.replacen(from, to, 1);
+        let examples = vec![
+            ("plyx\n".to_string() + &CUBE_CORNER[4..], 1, "not a PLY file"),
+            (replace("format ascii", "format binary_big_endian"), 2, "unsupported format 'binary_big_endian'"),
+            (replace("property float x", "property quad x"), 5, "unknown property type 'quad'"),
+            (replace("0 0 1 255 255 255", "0 0 1 255 x 255"), 20, "invalid number 'x'"),
+            (replace("3 0 4 1", "3 0 5 1"), 23, "face 1 refers to missing vertex 5"),
+            (replace("0 1 0 0 0 0", "0 1 0 0 0"), 21, "too few values for vertex 4"),
+            (replace("end_header", "end_head"), 24, "missing end_header"),
+            (replace("format ascii 1.0\n", ""), 15, "missing format"),
+            (replace("property float y", "property float w"), 4, "vertices need x, y and z properties"),
+            (replace("vertex_indices", "corners"), 11, "faces need a vertex_indices property")
+        ];
+
+        for (input, line, message) in examples {
+            assert_eq!(Err(ParseError::new(line, message)), parse_ply(input.as_bytes()));
+        }
+
+        let mut not_text = CUBE_CORNER.as_bytes().to_vec();
+        not_text[28] = 0xff;
+        assert_eq!(Err(ParseError::new(3, "header is not text")), parse_ply(&not_text));
+
+        let mut truncated = binary_triangle();
+        truncated.pop();
+        assert_eq!(Err(ParseError::new(0, "data ends in face 0")), parse_ply(&truncated));
+    }
+}
@@ -0,0 +1,297 @@
+// Finds the real roots of polynomials up to degree four.
+//
+// Coefficients are given from the highest power down. The closed form
+// solutions follow Schwarze's "Cubic and Quartic Roots" from Graphics Gems,
+// and every root is then polished with a few Newton steps against the
+// original polynomial to recover the precision the closed forms lose.
+// Roots are returned in ascending order, with repeated roots reported once.
+//
+// Every test for zero is made relative to the size of the terms involved,
+// rather than against a fixed epsilon, so that polynomials with very large
+// or very small coefficients, such as those from rays through shapes scaled
+// up or down by a large factor, are solved as reliably as any other.
+
+const EPSILON: f64 = 1e-9;
+
+// Roots further from zero than this are dropped. A leading coefficient that
+// is only nonzero through rounding error gives a root of this sort, so the
+// polynomial is instead solved as one of lower degree.
+const MAX_ROOT: f64 = 1e12;
+
+// Whether `x` is negligible next to `scale`, the size of the terms that
+// were added up to make it.
+fn is_zero(x: f64, scale: f64) -> bool {
+    x.abs() <= EPSILON * scale
+}
+
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    solve(&[a, b, c])
+}
+
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    solve(&[a, b, c, d])
+}
+
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    solve(&[a, b, c, d, e])
+}
+
+// A bound on the size of the roots of a polynomial, from the coefficients
+// relative to the leading one.
+fn root_scale(coefficients: &[f64]) -> f64 {
+    let a = coefficients[0];
+
+    coefficients[1..].
+        iter().
+        enumerate().
+        map(|(i, c)| (c / a).abs().powf(1.0 / (i + 1) as f64)).
+        fold(0.0, f64::max)
+}
+
+fn solve(coefficients: &[f64]) -> Vec<f64> {
+    if coefficients.len() < 2 {
+        return vec![];
+    }
+
+    let a = coefficients[0];
+    let scale = root_scale(coefficients);
+
+    if a == 0.0 || scale.is_nan() || scale > MAX_ROOT {
+        return solve(&coefficients[1..]);
+    }
+
+    let monic: Vec<f64> = coefficients[1..].iter().map(|c| c / a).collect();
+
+    let roots = match monic[..] {
+        [b] => vec![-b],
+        [b, c] => normal_quadratic(b, c),
+        [b, c, d] => normal_cubic(b, c, d),
+        [b, c, d, e] => normal_quartic(b, c, d, e),
+        _ => panic!("only polynomials up to degree four can be solved")
+    };
+
+    polish(coefficients, roots)
+}
+
+// Evaluates a polynomial at `x` using Horner's method.
+pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().fold(0.0, |acc, c| acc * x + c)
+}
+
+// Solves x^2 + bx + c = 0.
+fn normal_quadratic(b: f64, c: f64) -> Vec<f64> {
+    let p = b / 2.0;
+    let discriminant = p * p - c;
+
+    if is_zero(discriminant, (p * p).max(c.abs())) {
+        vec![-p]
+    } else if discriminant < 0.0 {
+        vec![]
+    } else {
+        let sqrt_d = discriminant.sqrt();
+        vec![sqrt_d - p, -sqrt_d - p]
+    }
+}
+
+// Solves x^3 + bx^2 + cx + d = 0.
+fn normal_cubic(b: f64, c: f64, d: f64) -> Vec<f64> {
+    // Substitute x = y - b/3 to eliminate the quadratic term, leaving
+    // y^3 + 3py + 2q = 0.
+    let sq_b = b * b;
+    let p = (-sq_b / 3.0 + c) / 3.0;
+    let q = (2.0 / 27.0 * b * sq_b - b * c / 3.0 + d) / 2.0;
+    let cb_p = p * p * p;
+    let discriminant = q * q + cb_p;
+
+    let scale = root_scale(&[1.0, b, c, d]);
+
+    let roots = if is_zero(discriminant, (q * q).max(cb_p.abs())) {
+        if is_zero(q, scale * scale * scale) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if discriminant < 0.0 {
+        // Three real roots, found trigonometrically.
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        let third = std::f64::consts::PI / 3.0;
+
+        vec![t * phi.cos(), -t * (phi + third).cos(), -t * (phi - third).cos()]
+    } else {
+        let sqrt_d = discriminant.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    roots.iter().map(|y| y - b / 3.0).collect()
+}
+
+// Solves x^4 + bx^3 + cx^2 + dx + e = 0.
+fn normal_quartic(b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    // Substitute x = y - b/4 to eliminate the cubic term, leaving
+    // y^4 + py^2 + qy + r = 0.
+    let sq_b = b * b;
+    let p = -3.0 / 8.0 * sq_b + c;
+    let q = sq_b * b / 8.0 - b * c / 2.0 + d;
+    let r = -3.0 / 256.0 * sq_b * sq_b + sq_b * c / 16.0 - b * d / 4.0 + e;
+
+    let scale = root_scale(&[1.0, b, c, d, e]);
+
+    let roots = if is_zero(r, scale.powi(4)) {
+        // No absolute term, so y(y^3 + py + q) = 0.
+        let mut roots = normal_cubic(0.0, p, q);
+        roots.push(0.0);
+        roots
+    } else {
+        // Split the quartic into two quadratics using any real root of the
+        // resolvent cubic.
+        let z = normal_cubic(-p / 2.0, -r, r * p / 2.0 - q * q / 8.0)[0];
+
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+
+        let u = if is_zero(u, (z * z).max(r.abs())) { 0.0 } else if u > 0.0 { u.sqrt() } else { return vec![] };
+        let v = if is_zero(v, (2.0 * z).abs().max(p.abs())) { 0.0 } else if v > 0.0 { v.sqrt() } else { return vec![] };
+        let v = if q < 0.0 { -v } else { v };
+
+        let mut roots = normal_quadratic(v, z - u);
+        roots.extend(normal_quadratic(-v, z + u));
+        roots
+    };
+
+    roots.iter().map(|y| y - b / 4.0).collect()
+}
+
+// Refines each root with Newton's method, then sorts them and drops
+// duplicates.
+fn polish(coefficients: &[f64], roots: Vec<f64>) -> Vec<f64> {
+    let degree = coefficients.len() - 1;
+    let derivative: Vec<f64> = coefficients[..degree].
+        iter().
+        enumerate().
+        map(|(i, c)| c * (degree - i) as f64).
+        collect();
+
+    let mut roots: Vec<f64> = roots.
+        into_iter().
+        map(|mut x| {
+            for _ in 0..4 {
+                let slope = evaluate(&derivative, x);
+
+                if slope == 0.0 {
+                    break;
+                }
+
+                let step = evaluate(coefficients, x) / slope;
+                if !step.is_finite() {
+                    break;
+                }
+
+                x -= step;
+            }
+            x
+        }).
+        collect();
+
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    roots.dedup_by(|a, b| (*a - *b).abs() < 1e-7);
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(expected: &[f64], actual: Vec<f64>) {
+        assert_eq!(expected.len(), actual.len(), "roots were {:?}", actual);
+
+        for (e, a) in expected.iter().zip(actual) {
+            assert!((e - a).abs() < 1e-6, "expected {} but got {}", e, a);
+        }
+    }
+
+    fn assert_relative_roots(expected: &[f64], actual: Vec<f64>) {
+        assert_eq!(expected.len(), actual.len(), "roots were {:?}", actual);
+
+        for (e, a) in expected.iter().zip(actual) {
+            assert!((e - a).abs() < 1e-6 * e.abs(), "expected {} but got {}", e, a);
+        }
+    }
+
+    #[test]
+    fn evaluating_a_polynomial() {
+        assert_eq!(13.0, evaluate(&[2.0, -3.0, 1.0, 7.0], 2.0));
+    }
+
+    #[test]
+    fn solving_a_linear_equation_as_a_degenerate_quadratic() {
+        assert_roots(&[2.0], solve_quadratic(0.0, 2.0, -4.0));
+    }
+
+    #[test]
+    fn dropping_a_leading_coefficient_left_by_rounding_error() {
+        assert_roots(&[2.0], solve_quadratic(1e-17, 2.0, -4.0));
+    }
+
+    #[test]
+    fn solving_polynomials_with_small_coefficients() {
+        // 1e-10 (x - 1e5)(x - 3e5), as from a ray through a quadric scaled
+        // up by 1e5.
+        assert_relative_roots(&[1e5, 3e5], solve_quadratic(1e-10, -4e-5, 3.0));
+        // 1e-20 (x - 1e5)(x - 2e5)(x - 3e5)(x - 4e5)
+        assert_relative_roots(&[1e5, 2e5, 3e5, 4e5], solve_quartic(1e-20, -1e-14, 3.5e-9, -5e-4, 24.0));
+    }
+
+    #[test]
+    fn solving_polynomials_with_tiny_roots() {
+        // 1e10 (x - 1e-5)(x - 3e-5)
+        assert_relative_roots(&[1e-5, 3e-5], solve_quadratic(1e10, -4e5, 3.0));
+        // (x - 1e-3)(x - 2e-3)(x - 3e-3)
+        assert_relative_roots(&[1e-3, 2e-3, 3e-3], solve_cubic(1.0, -6e-3, 11e-6, -6e-9));
+    }
+
+    #[test]
+    fn solving_quadratics() {
+        assert_roots(&[-3.0, 2.0], solve_quadratic(1.0, 1.0, -6.0));
+        assert_roots(&[1.0], solve_quadratic(2.0, -4.0, 2.0));
+        assert_roots(&[], solve_quadratic(1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn solving_cubics() {
+        assert_roots(&[1.0, 2.0, 3.0], solve_cubic(1.0, -6.0, 11.0, -6.0));
+        assert_roots(&[-1.0, 2.0], solve_cubic(1.0, -3.0, 0.0, 4.0));
+        assert_roots(&[1.0], solve_cubic(1.0, 0.0, 1.0, -2.0));
+        assert_roots(&[0.0], solve_cubic(1.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn solving_quartics() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(&[1.0, 2.0, 3.0, 4.0], solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0));
+        // (x^2 - 4)(x^2 + 1)
+        assert_roots(&[-2.0, 2.0], solve_quartic(1.0, 0.0, -3.0, 0.0, -4.0));
+        // x(x - 1)(x + 2)(x - 5)
+        assert_roots(&[-2.0, 0.0, 1.0, 5.0], solve_quartic(1.0, -4.0, -7.0, 10.0, 0.0));
+        // x^4 + 1 has no real roots.
+        assert_roots(&[], solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn solving_a_quartic_with_a_leading_scale() {
+        assert_roots(&[1.0, 2.0, 3.0, 4.0], solve_quartic(3.0, -30.0, 105.0, -150.0, 72.0));
+    }
+
+    #[test]
+    fn solving_a_quartic_with_widely_spread_roots() {
+        // (x - 0.001)(x - 1)(x - 100)(x - 1000)
+        let roots = [0.001, 1.0, 100.0, 1000.0];
+        let b = -(0.001 + 1.0 + 100.0 + 1000.0);
+        let c = 0.001 + 0.1 + 1.0 + 100.0 + 1000.0 + 100000.0;
+        let d = -(0.1 + 1.0 + 100.0 + 100000.0);
+        let e = 100.0;
+
+        assert_roots(&roots, solve_quartic(1.0, b, c, d, e));
+    }
+}
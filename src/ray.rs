@@ -5,12 +5,21 @@ use crate::matrix::Matrix4x4;
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Ray {
     pub origin: Point,
-    pub direction: Vector
+    pub direction: Vector,
+    // When the ray was cast, for shapes that move.
+    pub time: f64
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vector) -> Ray {
-        Ray {origin, direction}
+        Ray {origin, direction, time: 0.0}
+    }
+
+    pub fn time(self, time: f64) -> Ray {
+        Ray {
+            time,
+            ..self
+        }
     }
 
     pub fn position(&self, t: f64) -> Point {
@@ -20,7 +29,8 @@ impl Ray {
     pub fn transform(&self, m: Matrix4x4) -> Ray {
         Ray{
             origin: m * self.origin,
-            direction: m * self.direction
+            direction: m * self.direction,
+            time: self.time
         }
     }
 }
@@ -40,6 +50,19 @@ mod tests {
         assert_eq!(Point::new(4.5, 3.0, 4.0), r.position(2.5));
     }
 
+    #[test]
+    fn rays_are_cast_at_time_zero_unless_given_a_time() {
+        let r = Ray::new(Point::new(1.0, 2.0, 3.0), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(0.0, r.time);
+        assert_eq!(0.5, r.time(0.5).time);
+    }
+
+    #[test]
+    fn transforming_a_ray_keeps_its_time() {
+        let r = Ray::new(Point::new(1.0, 2.0, 3.0), Vector::new(0.0, 1.0, 0.0)).time(0.25);
+        assert_eq!(0.25, r.transform(Matrix4x4::identity().translate(3.0, 4.0, 5.0)).time);
+    }
+
     #[test]
     fn translating_a_ray() {
         let r = Ray::new(Point::new(1.0, 2.0, 3.0), Vector::new(0.0, 1.0, 0.0));
@@ -0,0 +1,295 @@
+// Reading STL models, as exported by most CAD packages.
+//
+// STL files list each triangle separately with its own copy of its
+// vertices, in either a text or a binary encoding, so the triangles are
+// welded back together at shared vertices once read. Each triangle also
+// carries a facet normal, which is used to wind it consistently and is
+// otherwise discarded; smooth normals can be computed from the welded mesh
+// instead.
+
+use crate::geom::Mesh;
+use crate::{ParseError, Point, Vector};
+
+// Binary files start with an 80 byte header and a count of triangles,
+// followed by 50 bytes for each triangle.
+const BINARY_HEADER: usize = 84;
+const BINARY_TRIANGLE: usize = 50;
+
+// Reads a welded mesh from either kind of STL file. With a `crease_angle`,
+// in radians, the mesh is given smooth normals everywhere except along
+// edges sharper than that angle.
+pub fn parse_stl(input: &[u8], crease_angle: Option<f64>) -> Result<Mesh, ParseError> {
+    let triangles = if is_binary(input) {
+        parse_binary(input)?
+    } else {
+        let text = std::str::from_utf8(input).map_err(|_| ParseError::new(1, "not an STL file"))?;
+        parse_ascii(text)?
+    };
+
+    let mut mesh = Mesh::new();
+
+    for (normal, [p1, p2, p3]) in triangles {
+        let start = mesh.vertices.len();
+        mesh.vertices.extend([p1, p2, p3]);
+
+        // Facet normals follow the right hand rule, so a triangle whose own
+        // normal disagrees is wound the other way.
+        if (p3 - p1).cross(p2 - p1).dot(normal) < 0.0 {
+            mesh.triangles.push([start, start + 2, start + 1]);
+        } else {
+            mesh.triangles.push([start, start + 1, start + 2]);
+        }
+    }
+
+    let mesh = mesh.weld();
+
+    Ok(match crease_angle {
+        None => mesh,
+        Some(angle) => mesh.smooth(angle)
+    })
+}
+
+// Text files start with "solid", but so do some binary ones, so a file
+// whose size matches the triangle count in its header is taken as binary.
+fn is_binary(input: &[u8]) -> bool {
+    if input.len() < BINARY_HEADER {
+        return false;
+    }
+
+    let count = triangle_count(input);
+    binary_size(count) == Some(input.len() - BINARY_HEADER) || !input.starts_with(b"solid")
+}
+
+fn triangle_count(input: &[u8]) -> usize {
+    u32::from_le_bytes([input[80], input[81], input[82], input[83]]) as usize
+}
+
+// The size of the triangle data, or None if the count in the header is too
+// large for it to fit in memory at all.
+fn binary_size(count: usize) -> Option<usize> {
+    count.checked_mul(BINARY_TRIANGLE)
+}
+
+fn parse_binary(input: &[u8]) -> Result<Vec<(Vector, [Point; 3])>, ParseError> {
+    let count = triangle_count(input);
+    let data = &input[BINARY_HEADER..];
+
+    if binary_size(count).is_none_or(|size| data.len() < size) {
+        return Err(ParseError::new(0, &format!("expected {} triangles but found {}", count, data.len() / BINARY_TRIANGLE)));
+    }
+
+    let float = |bytes: &[u8], i: usize| {
+        f32::from_le_bytes([bytes[i * 4], bytes[i * 4 + 1], bytes[i * 4 + 2], bytes[i * 4 + 3]]) as f64
+    };
+
+    Ok(data.
+        chunks_exact(BINARY_TRIANGLE).
+        take(count).
+        map(|t| {
+            let normal = Vector::new(float(t, 0), float(t, 1), float(t, 2));
+            let point = |i: usize| Point::new(float(t, i), float(t, i + 1), float(t, i + 2));
+            (normal, [point(3), point(6), point(9)])
+        }).
+        collect())
+}
+
+type Lines<'a> = dyn Iterator<Item = (usize, Vec<&'a str>)> + 'a;
+
+fn parse_ascii(input: &str) -> Result<Vec<(Vector, [Point; 3])>, ParseError> {
+    let last_line = input.lines().count();
+    let lines = &mut input.
+        lines().
+        enumerate().
+        map(|(i, line)| (i + 1, line.split_whitespace().collect::<Vec<&str>>())).
+        filter(|(_, words)| !words.is_empty());
+
+    match lines.next() {
+        Some((_, words)) if words[0] == "solid" => (),
+        _ => return Err(ParseError::new(1, "not an STL file"))
+    }
+
+    let mut triangles = vec![];
+
+    loop {
+        match lines.next() {
+            Some((_, words)) if words[0] == "endsolid" => break,
+            Some((line_number, words)) if words[0] == "facet" => {
+                let n = match &words[..] {
+                    ["facet", "normal", numbers @ ..] if numbers.len() == 3 => parse_numbers(line_number, numbers)?,
+                    _ => return Err(ParseError::new(line_number, "expected 'facet normal' and 3 numbers"))
+                };
+
+                expect(lines, last_line, &["outer", "loop"], 0)?;
+                let p1 = expect(lines, last_line, &["vertex"], 3)?;
+                let p2 = expect(lines, last_line, &["vertex"], 3)?;
+                let p3 = expect(lines, last_line, &["vertex"], 3)?;
+                expect(lines, last_line, &["endloop"], 0)?;
+                expect(lines, last_line, &["endfacet"], 0)?;
+
+                let point = |p: Vec<f64>| Point::new(p[0], p[1], p[2]);
+                triangles.push((Vector::new(n[0], n[1], n[2]), [point(p1), point(p2), point(p3)]));
+            },
+            Some((line_number, words)) =>
+                return Err(ParseError::new(line_number, &format!("unexpected '{}'", words[0]))),
+            None => return Err(ParseError::new(last_line, "expected 'endsolid'"))
+        }
+    }
+
+    Ok(triangles)
+}
+
+// Reads the next line, which should start with `keyword` and be followed by
+// `count` numbers.
+fn expect(lines: &mut Lines, last_line: usize, keyword: &[&str], count: usize) -> Result<Vec<f64>, ParseError> {
+    let missing = |line_number| ParseError::new(line_number, &format!("expected '{}'", keyword.join(" ")));
+    let (line_number, words) = lines.next().ok_or_else(|| missing(last_line))?;
+
+    if words.len() < keyword.len() || words[..keyword.len()] != *keyword {
+        return Err(missing(line_number));
+    }
+
+    let numbers = &words[keyword.len()..];
+
+    if numbers.len() != count {
+        return Err(ParseError::new(line_number, &format!("expected {} numbers but found {}", count, numbers.len())));
+    }
+
+    parse_numbers(line_number, numbers)
+}
+
+fn parse_numbers(line_number: usize, numbers: &[&str]) -> Result<Vec<f64>, ParseError> {
+    numbers.
+        iter().
+        map(|s| s.parse::<f64>().map_err(|_| ParseError::new(line_number, &format!("invalid number '{}'", s)))).
+        collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    // Two faces of a unit cube meeting at a right angle along the z axis.
+    const CORNER: &str = "solid corner\n\
+        facet normal 0 0 -1\n\
+          outer loop\n\
+            vertex 0 0 0\n\
+            vertex 0 1 0\n\
+            vertex 1 0 0\n\
+          endloop\n\
+        endfacet\n\
+        facet normal 0 0 -1\n\
+          outer loop\n\
+            vertex 1 0 0\n\
+            vertex 0 1 0\n\
+            vertex 1 1 0\n\
+          endloop\n\
+        endfacet\n\
+        facet normal -1 0 0\n\
+          outer loop\n\
+            vertex 0 0 0\n\
+            vertex 0 0 1\n\
+            vertex 0 1 0\n\
+          endloop\n\
+        endfacet\n\
+        endsolid corner\n";
+
+    fn binary(triangles: &[(Vector, [Point; 3])]) -> Vec<u8> {
+        let mut data = b"solid but really binary".to_vec();
+        data.resize(80, 0);
+        data.extend((triangles.len() as u32).to_le_bytes());
+
+        for (n, points) in triangles {
+            let mut floats = vec![n.x, n.y, n.z];
+
+            for p in points {
+                floats.extend([p.x, p.y, p.z]);
+            }
+
+            for f in floats {
+                data.extend((f as f32).to_le_bytes());
+            }
+
+            data.extend([0, 0]);
+        }
+
+        data
+    }
+
+    #[test]
+    fn reading_an_ascii_file() {
+        let mesh = parse_stl(CORNER.as_bytes(), None).unwrap();
+
+        assert_eq!(5, mesh.vertices.len());
+        assert_eq!(3, mesh.triangles.len());
+        assert!(!mesh.has_normals());
+        assert_eq!(mesh.triangles[0][0], mesh.triangles[2][0]);
+    }
+
+    #[test]
+    fn triangles_are_wound_to_match_their_facet_normals() {
+        let mesh = parse_stl(CORNER.as_bytes(), None).unwrap();
+
+        assert_eq!(Vector::new(0.0, 0.0, -1.0), mesh.face_normal(mesh.triangles[0]).normalize());
+        assert_eq!(Vector::new(-1.0, 0.0, 0.0), mesh.face_normal(mesh.triangles[2]).normalize());
+    }
+
+    #[test]
+    fn reading_a_binary_file() {
+        let triangle = (
+            Vector::new(0.0, 0.0, 1.0),
+            [Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0)]
+        );
+        let mesh = parse_stl(&binary(&[triangle]), None).unwrap();
+
+        assert_eq!(triangle.1.to_vec(), mesh.vertices);
+        assert_eq!(Vector::new(0.0, 0.0, 1.0), mesh.face_normal(mesh.triangles[0]).normalize());
+    }
+
+    #[test]
+    fn smoothing_with_a_crease_angle() {
+        let sharp = parse_stl(CORNER.as_bytes(), Some(PI / 4.0)).unwrap();
+        let origin = |mesh: &Mesh| mesh.triangles[2][0];
+
+        assert_eq!(Vector::new(-1.0, 0.0, 0.0), sharp.normals[origin(&sharp)]);
+        assert_eq!(Vector::new(0.0, 0.0, -1.0), sharp.normals[sharp.triangles[0][0]]);
+
+        let smooth = parse_stl(CORNER.as_bytes(), Some(PI * 0.6)).unwrap();
+        assert_eq!(5, smooth.vertices.len());
+        assert_eq!(Vector::new(-1.0, 0.0, -1.0).normalize(), smooth.normals[origin(&smooth)]);
+    }
+
+    #[test]
+    fn reporting_malformed_files() {
+        let examples = vec![
+            (CORNER.replacen("vertex 0 1 0", "vertex 0 1", 1), 5, "expected 3 numbers but found 2"),
+            (CORNER.replacen("vertex 1 0 0", "vertex 1 x 0", 1), 6, "invalid number 'x'"),
+            (CORNER.replacen("endloop", "endlop", 1), 7, "expected 'endloop'"),
+            (CORNER.replacen("facet normal 0 0 -1", "facet 0 0 -1", 1), 2, "expected 'facet normal' and 3 numbers"),
+            (CORNER.replacen("endsolid corner\n", "", 1), 22, "expected 'endsolid'"),
+            ("hello".to_string(), 1, "not an STL file")
+        ];
+
+        for (input, line, message) in examples {
+            assert_eq!(Err(ParseError::new(line, message)), parse_stl(input.as_bytes(), None));
+        }
+
+        let mut truncated = binary(&[(Vector::new(0.0, 0.0, 1.0), [Point::origin(); 3])]);
+        truncated[80] = 2;
+        assert_eq!(Err(ParseError::new(1, "not an STL file")), parse_stl(&truncated, None));
+
+        // Without "solid" at the start there's no doubt that it's binary.
+        truncated[0] = b'S';
+        assert_eq!(Err(ParseError::new(0, "expected 2 triangles but found 1")), parse_stl(&truncated, None));
+
+        // A count too large to fit in memory is never taken as binary.
+        let mut huge = binary(&[(Vector::new(0.0, 0.0, 1.0), [Point::origin(); 3])]);
+        huge[80..84].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(!is_binary(&huge));
+
+        huge[0] = b'S';
+        assert_eq!(
+            Err(ParseError::new(0, &format!("expected {} triangles but found 1", u32::MAX))),
+            parse_stl(&huge, None));
+    }
+}
@@ -1,6 +1,11 @@
 use crate::{Color, Point, PointLight, Shape, Material, Matrix4x4, Intersection, Ray};
+use crate::geom::Medium;
 use crate::intersection;
 
+// The number of points along each stretch of a ray through a volume at
+// which the light scattered towards the eye is gathered.
+const VOLUME_SAMPLES: usize = 16;
+
 #[derive(Debug)]
 pub struct World {
     pub light: PointLight,
@@ -27,15 +32,51 @@ impl World {
         }
     }
 
+    // Any volumes the ray passes through before reaching a surface dim it
+    // and add light of their own.
     pub fn color_at(&self, ray: Ray) -> Color {
         let intersections = self.intersect(&ray);
-
-        match intersection::hit(&intersections) {
+        let hit = intersection::hit(&intersections);
+        let surface = match hit {
             None => Color::black(),
-            Some(hit) => hit.lighting(self.light, self.is_shadowed(hit.over_point()))
+            Some(hit) => self.shade_hit(&hit)
+        };
+
+        let media = self.media(&ray);
+
+        if media.is_empty() {
+            return surface;
         }
+
+        let distance = hit.map_or(f64::INFINITY, |hit| hit.t);
+        let length = ray.direction.magnitude();
+
+        self.in_scattering(&ray, &media, distance) +
+            surface * (-optical_depth(&media, distance, length)).exp()
     }
 
+    fn shade_hit(&self, hit: &Intersection) -> Color {
+        let point = hit.over_point();
+        let time = hit.ray.time;
+
+        if self.is_shadowed_at(point, time) {
+            return hit.lighting(self.light, true);
+        }
+
+        let lit = hit.lighting(self.light, false);
+        let transmittance = self.transmittance_to_light(point, time);
+
+        if transmittance < 1.0 {
+            // Only the direct light is dimmed by volumes on the way to the
+            // light, not the ambient.
+            let ambient = hit.lighting(self.light, true);
+            ambient + (lit - ambient) * transmittance
+        } else {
+            lit
+        }
+    }
+
+    #[allow(mismatched_lifetime_syntaxes)]
     pub fn intersect<'a>(&'a self, r: &'a Ray) -> Vec<Intersection> {
         let mut intersections: Vec<Intersection> = Vec::new();
 
@@ -47,10 +88,70 @@ impl World {
         intersections
     }
 
+    pub fn media<'a>(&'a self, r: &'a Ray) -> Vec<(f64, f64, &'a Medium)> {
+        self.objects.
+            iter().
+            flat_map(|object| object.media(r)).
+            collect()
+    }
+
+    // The fraction of the light that reaches `p` through any volumes in the
+    // way, ignoring surfaces.
+    pub fn transmittance_to_light(&self, p: Point, time: f64) -> f64 {
+        let point_to_light = self.light.position - p;
+        let distance_to_light = point_to_light.magnitude();
+        let ray_to_light = Ray::new(p, point_to_light / distance_to_light).time(time);
+
+        (-optical_depth(&self.media(&ray_to_light), distance_to_light, 1.0)).exp()
+    }
+
+    // Gathers the light scattered towards the eye by the media along the
+    // ray, up to `distance`, at evenly spaced points in each medium.
+    fn in_scattering(&self, ray: &Ray, media: &[(f64, f64, &Medium)], distance: f64) -> Color {
+        let length = ray.direction.magnitude();
+        let direction = ray.direction / length;
+        let mut color = Color::black();
+
+        for (t0, t1, medium) in media {
+            let t1 = t1.min(distance);
+
+            if t1 <= *t0 {
+                continue;
+            }
+
+            let step = (t1 - t0) / VOLUME_SAMPLES as f64;
+
+            for i in 0..VOLUME_SAMPLES {
+                let t = t0 + (i as f64 + 0.5) * step;
+                let p = ray.position(t);
+
+                if self.is_shadowed_at(p, ray.time) {
+                    continue;
+                }
+
+                // The light travels from the light to p, then back along
+                // the ray to the eye.
+                let cos_theta = (p - self.light.position).normalize().dot(-direction);
+                let scattered = medium.density * medium.phase.value(cos_theta) * step * length;
+                let reaching = self.transmittance_to_light(p, ray.time) * (-optical_depth(media, t, length)).exp();
+
+                color = color + self.light.intensity * medium.albedo * (scattered * reaching);
+            }
+        }
+
+        color
+    }
+
     pub fn is_shadowed(&self, p: Point) -> bool {
+        self.is_shadowed_at(p, 0.0)
+    }
+
+    // Whether `p` is in shadow at the given time, when moving objects may
+    // have moved into or out of the way.
+    pub fn is_shadowed_at(&self, p: Point, time: f64) -> bool {
         let point_to_light = self.light.position - p;
         let distance_to_light = point_to_light.magnitude();
-        let ray_to_light = Ray::new(p, point_to_light.normalize());
+        let ray_to_light = Ray::new(p, point_to_light.normalize()).time(time);
 
         match intersection::hit(&self.intersect(&ray_to_light)) {
             None => false,
@@ -61,10 +162,21 @@ impl World {
     }
 }
 
+// How much the media dim a ray over its first `distance`, where `length`
+// is the length of the ray's direction, converting t into world units.
+fn optical_depth(media: &[(f64, f64, &Medium)], distance: f64, length: f64) -> f64 {
+    media.
+        iter().
+        map(|(t0, t1, medium)| medium.density * (t1.min(distance) - t0).max(0.0) * length).
+        sum()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{Ray, Point, Vector, Intersection};
+    use crate::geom::{CsgOperation, Medium};
+    use std::sync::Arc;
 
     #[test]
     fn intersect_world() {
@@ -124,6 +236,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn no_shadow_when_nothing_between_point_and_light() {
         let w = World::default_world();
         let p = Point::new(0.0, 10.0, 0.0);
@@ -131,9 +244,126 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn shadow_when_there_is_object_between_point_and_light() {
         let w = World::default_world();
         let p = Point::new(10.0, -10.0, 10.0);
         assert_eq!(true, w.is_shadowed(p));
     }
+
+    #[test]
+    fn color_when_a_ray_hits_a_grouped_object() {
+        let w = World::default_world();
+        let w = World {
+            objects: vec![Shape::group(w.objects)],
+            ..w
+        };
+
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(Color::new(0.38066, 0.47583, 0.2855), w.color_at(r));
+    }
+
+    #[test]
+    fn light_passes_through_a_hole_cut_with_csg() {
+        let wall = Shape::csg(
+            CsgOperation::Difference,
+            Shape::cube().transform(Matrix4x4::identity().scale(5.0, 0.1, 5.0)),
+            Shape::cylinder(-1.0, 1.0, true)).
+            transform(Matrix4x4::identity().translate(0.0, 3.0, 0.0));
+        let w = World {
+            light: PointLight::new(Point::new(0.0, 10.0, 0.0), Color::white()),
+            objects: vec![Shape::plane(), wall]
+        };
+
+        assert!(!w.is_shadowed(Point::new(0.2, 0.0, 0.2)));
+        assert!(w.is_shadowed(Point::new(3.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn cube_casts_a_shadow() {
+        let w = World {
+            light: PointLight::new(Point::new(0.0, 10.0, 0.0), Color::white()),
+            objects: vec![
+                Shape::plane(),
+                Shape::cube().transform(Matrix4x4::identity().translate(0.0, 3.0, 0.0))
+            ]
+        };
+
+        assert!(w.is_shadowed(Point::new(0.5, 0.0, -0.5)));
+        assert!(!w.is_shadowed(Point::new(2.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn color_when_a_ray_hits_an_instance_with_its_own_material() {
+        let w = World::default_world();
+        let material = w.objects[0].material.clone().ambient(1.0);
+        let w = World {
+            objects: vec![Shape::instance(Arc::new(w.objects[0].clone())).material(material)],
+            ..w
+        };
+
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(Color::new(1.10066, 1.37583, 0.8255), w.color_at(r));
+    }
+
+    #[test]
+    fn moving_objects_cast_shadows_at_the_time_of_the_ray() {
+        let w = World {
+            light: PointLight::new(Point::new(0.0, 10.0, 0.0), Color::white()),
+            objects: vec![
+                Shape::plane(),
+                Shape::sphere().
+                    transform(Matrix4x4::identity().translate(0.0, 3.0, 0.0)).
+                    motion(Matrix4x4::identity().translate(5.0, 3.0, 0.0))
+            ]
+        };
+
+        assert!(w.is_shadowed_at(Point::origin(), 0.0));
+        assert!(!w.is_shadowed_at(Point::origin(), 1.0));
+        assert!(!w.is_shadowed_at(Point::new(6.0, 0.0, 0.0), 0.0));
+        assert!(w.is_shadowed_at(Point::new(6.0, 0.0, 0.0), 1.0));
+    }
+
+    #[test]
+    fn fog_dims_the_surfaces_behind_it() {
+        let mut w = World::default_world();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let clear = w.color_at(r);
+
+        // Absorbs without scattering, across z = -4 to -3.
+        w.objects.push(Shape::volume(
+            Shape::cube().transform(Matrix4x4::identity().scale(0.5, 0.5, 0.5).translate(0.0, 0.0, -3.5)),
+            Medium::new(0.5).albedo(Color::black())));
+
+        assert_eq!(clear * (-0.5_f64).exp(), w.color_at(r));
+    }
+
+    #[test]
+    fn fog_scatters_light_towards_the_eye() {
+        let w = World {
+            light: PointLight::new(Point::new(0.0, 10.0, 0.0), Color::white()),
+            objects: vec![Shape::volume(Shape::sphere(), Medium::new(1.0).albedo(Color::new(1.0, 0.0, 0.0)))]
+        };
+        let c = w.color_at(Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)));
+
+        assert!(c.r > 0.01);
+        assert_eq!(0.0, c.g);
+        assert_eq!(0.0, c.b);
+        assert_eq!(Color::black(), w.color_at(Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 1.0, 0.0))));
+    }
+
+    #[test]
+    fn fog_dims_the_light_reaching_a_point() {
+        let w = World {
+            light: PointLight::new(Point::new(0.0, 10.0, 0.0), Color::white()),
+            objects: vec![
+                Shape::volume(
+                    Shape::cube().transform(Matrix4x4::identity().scale(1.0, 0.5, 1.0).translate(0.0, 5.0, 0.0)),
+                    Medium::new(2.0))
+            ]
+        };
+
+        assert!((w.transmittance_to_light(Point::origin(), 0.0) - (-2.0_f64).exp()).abs() < 0.0001);
+        assert_eq!(1.0, w.transmittance_to_light(Point::new(5.0, 0.0, 0.0), 0.0));
+    }
 }
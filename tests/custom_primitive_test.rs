@@ -0,0 +1,73 @@
+use ray_tracer::geom::{Bounds, Primitive};
+use ray_tracer::{Color, Material, Matrix4x4, Point, Ray, Shape, Vector, World};
+
+// A unit sphere written outside the crate, to check that user-defined
+// primitives behave like the built in shapes.
+#[derive(Debug)]
+struct Ball;
+
+impl Primitive for Ball {
+    fn intersects(&self, ray: &Ray) -> Vec<f64> {
+        let to_ray = ray.origin - Point::origin();
+        let a = ray.direction.dot(ray.direction);
+        let b = 2.0 * ray.direction.dot(to_ray);
+        let c = to_ray.dot(to_ray) - 1.0;
+        let discriminant = b * b - 4.0 * a * c;
+
+        if discriminant < 0.0 {
+            return vec![];
+        }
+
+        vec![(-b - discriminant.sqrt()) / (2.0 * a), (-b + discriminant.sqrt()) / (2.0 * a)]
+    }
+
+    fn normal_at(&self, point: Point) -> Vector {
+        point - Point::origin()
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0))
+    }
+}
+
+#[test]
+fn intersecting_a_transformed_primitive() {
+    let s = Shape::primitive(Ball).transform(Matrix4x4::identity().translate(0.0, 0.0, 1.0));
+    let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let xs = s.intersects(&r);
+
+    assert_eq!(2, xs.len());
+    assert_eq!(5.0, xs[0].t);
+    assert_eq!(7.0, xs[1].t);
+    assert_eq!(Vector::new(0.0, 0.0, -1.0), xs[0].normal());
+    assert_eq!(Vector::new(0.0, 0.0, -1.0), s.normal_at(Point::new(0.0, 0.0, 0.0)));
+}
+
+#[test]
+fn primitives_shade_like_built_in_shapes() {
+    let material = Material::new().color(Color::new(0.8, 1.0, 0.6)).diffuse(0.7).specular(0.2);
+    let r = Ray::new(Point::new(0.3, 0.2, -5.0), Vector::new(0.0, 0.0, 1.0));
+    let mut w = World::default_world();
+    let expected = w.color_at(r);
+
+    w.objects[0] = Shape::primitive(Ball).material(material);
+    assert_eq!(expected, w.color_at(r));
+}
+
+#[test]
+fn primitives_cast_shadows() {
+    let mut w = World::default_world();
+    w.objects = vec![Shape::primitive(Ball).transform(Matrix4x4::identity().translate(-5.0, 5.0, -5.0))];
+
+    assert!(w.is_shadowed(Point::new(0.0, 0.0, 0.0)));
+    assert!(!w.is_shadowed(Point::new(0.0, 0.0, 10.0)));
+}
+
+#[test]
+fn groups_cull_rays_with_the_bounds_of_a_primitive() {
+    let g = Shape::group(vec![Shape::primitive(Ball)]);
+
+    assert_eq!(Point::new(-1.0, -1.0, -1.0), g.world_bounds().min);
+    assert!(g.intersects(&Ray::new(Point::new(2.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0))).is_empty());
+    assert_eq!(2, g.intersects(&Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0))).len());
+}